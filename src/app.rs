@@ -1,39 +1,94 @@
+use std::collections::HashMap;
+use std::fs;
 use std::io;
-use std::path::{Path, PathBuf};
-use crossterm::terminal;
+use std::path::PathBuf;
+use std::time::Instant;
+use chrono::Utc;
+use crossterm::event;
 use grus_lib::Store;
+use crate::avc::AgendaViewController;
+use crate::clock::Clock;
+use crate::cvc::ConflictViewController;
+use crate::ical::{self, Event, Target};
+use crate::config::Config;
+use crate::history::{Checkpoint, History};
+use crate::keymap::{Keymap, AGENDA_DEFAULTS, CONFLICT_DEFAULTS, REPORT_DEFAULTS, SESSION_DEFAULTS, TREE_DEFAULTS, WEEK_DEFAULTS};
+use crate::meta::Meta;
+use crate::node::TimeFormat;
+use crate::recurrence;
+use crate::remind::{self, Notifier};
+use crate::snapshot::Snapshot;
+use crate::rvc::ReportViewController;
 use crate::svc::SessionViewController;
+use crate::sync;
 use crate::tvc::TreeViewController;
+use crate::wvc::WeekViewController;
 use crate::ui::{BufPrint, Screen, SessionViewMode};
 
-pub struct Application {
+pub struct Application<'c> {
+	/// The clock which every view resolves and displays dates against.
+	pub clock: &'c dyn Clock,
 	pub store: Store,
 	pub store_args: StoreArgs,
+	pub meta: Meta,
+	pub history: History,
 	pub screen: Screen,
-	pub tvc: TreeViewController,
-	pub svc: SessionViewController,
+	pub tvc: TreeViewController<'c>,
+	pub svc: SessionViewController<'c>,
+	pub cvc: ConflictViewController<'c>,
+	pub avc: AgendaViewController<'c>,
+	pub wvc: WeekViewController<'c>,
+	pub rvc: ReportViewController,
 	pub view: View,
+	pub time_format: TimeFormat,
+	pub notifier: Notifier,
+	/// When reminders were last looked for.
+	pub reminded: Instant,
 }
 
-impl Application {
-	pub fn init<P: AsRef<Path>>(path: P, n_roots: usize, export_path: P) -> Result<Self, Error> {
-		let store_args = StoreArgs { n_roots, path: path.as_ref().into(), export_path: export_path.as_ref().into() };
-		let store = Store::open(path, n_roots)?;
+impl<'c> Application<'c> {
+	pub fn init(store_args: StoreArgs, config: &Config, clock: &'c dyn Clock) -> Result<Self, Error> {
+		let keys = &config.keys;
+		let store = store_args.open()?;
+		let mut meta = Meta::load(&store_args)?;
+		if recurrence::advance(&store, &mut meta, clock.now())? { meta.save()? };
 		let screen = Screen::new()?;
-		let tvc = TreeViewController::new(&store)?;
-		let svc = SessionViewController::new(&store)?;
+		let size = screen.size()?;
+		let tvc = TreeViewController::new(&store, &meta, clock, Keymap::new(TREE_DEFAULTS, &keys.tree), config, size)?;
+		let svc = SessionViewController::new(&store, &meta, clock, config.time_format, Keymap::new(SESSION_DEFAULTS, &keys.session), size)?;
+		let cvc = ConflictViewController::new(clock, config.time_format, Keymap::new(CONFLICT_DEFAULTS, &keys.conflict), size)?;
+		let avc = AgendaViewController::new(clock, config.time_format, Keymap::new(AGENDA_DEFAULTS, &keys.agenda), size)?;
+		let wvc = WeekViewController::new(clock, config.time_format, Keymap::new(WEEK_DEFAULTS, &keys.week), size)?;
+		let rvc = ReportViewController::new(clock, Keymap::new(REPORT_DEFAULTS, &keys.report), size)?;
 		let view = View::Tree;
 
-		Ok(Application { store, store_args, screen, tvc, svc, view })
+		let notifier = Notifier::new(config);
+
+		Ok(Application {
+			clock, store, store_args, meta, history: History::default(), screen, tvc, svc, cvc, avc, wvc, rvc, view,
+			time_format: config.time_format, notifier, reminded: Instant::now(),
+		})
 	}
 
 	pub fn run(mut self) -> Result<(), Error> {
+		self.remind()?;
 		self.draw()?;
 
 		loop {
+			// Reminders are looked for every so often, while waiting for events or between them.
+			if self.reminded.elapsed() >= remind::INTERVAL {
+				self.remind()?;
+				self.draw()?;
+			}
+			if !event::poll(remind::INTERVAL.saturating_sub(self.reminded.elapsed()))? { continue };
+
 			match match self.view {
-				View::Tree => self.tvc.run(&self.store)?,
-				View::Session => self.svc.run(&self.store)?,
+				View::Tree => self.tvc.run(&self.store, &mut self.meta, &mut self.history)?,
+				View::Session => self.svc.run(&self.store, &self.meta, &mut self.history)?,
+				View::Conflict => self.cvc.run()?,
+				View::Agenda => self.avc.run(&self.store, &mut self.meta, &mut self.history)?,
+				View::Week => self.wvc.run(&self.store, &self.meta, &mut self.history)?,
+				View::Report => self.rvc.run(&self.store)?,
 			} {
 				Action::Switch(view) => {
 					self.view = view;
@@ -42,17 +97,45 @@ impl Application {
 				Action::Quit => break,
 				Action::TaskSessions(id) => {
 					self.view = View::Session;
-					self.svc.change_mode(&self.store, SessionViewMode::Task(id))?;
+					self.svc.change_mode(&self.store, &self.meta, SessionViewMode::Task(id))?;
+				}
+				Action::ShowTask(path) => {
+					self.view = View::Tree;
+					self.update_view()?;
+					self.tvc.show_task(&self.store, &self.meta, &path)?;
 				}
 				Action::Import => {
-					drop(self.store);
-					std::fs::copy(&self.store_args.export_path, &self.store_args.path)?;
-					self.store = Store::open(&self.store_args.path, self.store_args.n_roots)?;
+					let checkpoint = Checkpoint::read(&self.store, &self.meta)?;
+					let conflicts = sync::import(&self.store, &self.store_args)?;
+					self.history.record(checkpoint, &self.store, &self.meta)?;
+					if !conflicts.is_empty() {
+						self.cvc.reset(conflicts);
+						self.view = View::Conflict;
+					}
 					self.update_view()?;
 				}
-				Action::Export => {
-					std::fs::copy(&self.store_args.path, &self.store_args.export_path)?;
+				Action::Export => sync::export(&self.store, &self.store_args)?,
+				Action::ExportCalendar => {
+					let snapshot = Snapshot::read(&self.store.reader()?)?;
+					fs::write(&self.store_args.calendar_path, ical::render(&snapshot, Utc::now().naive_utc()))?;
+				}
+				Action::ImportCalendar(events, target) => {
+					let checkpoint = Checkpoint::read(&self.store, &self.meta)?;
+					ical::import(&self.store, &self.store_args, &events, target)?;
+					self.history.record(checkpoint, &self.store, &self.meta)?;
+					self.update_view()?;
+				}
+				Action::Resolve(take_imported) => {
+					if let Some(conflict) = self.cvc.take() {
+						let checkpoint = Checkpoint::read(&self.store, &self.meta)?;
+						sync::resolve(&self.store, &self.store_args, &conflict, take_imported)?;
+						self.history.record(checkpoint, &self.store, &self.meta)?;
+					}
+					if self.cvc.is_empty() { self.view = View::Tree };
+					self.update_view()?;
 				}
+				Action::Undo => if let Some(created) = self.history.undo(&self.store, &mut self.meta)? { self.reload(&created)? },
+				Action::Redo => if let Some(created) = self.history.redo(&self.store, &mut self.meta)? { self.reload(&created)? },
 				Action::None => {}
 			}
 			self.draw()?;
@@ -61,48 +144,171 @@ impl Application {
 		Ok(())
 	}
 
+	/// Delivers the reminders which have come up through the notification command, or shows them
+	/// in the status line of the current view if there is none. So do the failures of the
+	/// command, whose reminders are tried again on the next check.
+	fn remind(&mut self) -> Result<(), Error> {
+		self.reminded = Instant::now();
+		let notices = self.notifier.check(&self.store, &self.meta, &self.store_args, self.clock.now())?;
+		if notices.is_empty() { return Ok(()) };
+
+		let mut delivered = Vec::new();
+		let mut messages = Vec::new();
+		for notice in notices {
+			if !self.notifier.has_command() {
+				messages.push(notice.to_string());
+			} else if let Err(e) = self.notifier.deliver(&notice, &mut io::sink()) {
+				messages.push(e.to_string());
+				continue;
+			}
+			delivered.push(notice.reminder);
+		}
+		self.notifier.mark_delivered(&self.store_args, &delivered)?;
+		if messages.is_empty() { return Ok(()) };
+
+		let message = messages.join(", ");
+		match self.view {
+			View::Tree => self.tvc.set_message(message),
+			View::Session => self.svc.set_message(message),
+			View::Conflict => self.cvc.set_message(message),
+			View::Agenda => self.avc.set_message(message),
+			View::Week => self.wvc.set_message(message),
+			View::Report => self.rvc.set_message(message),
+		}
+		Ok(())
+	}
+
+	/// Saves the completion state and redraws the current view after undoing or redoing, which
+	/// may have deleted selected tasks. The tasks it created again are given new ids, which the
+	/// states of syncing and of calendar imports are moved over to.
+	fn reload(&mut self, created: &HashMap<u64, u64>) -> Result<(), Error> {
+		self.meta.save()?;
+		sync::remap(&self.store_args, created)?;
+		ical::remap(&self.store_args, created)?;
+		self.tvc.clear_selections();
+		self.update_view()
+	}
+
+	/// Also adds the next occurrences of repeating sessions which have ended since.
 	fn update_view(&mut self) -> Result<(), Error> {
+		if recurrence::advance(&self.store, &mut self.meta, self.clock.now())? { self.meta.save()? };
 		match self.view {
 			View::Tree => {
-				let (w, h) = terminal::size()?;
-				self.tvc.resize(&self.store, w, h)?;
+				let (w, h) = self.screen.size()?;
+				self.tvc.resize(&self.store, &self.meta, w, h)?;
 			}
 			View::Session => {
-				let (w, h) = terminal::size()?;
+				let (w, h) = self.screen.size()?;
 				self.svc.resize(w, h);
-				self.svc.update_session_view(&self.store)?;
+				self.svc.update_session_view(&self.store, &self.meta)?;
+			}
+			View::Conflict => {
+				let (w, h) = self.screen.size()?;
+				self.cvc.resize(w, h);
+			}
+			View::Agenda => {
+				let (w, h) = self.screen.size()?;
+				self.avc.resize(w, h);
+				self.avc.update_agenda_view(&self.store, &self.meta)?;
+			}
+			View::Week => {
+				let (w, h) = self.screen.size()?;
+				self.wvc.resize(w, h);
+				self.wvc.update_week_view(&self.store)?;
+			}
+			View::Report => {
+				let (w, h) = self.screen.size()?;
+				self.rvc.resize(w, h);
+				self.rvc.update_report_view(&self.store)?;
 			}
 		}
 		Ok(())
 	}
 
-	fn draw(&mut self) -> io::Result<()> {
+	/// Describes the running timers, as in `report since 9:30 AM`, with the date of those started
+	/// on an earlier day.
+	fn timers(&self) -> Result<String, Error> {
+		let reader = self.store.reader()?;
+		let today = self.clock.today();
+		let mut timers = Vec::new();
+		for (id, start) in self.meta.timers() {
+			let Some(name) = reader.name(id)? else { continue };
+			if start.date() == today {
+				timers.push(format!("{} since {}", name, self.time_format.format(start.time())));
+			} else {
+				timers.push(format!("{} since {} {}", name, start.format("%-d %b"), self.time_format.format(start.time())));
+			}
+		}
+		Ok(timers.join(", "))
+	}
+
+	/// Shows the running timers in the status line of the current view before drawing it.
+	fn draw(&mut self) -> Result<(), Error> {
+		let info = self.timers()?;
+		match self.view {
+			View::Tree => self.tvc.set_info(info),
+			View::Session => self.svc.set_info(info),
+			View::Conflict => self.cvc.set_info(info),
+			View::Agenda => self.avc.set_info(info),
+			View::Week => self.wvc.set_info(info),
+			View::Report => self.rvc.set_info(info),
+		}
 		match self.view {
 			View::Tree => self.screen.bufprint(&self.tvc)?,
 			View::Session => self.screen.bufprint(&self.svc)?,
+			View::Conflict => self.screen.bufprint(&self.cvc)?,
+			View::Agenda => self.screen.bufprint(&self.avc)?,
+			View::Week => self.screen.bufprint(&self.wvc)?,
+			View::Report => self.screen.bufprint(&self.rvc)?,
 		};
 		Ok(())
 	}
 }
 
 pub struct StoreArgs {
-	n_roots: usize,
-	path: PathBuf,
-	export_path: PathBuf,
+	pub n_roots: usize,
+	pub path: PathBuf,
+	pub export_path: PathBuf,
+	pub calendar_path: PathBuf,
+}
+
+impl StoreArgs {
+	pub fn open(&self) -> Result<Store, sanakirja::Error> {
+		Store::open(&self.path, self.n_roots)
+	}
+
+	pub fn with_export_path(&self, export_path: Option<PathBuf>) -> Self {
+		StoreArgs {
+			n_roots: self.n_roots,
+			path: self.path.clone(),
+			export_path: export_path.unwrap_or_else(|| self.export_path.clone()),
+			calendar_path: self.calendar_path.clone(),
+		}
+	}
 }
 
 pub enum Action {
 	Quit,
 	Switch(View),
 	TaskSessions(u64),
+	ShowTask(Vec<u64>),
 	Import,
 	Export,
+	ExportCalendar,
+	ImportCalendar(Vec<Event>, Target),
+	Resolve(bool),
+	Undo,
+	Redo,
 	None,
 }
 
 pub enum View {
 	Tree,
 	Session,
+	Conflict,
+	Agenda,
+	Week,
+	Report,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -111,4 +317,6 @@ pub enum Error {
 	StoreError(#[from] sanakirja::Error),
 	#[error("IO Error: {0}")]
 	IoError(#[from] io::Error),
+	#[error("Sync State Error: {0}")]
+	SyncStateError(#[from] serde_json::Error),
 }
@@ -0,0 +1,168 @@
+use std::io;
+use chrono::{NaiveDateTime, NaiveTime};
+use crossterm::event::{self, Event};
+use grus_lib::Store;
+use crate::app::{Action, Error, View};
+use crate::clock::Clock;
+use crate::history::{Checkpoint, History};
+use crate::keymap::{AgendaAction, Keymap};
+use crate::meta::Meta;
+use crate::node::{Displayable, TimeFormat};
+use crate::search::Index;
+use crate::snapshot::Snapshot;
+use crate::ui::{BufPrint, Screen};
+use crate::ui::backend::Backend;
+use crate::ui::agenda::{AgendaView, Group, Item};
+use crate::ui::status::StatusView;
+
+pub struct AgendaViewController<'c> {
+	clock: &'c dyn Clock,
+	time_format: TimeFormat,
+	agenda_view: AgendaView,
+	status_view: StatusView<{View::Agenda as usize}>,
+	keymap: Keymap<AgendaAction>,
+}
+
+impl<'c> AgendaViewController<'c> {
+	pub fn new(clock: &'c dyn Clock, time_format: TimeFormat, keymap: Keymap<AgendaAction>, (w, h): (u16, u16)) -> Result<Self, Error> {
+		Ok(AgendaViewController {
+			clock,
+			time_format,
+			agenda_view: AgendaView::new(w, h),
+			status_view: StatusView::new(w, h),
+			keymap,
+		})
+	}
+
+	pub fn run(&mut self, store: &Store, meta: &mut Meta, history: &mut History) -> Result<Action, Error> {
+		self.status_view.clear_message();
+		match event::read()? {
+			Event::Key(kev) => match self.keymap.feed(kev) {
+				Some(AgendaAction::Quit) => return Ok(Action::Quit),
+				Some(AgendaAction::CursorDown) => self.agenda_view.cursor_down(),
+				Some(AgendaAction::CursorUp) => self.agenda_view.cursor_up(),
+				Some(AgendaAction::Delete) => self.delete(store, meta, history)?,
+				Some(AgendaAction::ViewSessions) => if let Some(item) = self.agenda_view.item() {
+					return Ok(Action::TaskSessions(item.id));
+				},
+				Some(AgendaAction::ShowTask) => if let Some(item) = self.agenda_view.item() {
+					return Ok(Action::ShowTask(item.path.clone()));
+				},
+				Some(AgendaAction::Undo) => return Ok(Action::Undo),
+				Some(AgendaAction::Redo) => return Ok(Action::Redo),
+				Some(AgendaAction::SwitchTree) => return Ok(Action::Switch(View::Tree)),
+				Some(AgendaAction::SwitchSession) => return Ok(Action::Switch(View::Session)),
+				Some(AgendaAction::SwitchWeek) => return Ok(Action::Switch(View::Week)),
+				Some(AgendaAction::SwitchReport) => return Ok(Action::Switch(View::Report)),
+				None => {},
+			}
+			Event::Resize(w, h) => {
+				self.resize(w, h);
+				self.update_agenda_view(store, meta)?;
+			}
+			_ => {}
+		}
+		Ok(Action::None)
+	}
+
+	pub fn set_message(&mut self, message: String) {
+		self.status_view.set_message(message);
+	}
+
+	pub fn set_info(&mut self, info: String) {
+		self.status_view.set_info(info);
+	}
+
+	pub fn resize(&mut self, w: u16, h: u16) {
+		self.agenda_view.constr.update(w, h);
+		self.status_view.constr.update(w, h);
+	}
+
+	/// Lists the tasks which have a due date and are not done, in order of due date.
+	pub fn update_agenda_view(&mut self, store: &Store, meta: &Meta) -> Result<(), Error> {
+		let snapshot = Snapshot::read(&store.reader()?)?;
+		let now = self.clock.now();
+		let mut items: Vec<(NaiveDateTime, Item)> = Index::new(&snapshot).entries().iter().filter_map(|entry| {
+			let due_date = snapshot.tasks[&entry.id].due_date?;
+			if meta.done(entry.id).is_some() { return None };
+			Some((due_date, Item {
+				id: entry.id,
+				group: group(due_date, now),
+				name: entry.name.clone(),
+				location: entry.location.clone(),
+				due_date_text: match meta.repeat(entry.id) {
+					Some(rule) => format!("{}, {}", Displayable(Some(due_date), self.clock, self.time_format), rule),
+					None => format!("{}", Displayable(Some(due_date), self.clock, self.time_format)),
+				},
+				path: entry.path.clone(),
+			}))
+		}).collect();
+		items.sort_by(|(a, ia), (b, ib)| a.cmp(b).then(ia.name.cmp(&ib.name)));
+		self.agenda_view.reset(items.into_iter().map(|(_, item)| item).collect());
+		Ok(())
+	}
+
+	/// Unsets the due date of the task under the cursor, which removes it from the agenda.
+	fn delete(&mut self, store: &Store, meta: &mut Meta, history: &mut History) -> Result<(), Error> {
+		let Some(id) = self.agenda_view.item().map(|item| item.id) else { return Ok(()) };
+
+		let checkpoint = Checkpoint::read(store, meta)?;
+		let mut writer = store.writer()?;
+		writer.unset_due_date(id)?;
+		writer.commit()?;
+		meta.set_repeat(id, None);
+		meta.save()?;
+		history.record(checkpoint, store, meta)?;
+
+		self.update_agenda_view(store, meta)?;
+		Ok(())
+	}
+}
+
+/// Due dates without a time of day are overdue only from the next day on. The days of this
+/// week are the next six days, as with the weekday names used for due dates.
+fn group(due_date: NaiveDateTime, now: NaiveDateTime) -> Group {
+	let days = (due_date.date() - now.date()).num_days();
+	if days < 0 || (due_date.time() != NaiveTime::MIN && due_date < now) {
+		Group::Overdue
+	} else if days == 0 {
+		Group::Today
+	} else if days == 1 {
+		Group::Tomorrow
+	} else if days < 7 {
+		Group::ThisWeek
+	} else {
+		Group::Later
+	}
+}
+
+impl<B: Backend> BufPrint<AgendaViewController<'_>> for Screen<B> {
+	fn bufprint(&mut self, avc: &AgendaViewController) -> io::Result<&mut Self> {
+		self
+			.clear()?
+			.bufprint(&avc.status_view)?
+			.bufprint(&avc.agenda_view)?
+			.flush()?;
+		Ok(self)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::NaiveDate;
+	use crate::ui::agenda::Group;
+	use super::group;
+
+	#[test]
+	fn group_due_dates() {
+		let date = |d, h| NaiveDate::from_ymd_opt(2023, 7, d).unwrap().and_hms_opt(h, 0, 0).unwrap();
+		let now = date(5, 12);
+		assert!(group(date(4, 0), now) == Group::Overdue);
+		assert!(group(date(5, 9), now) == Group::Overdue);
+		assert!(group(date(5, 0), now) == Group::Today);
+		assert!(group(date(5, 18), now) == Group::Today);
+		assert!(group(date(6, 0), now) == Group::Tomorrow);
+		assert!(group(date(11, 23), now) == Group::ThisWeek);
+		assert!(group(date(12, 0), now) == Group::Later);
+	}
+}
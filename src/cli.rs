@@ -0,0 +1,820 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::thread;
+use chrono::{NaiveDateTime, Utc};
+use grus_lib::Store;
+use grus_lib::reader::StoreReader;
+use grus_lib::types::Session;
+use serde_json::{json, Value};
+use crate::app::{self, StoreArgs};
+use crate::clock::Clock;
+use crate::config::{Config, Overrides};
+use crate::ical;
+use crate::json::{self, Document};
+use crate::meta::Meta;
+use crate::node::Displayable;
+use crate::outline;
+use crate::parser::{describe, parse_datetime, parse_repeating_datetime, parse_repeating_session};
+use crate::recurrence::{self, Repeating};
+use crate::remind::{self, Notifier};
+use crate::report::{self, format_duration, Period};
+use crate::snapshot::Snapshot;
+use crate::sync::{self, ConflictKind};
+
+pub const USAGE: &str = "\
+Usage: grus [<options>] [<command> [--json] [<args>...]]
+
+Without a command, grus starts the interactive task manager.
+
+Options:
+  --config <path>           Read the config file at <path>
+  --data <path>             Use the task store at <path>
+  --export <path>           Import from and export to <path>
+
+Commands:
+  add <parent> <name>       Add a task named <name> under <parent>
+  ls [<task>]               List the children of <task> (default: root)
+  due <task> <datetime>     Set the due date of <task>
+  due <task> --unset        Unset the due date of <task>
+  session <task> <session>  Add a session to <task>
+  rm <task>                 Delete <task> and all of its descendents
+  done <task> [--undo]      Mark <task> as done, or as not done with --undo
+  export [--format <format>] [--root <task>] [<path>]
+                            Export the store to <path>
+  import [--format <format>] [--task <task> | --parent <task>] [<path>]
+                            Import the store from <path>
+  report [--from <datetime>] [--to <datetime>] [--root <task>]
+                            Sum the session time of every task under <task>
+  remind [--daemon]         Deliver the reminders which have come up, or keep
+                            delivering them with --daemon
+  help                      Show this message
+
+A <task> is either a task id, or a path of task names starting at the root
+such as /work/report. Dates and sessions accept the same formats as the
+last line prompt.
+
+The <format> of export and import is one of:
+  db    Copy the database to, and merge it from, the export path (default)
+  json  Write the whole task tree, or add the tasks read, under the root
+  ics   Write sessions and due dates as a calendar, or add the events read
+        as sessions of --task or as new children of --parent
+  md    Write the subtree at --root as a Markdown outline, or add the
+        outline read under --parent
+A <path> is used instead of the export path for db, and instead of standard
+input or output for the other formats.
+
+The report lists the time of each task's own sessions and the total time of
+its subtree, counting shared tasks once, for sessions between --from and --to,
+excluding --to.
+
+Reminders are delivered remind_before minutes before due dates and session
+starts, by running notify_command with the task name and the reminder as its
+last arguments, or by printing them if notify_command is not set.";
+
+pub enum Command {
+	Add { parent: TaskRef, name: String },
+	Ls { task: TaskRef },
+	Due { task: TaskRef, due_date: Option<String> },
+	Session { task: TaskRef, session: String },
+	Rm { task: TaskRef },
+	Done { task: TaskRef, undo: bool },
+	Export { format: StoreFormat, path: Option<PathBuf> },
+	Import { format: StoreFormat, path: Option<PathBuf> },
+	ExportCalendar { path: Option<PathBuf> },
+	ImportCalendar { path: Option<PathBuf>, target: ImportTarget },
+	ExportOutline { path: Option<PathBuf>, root: TaskRef },
+	ImportOutline { path: Option<PathBuf>, parent: TaskRef },
+	Report { from: Option<String>, to: Option<String>, root: TaskRef },
+	Remind { daemon: bool },
+	Help,
+}
+
+impl Command {
+	/// Whether the command changes the store, in which case repeating sessions which have ended
+	/// are moved on first. Commands which only read leave the store and `.meta` as they are.
+	fn is_writing(&self) -> bool {
+		match self {
+			Command::Add { .. } | Command::Due { .. } | Command::Session { .. } | Command::Rm { .. }
+				| Command::Done { .. } | Command::Import { .. } | Command::ImportCalendar { .. }
+				| Command::ImportOutline { .. } => true,
+			Command::Ls { .. } | Command::Export { .. } | Command::ExportCalendar { .. } | Command::ExportOutline { .. }
+				| Command::Report { .. } | Command::Help => false,
+			&Command::Remind { daemon } => daemon,
+		}
+	}
+}
+
+/// The task which calendar events or outlines are imported into. Events become either sessions
+/// of the task or new children of it, and outlines always become new children.
+pub enum ImportTarget {
+	Task(TaskRef),
+	Parent(TaskRef),
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Format {
+	Db,
+	Json,
+	Ics,
+	Md,
+}
+
+/// The formats which hold the whole store, as opposed to calendars and outlines, which are
+/// parsed into their own commands.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StoreFormat {
+	Db,
+	Json,
+}
+
+pub struct Args {
+	pub overrides: Overrides,
+	pub invocation: Option<Invocation>,
+}
+
+impl Args {
+	/// Separates the global options from the command and its arguments. Options may appear
+	/// anywhere on the command line, either as `--data <path>` or `--data=<path>`.
+	pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, Error> {
+		let mut overrides = Overrides::default();
+		let mut rest = Vec::new();
+		let mut args = args.into_iter();
+		while let Some(arg) = args.next() {
+			let (name, value) = match arg.split_once('=') {
+				Some((name, value)) => (name, Some(value.to_string())),
+				None => (arg.as_str(), None),
+			};
+			let slot = match name {
+				"--config" => &mut overrides.config,
+				"--data" => &mut overrides.data,
+				"--export" => &mut overrides.export,
+				_ => {
+					rest.push(arg);
+					continue;
+				}
+			};
+			let value = match value {
+				Some(value) => value,
+				None => args.next().ok_or_else(|| Error::Usage(format!("missing value for {}", name)))?,
+			};
+			*slot = Some(value.into());
+		}
+
+		let invocation = if rest.is_empty() { None } else { Some(Invocation::parse(rest)?) };
+		Ok(Args { overrides, invocation })
+	}
+}
+
+pub struct Invocation {
+	pub command: Command,
+	pub json: bool,
+}
+
+impl Invocation {
+	pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, Error> {
+		let mut json = false;
+		let mut rest = Vec::new();
+		for arg in args {
+			match arg.as_str() {
+				"--json" => json = true,
+				"-h" | "--help" => return Ok(Invocation { command: Command::Help, json }),
+				_ => rest.push(arg),
+			}
+		}
+
+		let mut rest = rest.into_iter();
+		let Some(name) = rest.next() else { return Err(Error::Usage("missing command".into())) };
+		let command = match name.as_str() {
+			"add" => {
+				let parent = TaskRef::parse(&next_arg(&mut rest, "parent")?)?;
+				let name = join_args(rest, "name")?;
+				Command::Add { parent, name }
+			}
+			"ls" => {
+				let task = rest.next().map(|arg| TaskRef::parse(&arg)).transpose()?;
+				no_more_args(rest)?;
+				Command::Ls { task: task.unwrap_or(TaskRef::Path(Vec::new())) }
+			}
+			"due" => {
+				let task = TaskRef::parse(&next_arg(&mut rest, "task")?)?;
+				let due_date = join_args(rest, "datetime")?;
+				let due_date = if due_date == "--unset" { None } else { Some(due_date) };
+				Command::Due { task, due_date }
+			}
+			"session" => {
+				let task = TaskRef::parse(&next_arg(&mut rest, "task")?)?;
+				let session = join_args(rest, "session")?;
+				Command::Session { task, session }
+			}
+			"rm" => {
+				let task = TaskRef::parse(&next_arg(&mut rest, "task")?)?;
+				no_more_args(rest)?;
+				Command::Rm { task }
+			}
+			"done" => {
+				let task = TaskRef::parse(&next_arg(&mut rest, "task")?)?;
+				let undo = match rest.next().as_deref() {
+					Some("--undo") => true,
+					Some(arg) => return Err(Error::Usage(format!("unexpected argument '{}'", arg))),
+					None => false,
+				};
+				no_more_args(rest)?;
+				Command::Done { task, undo }
+			}
+			"export" | "import" => {
+				let mut format = Format::Db;
+				let mut path = None;
+				let mut target = None;
+				let mut root = None;
+				while let Some(arg) = rest.next() {
+					if let Some(value) = option_value(&arg, "--format", &mut rest)? {
+						format = match value.as_str() {
+							"db" => Format::Db,
+							"json" => Format::Json,
+							"ics" => Format::Ics,
+							"md" => Format::Md,
+							_ => return Err(Error::Usage(format!("unknown format '{}'", value))),
+						};
+					} else if let Some(value) = option_value(&arg, "--task", &mut rest)? {
+						target = Some(ImportTarget::Task(TaskRef::parse(&value)?));
+					} else if let Some(value) = option_value(&arg, "--parent", &mut rest)? {
+						target = Some(ImportTarget::Parent(TaskRef::parse(&value)?));
+					} else if let Some(value) = option_value(&arg, "--root", &mut rest)? {
+						root = Some(TaskRef::parse(&value)?);
+					} else if path.is_none() && !arg.starts_with("--") {
+						path = Some(PathBuf::from(arg));
+					} else {
+						return Err(Error::Usage(format!("unexpected argument '{}'", arg)));
+					}
+				}
+				let store_root = || TaskRef::Path(Vec::new());
+				match (name.as_str(), format, target, root) {
+					("export", Format::Db, None, None) => Command::Export { format: StoreFormat::Db, path },
+					("export", Format::Json, None, None) => Command::Export { format: StoreFormat::Json, path },
+					("export", Format::Ics, None, None) => Command::ExportCalendar { path },
+					("export", Format::Md, None, root) => Command::ExportOutline { path, root: root.unwrap_or_else(store_root) },
+					("import", Format::Db, None, None) => Command::Import { format: StoreFormat::Db, path },
+					("import", Format::Json, None, None) => Command::Import { format: StoreFormat::Json, path },
+					("import", Format::Ics, Some(target), None) => Command::ImportCalendar { path, target },
+					("import", Format::Ics, None, None) => return Err(Error::Usage("missing --task or --parent".into())),
+					("import", Format::Md, Some(ImportTarget::Parent(parent)), None) => Command::ImportOutline { path, parent },
+					("import", Format::Md, None, None) => Command::ImportOutline { path, parent: store_root() },
+					_ => return Err(Error::Usage(format!("--task, --parent or --root cannot be used with {} in this format", name))),
+				}
+			}
+			"report" => {
+				let (mut from, mut to, mut root) = (None, None, None);
+				while let Some(arg) = rest.next() {
+					if let Some(value) = option_value(&arg, "--from", &mut rest)? {
+						from = Some(value);
+					} else if let Some(value) = option_value(&arg, "--to", &mut rest)? {
+						to = Some(value);
+					} else if let Some(value) = option_value(&arg, "--root", &mut rest)? {
+						root = Some(TaskRef::parse(&value)?);
+					} else {
+						return Err(Error::Usage(format!("unexpected argument '{}'", arg)));
+					}
+				}
+				Command::Report { from, to, root: root.unwrap_or(TaskRef::Path(Vec::new())) }
+			}
+			"remind" => {
+				let daemon = match rest.next().as_deref() {
+					Some("--daemon") => true,
+					Some(arg) => return Err(Error::Usage(format!("unexpected argument '{}'", arg))),
+					None => false,
+				};
+				no_more_args(rest)?;
+				Command::Remind { daemon }
+			}
+			"help" => Command::Help,
+			_ => return Err(Error::Usage(format!("unknown command '{}'", name))),
+		};
+		Ok(Invocation { command, json })
+	}
+
+	/// Runs the command, resolving relative dates against the clock.
+	pub fn run(self, store: &Store, store_args: &StoreArgs, config: &Config, clock: &dyn Clock) -> Result<(), Error> {
+		let mut out = io::stdout().lock();
+		let mut meta = Meta::load(store_args)?;
+		if self.command.is_writing() && recurrence::advance(store, &mut meta, clock.now())? { meta.save()? };
+		match self.command {
+			Command::Add { parent, name } => {
+				let pid = parent.resolve(&store.reader()?)?;
+				let mut writer = store.writer()?;
+				let id = writer.add_child(pid, &name)?;
+				writer.commit()?;
+
+				if self.json {
+					writeln!(out, "{}", json!({ "id": id }))?;
+				} else {
+					writeln!(out, "{}", id)?;
+				}
+			}
+			Command::Ls { task } => {
+				let reader = store.reader()?;
+				let pid = task.resolve(&reader)?;
+				let mut children = Vec::new();
+				for id in reader.child_ids(pid)? {
+					children.push(Listing::read(&reader, &meta, id?)?);
+				}
+
+				if self.json {
+					let children: Vec<_> = children.iter().map(Listing::to_json).collect();
+					writeln!(out, "{}", Value::Array(children))?;
+				} else {
+					for child in children {
+						writeln!(
+							out,
+							"{}\t{}\t{}\t{}\t{}",
+							child.id,
+							child.name,
+							Displayable(child.due_date, clock, config.time_format),
+							Displayable(child.session, clock, config.time_format),
+							if child.done.is_some() { "done" } else { "" },
+						)?;
+					}
+				}
+			}
+			Command::Due { task, due_date } => {
+				let id = task.resolve(&store.reader()?)?;
+				if id == 0 { return Err(Error::Usage("the root task cannot have a due date".into())) };
+
+				let mut writer = store.writer()?;
+				match due_date {
+					Some(due_date) => {
+						let (due_date, rule) = parse_repeating_datetime(&due_date, clock)
+							.map_err(|e| Error::Parse(format!("invalid date '{}': {}", due_date, describe(&e))))?;
+						writer.set_due_date(id, due_date)?;
+						meta.set_repeat(id, rule);
+					}
+					None => {
+						writer.unset_due_date(id)?;
+						meta.set_repeat(id, None);
+					}
+				}
+				writer.commit()?;
+				meta.save()?;
+			}
+			Command::Session { task, session } => {
+				let id = task.resolve(&store.reader()?)?;
+
+				let (session, rule) = parse_repeating_session(&session, clock)
+					.map_err(|e| Error::Parse(format!("invalid session '{}': {}", session, describe(&e))))?;
+				let mut writer = store.writer()?;
+				writer.add_session(id, &session)?;
+				writer.commit()?;
+				if let Some(rule) = rule {
+					meta.add_repeating(id, Repeating { start: session.start, end: session.end, rule });
+					meta.save()?;
+				}
+			}
+			Command::Done { task, undo } => {
+				let id = task.resolve(&store.reader()?)?;
+				if id == 0 { return Err(Error::Usage("the root task cannot be done".into())) };
+
+				let now = clock.now();
+				let mut writer = store.writer()?;
+				if undo {
+					meta.unmark_done(id);
+				} else if !recurrence::complete(&mut writer, &meta, id, now)? {
+					meta.mark_done(id, now);
+				}
+				writer.commit()?;
+				meta.save()?;
+			}
+			Command::Rm { task } => {
+				let reader = store.reader()?;
+				if task.resolve(&reader)? == 0 { return Err(Error::Usage("the root task cannot be deleted".into())) };
+				let links = task.links(&reader)?;
+				drop(reader);
+
+				let mut writer = store.writer()?;
+				for (pid, id) in links {
+					writer.delete(pid, id)?;
+				}
+				writer.commit()?;
+			}
+			Command::Export { format: StoreFormat::Db, path } => sync::export(store, &store_args.with_export_path(path))?,
+			Command::Export { format: StoreFormat::Json, path } => {
+				let document = Document::new(&Snapshot::read(&store.reader()?)?, &meta);
+				match path {
+					Some(path) => serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &document)?,
+					None => {
+						serde_json::to_writer_pretty(&mut out, &document)?;
+						writeln!(out)?;
+					}
+				}
+			}
+			Command::ExportCalendar { path } => {
+				let ics = ical::render(&Snapshot::read(&store.reader()?)?, Utc::now().naive_utc());
+				match path {
+					Some(path) => fs::write(path, ics)?,
+					None => write!(out, "{}", ics)?,
+				}
+			}
+			Command::Import { format: StoreFormat::Db, path } => {
+				let conflicts = sync::import(store, &store_args.with_export_path(path))?;
+				if conflicts.is_empty() { return Ok(()) };
+
+				let conflicts: Vec<_> = conflicts.iter().map(|conflict| match &conflict.kind {
+					ConflictKind::Name { local, foreign } =>
+						(conflict.id, "name", local.clone(), foreign.clone()),
+					ConflictKind::DueDate { local, foreign } => (
+						conflict.id,
+						"due_date",
+						local.map(|dt| dt.format(DATETIME_FORMAT).to_string()).unwrap_or_default(),
+						foreign.map(|dt| dt.format(DATETIME_FORMAT).to_string()).unwrap_or_default(),
+					),
+				}).collect();
+				if self.json {
+					let conflicts: Vec<_> = conflicts.iter().map(|(id, field, local, imported)| json!({
+						"id": id,
+						"field": field,
+						"local": local,
+						"imported": imported,
+					})).collect();
+					writeln!(out, "{}", Value::Array(conflicts))?;
+				} else {
+					for (id, field, local, imported) in &conflicts {
+						writeln!(out, "{}\t{}\t{}\t{}", id, field, local, imported)?;
+					}
+				}
+				return Err(Error::Conflicts(conflicts.len()));
+			}
+			Command::Import { format: StoreFormat::Json, path } => {
+				let document: Document = match path {
+					Some(path) => serde_json::from_reader(BufReader::new(File::open(path)?))?,
+					None => serde_json::from_reader(io::stdin().lock())?,
+				};
+				let mut writer = store.writer()?;
+				let count = document.write(&mut writer, &mut meta)?;
+				writer.commit()?;
+				meta.save()?;
+
+				if self.json {
+					writeln!(out, "{}", json!({ "tasks": count }))?;
+				} else {
+					writeln!(out, "{}", count)?;
+				}
+			}
+			Command::ImportCalendar { path, target } => {
+				let ics = match path {
+					Some(path) => fs::read_to_string(path)?,
+					None => io::read_to_string(io::stdin().lock())?,
+				};
+				let events = ical::parse(&ics)?;
+				let reader = store.reader()?;
+				let target = match target {
+					ImportTarget::Task(task) => ical::Target::Task(task.resolve(&reader)?),
+					ImportTarget::Parent(task) => ical::Target::Children(task.resolve(&reader)?),
+				};
+				drop(reader);
+				let count = ical::import(store, store_args, &events, target)?;
+
+				if self.json {
+					writeln!(out, "{}", json!({ "sessions": count }))?;
+				} else {
+					writeln!(out, "{}", count)?;
+				}
+			}
+			Command::ExportOutline { path, root } => {
+				let reader = store.reader()?;
+				let outline = outline::render(&reader, root.resolve(&reader)?)?;
+				match path {
+					Some(path) => fs::write(path, outline)?,
+					None => write!(out, "{}", outline)?,
+				}
+			}
+			Command::ImportOutline { path, parent } => {
+				let text = match path {
+					Some(path) => fs::read_to_string(path)?,
+					None => io::read_to_string(io::stdin().lock())?,
+				};
+				let items = outline::parse(&text)?;
+				let pid = parent.resolve(&store.reader()?)?;
+				let mut writer = store.writer()?;
+				let count = outline::write(&items, &mut writer, pid)?;
+				writer.commit()?;
+
+				if self.json {
+					writeln!(out, "{}", json!({ "tasks": count }))?;
+				} else {
+					writeln!(out, "{}", count)?;
+				}
+			}
+			Command::Report { from, to, root } => {
+				let parse = |date: Option<String>| date.map(|date| {
+					parse_datetime(&date, clock).map_err(|e| Error::Parse(format!("invalid date '{}': {}", date, describe(&e))))
+				}).transpose();
+				let period = Period { from: parse(from)?, to: parse(to)? };
+				let reader = store.reader()?;
+				let rows = report::build(&reader, root.resolve(&reader)?, period)?;
+
+				if self.json {
+					let rows: Vec<_> = rows.iter().map(|row| json!({
+						"id": row.id,
+						"name": row.name,
+						"depth": row.depth,
+						"own_seconds": row.own.num_seconds(),
+						"total_seconds": row.total.num_seconds(),
+					})).collect();
+					writeln!(out, "{}", Value::Array(rows))?;
+				} else {
+					for row in rows {
+						writeln!(
+							out,
+							"{:>8} {:>8}  {}{}",
+							format_duration(row.total),
+							format_duration(row.own),
+							"  ".repeat(row.depth),
+							row.name,
+						)?;
+					}
+				}
+			}
+			Command::Remind { daemon } => {
+				let notifier = Notifier::new(config);
+				loop {
+					let now = clock.now();
+					let mut delivered = Vec::new();
+					let mut failure = None;
+					for notice in notifier.check(store, &meta, store_args, now)? {
+						if self.json && !notifier.has_command() {
+							writeln!(out, "{}", json!({
+								"id": notice.reminder.id,
+								"kind": notice.reminder.kind,
+								"at": notice.reminder.at,
+								"name": notice.name,
+							}))?;
+						} else if let Err(e) = notifier.deliver(&notice, &mut out) {
+							// The daemon keeps going, and tries the reminder again on the next round.
+							if daemon { eprintln!("Error: {}", e) } else { failure = Some(e) };
+							continue;
+						}
+						delivered.push(notice.reminder);
+					}
+					notifier.mark_delivered(store_args, &delivered)?;
+					if let Some(e) = failure { return Err(e.into()) };
+					if !daemon { break };
+					out.flush()?;
+					thread::sleep(remind::INTERVAL);
+
+					// Tasks may have been completed or repeated from elsewhere in the meantime.
+					meta = Meta::load(store_args)?;
+					if recurrence::advance(store, &mut meta, clock.now())? { meta.save()? };
+				}
+			}
+			Command::Help => writeln!(out, "{}", USAGE)?,
+		}
+		Ok(())
+	}
+}
+
+pub enum TaskRef {
+	Id(u64),
+	Path(Vec<String>),
+}
+
+impl TaskRef {
+	fn parse(arg: &str) -> Result<Self, Error> {
+		if let Some(path) = arg.strip_prefix('/') {
+			Ok(TaskRef::Path(path.split('/').filter(|s| !s.is_empty()).map(String::from).collect()))
+		} else {
+			arg.parse().map(TaskRef::Id).map_err(|_| Error::Usage(format!("invalid task '{}'", arg)))
+		}
+	}
+
+	fn resolve(&self, reader: &StoreReader) -> Result<u64, Error> {
+		match self {
+			TaskRef::Id(id) if reader.name(*id)?.is_some() => Ok(*id),
+			TaskRef::Id(id) => Err(Error::NotFound(format!("no task with id {}", id))),
+			TaskRef::Path(_) => Ok(self.links(reader)?[0].1),
+		}
+	}
+
+	/// Returns the `(pid, id)` links through which the task is reachable. A path names exactly one
+	/// link while an id stands for the task under all of its parents.
+	fn links(&self, reader: &StoreReader) -> Result<Vec<(u64, u64)>, Error> {
+		match self {
+			TaskRef::Id(id) => Ok(parent_ids(reader, *id)?.into_iter().map(|pid| (pid, *id)).collect()),
+			TaskRef::Path(names) => {
+				let (mut pid, mut id) = (0, 0);
+				for name in names {
+					let mut found = None;
+					for child_id in reader.child_ids(id)? {
+						let child_id = child_id?;
+						if reader.name(child_id)? == Some(name.as_str()) {
+							found = Some(child_id);
+							break;
+						}
+					}
+					let Some(child_id) = found else {
+						return Err(Error::NotFound(format!("no task named '{}' under task {}", name, id)));
+					};
+					(pid, id) = (id, child_id);
+				}
+				Ok(vec![(pid, id)])
+			}
+		}
+	}
+}
+
+struct Listing {
+	id: u64,
+	name: String,
+	due_date: Option<NaiveDateTime>,
+	session: Option<Session>,
+	done: Option<NaiveDateTime>,
+}
+
+impl Listing {
+	fn read(reader: &StoreReader, meta: &Meta, id: u64) -> Result<Self, Error> {
+		Ok(Listing {
+			id,
+			name: reader.name(id)?.unwrap_or_default().into(),
+			due_date: reader.due_date(id)?,
+			session: reader.first_session(id)?,
+			done: meta.done(id),
+		})
+	}
+
+	fn to_json(&self) -> Value {
+		json!({
+			"id": self.id,
+			"name": self.name,
+			"due_date": self.due_date.map(|dt| dt.format(DATETIME_FORMAT).to_string()),
+			"session": self.session.map(|session| json!({
+				"start": session.start.format(DATETIME_FORMAT).to_string(),
+				"end": session.end.format(DATETIME_FORMAT).to_string(),
+			})),
+			"done": self.done.map(|dt| dt.format(DATETIME_FORMAT).to_string()),
+		})
+	}
+}
+
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+fn parent_ids(reader: &StoreReader, id: u64) -> Result<Vec<u64>, Error> {
+	let mut pids = Vec::new();
+	let mut stack = vec![0];
+	let mut visited = HashSet::from([0]);
+	while let Some(pid) = stack.pop() {
+		for child_id in reader.child_ids(pid)? {
+			let child_id = child_id?;
+			if child_id == id { pids.push(pid) };
+			if visited.insert(child_id) { stack.push(child_id) };
+		}
+	}
+	if pids.is_empty() { return Err(Error::NotFound(format!("no task with id {}", id))) };
+	Ok(pids)
+}
+
+fn next_arg(args: &mut impl Iterator<Item = String>, what: &str) -> Result<String, Error> {
+	args.next().ok_or_else(|| Error::Usage(format!("missing <{}>", what)))
+}
+
+fn join_args(args: impl Iterator<Item = String>, what: &str) -> Result<String, Error> {
+	let joined = args.collect::<Vec<_>>().join(" ");
+	if joined.is_empty() { return Err(Error::Usage(format!("missing <{}>", what))) };
+	Ok(joined)
+}
+
+/// Returns the value of `arg` if it is the option `name`, given either as `name=<value>` or as
+/// `name <value>`.
+fn option_value(arg: &str, name: &str, rest: &mut impl Iterator<Item = String>) -> Result<Option<String>, Error> {
+	match arg.strip_prefix(name) {
+		Some("") => rest.next().map(Some).ok_or_else(|| Error::Usage(format!("missing value for {}", name))),
+		Some(value) if value.starts_with('=') => Ok(Some(value[1..].to_string())),
+		_ => Ok(None),
+	}
+}
+
+fn no_more_args(mut args: impl Iterator<Item = String>) -> Result<(), Error> {
+	match args.next() {
+		Some(arg) => Err(Error::Usage(format!("unexpected argument '{}'", arg))),
+		None => Ok(()),
+	}
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("{0}")]
+	Usage(String),
+	#[error("{0}")]
+	Parse(String),
+	#[error("{0}")]
+	NotFound(String),
+	#[error("{0} conflicts were found while importing")]
+	Conflicts(usize),
+	#[error("Invalid JSON: {0}")]
+	JsonError(#[from] serde_json::Error),
+	#[error("{0}")]
+	DocumentError(#[from] json::Error),
+	#[error("{0}")]
+	CalendarError(#[from] ical::Error),
+	#[error("{0}")]
+	OutlineError(#[from] outline::Error),
+	#[error("{0}")]
+	AppError(#[from] app::Error),
+	#[error("Store Error: {0}")]
+	StoreError(#[from] sanakirja::Error),
+	#[error("IO Error: {0}")]
+	IoError(#[from] io::Error),
+}
+
+impl Error {
+	pub fn exit_code(&self) -> ExitCode {
+		ExitCode::from(match self {
+			Error::StoreError(_) | Error::IoError(_) | Error::AppError(_) => 1,
+			Error::Usage(_) => 2,
+			Error::Parse(_) | Error::JsonError(_) | Error::DocumentError(_) | Error::CalendarError(_) | Error::OutlineError(_) => 3,
+			Error::NotFound(_) => 4,
+			Error::Conflicts(_) => 5,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::Path;
+	use super::{Args, ImportTarget, Command, Invocation, StoreFormat, TaskRef};
+
+	fn parse(args: &[&str]) -> Invocation {
+		Invocation::parse(args.iter().map(|s| s.to_string())).ok().unwrap()
+	}
+
+	#[test]
+	fn parse_invocation() {
+		let inv = parse(&["add", "/work/report", "write", "summary"]);
+		assert!(!inv.json);
+		let Command::Add { parent: TaskRef::Path(path), name } = inv.command else { panic!() };
+		assert_eq!(path, ["work", "report"]);
+		assert_eq!(name, "write summary");
+
+		let inv = parse(&["ls", "--json"]);
+		assert!(inv.json);
+		let Command::Ls { task: TaskRef::Path(path) } = inv.command else { panic!() };
+		assert!(path.is_empty());
+
+		let Command::Due { task: TaskRef::Id(4), due_date: Some(due_date) } = parse(&["due", "4", "tmrw", "5pm"]).command
+			else { panic!() };
+		assert_eq!(due_date, "tmrw 5pm");
+
+		let Command::Due { due_date: None, .. } = parse(&["due", "/a", "--unset"]).command else { panic!() };
+
+		let Command::Export { format: StoreFormat::Json, path: None } = parse(&["export", "--format", "json"]).command
+			else { panic!() };
+		let Command::Import { format: StoreFormat::Db, path: Some(path) } = parse(&["import", "a.db"]).command
+			else { panic!() };
+		assert_eq!(path, Path::new("a.db"));
+		let Command::Import { format: StoreFormat::Json, path: Some(_) } = parse(&["import", "--format=json", "a.json"]).command
+			else { panic!() };
+		let Command::ExportCalendar { path: None } = parse(&["export", "--format=ics"]).command
+			else { panic!() };
+		let Command::ImportCalendar { path: None, target: ImportTarget::Parent(TaskRef::Path(path)) } =
+			parse(&["import", "--format", "ics", "--parent", "/work"]).command
+			else { panic!() };
+		assert_eq!(path, ["work"]);
+		assert!(Invocation::parse(["import".into(), "--format=ics".into()]).is_err());
+		let Command::ExportOutline { path: Some(_), root: TaskRef::Id(3) } =
+			parse(&["export", "--format", "md", "--root", "3", "plan.md"]).command
+			else { panic!() };
+		let Command::ImportOutline { path: None, parent: TaskRef::Path(_) } = parse(&["import", "--format", "md"]).command
+			else { panic!() };
+		assert!(Invocation::parse(["import".into(), "--format=md".into(), "--task=1".into()]).is_err());
+		assert!(Invocation::parse(["export".into(), "--task".into(), "1".into()]).is_err());
+		assert!(Invocation::parse(["export".into(), "--format".into(), "xml".into()]).is_err());
+
+		let Command::Report { from: Some(from), to: None, root: TaskRef::Id(2) } =
+			parse(&["report", "--root", "2", "--from=1/7/2023"]).command
+			else { panic!() };
+		assert_eq!(from, "1/7/2023");
+		assert!(Invocation::parse(["report".into(), "2".into()]).is_err());
+
+		assert!(matches!(parse(&["remind", "--daemon"]).command, Command::Remind { daemon: true }));
+		assert!(matches!(parse(&["remind"]).command, Command::Remind { daemon: false }));
+		assert!(Invocation::parse(["remind".into(), "--now".into()]).is_err());
+
+		assert!(Invocation::parse(["ls".into(), "a".into()]).is_err());
+		assert!(Invocation::parse(["rm".into()]).is_err());
+		assert!(Invocation::parse(["frobnicate".into()]).is_err());
+	}
+
+	#[test]
+	fn parse_args() {
+		let args = Args::parse(["--data", "/tmp/tasks", "ls", "--export=/tmp/sync"].map(String::from)).ok().unwrap();
+		assert_eq!(args.overrides.data.as_deref(), Some(Path::new("/tmp/tasks")));
+		assert_eq!(args.overrides.export.as_deref(), Some(Path::new("/tmp/sync")));
+		assert!(args.overrides.config.is_none());
+		assert!(matches!(args.invocation, Some(Invocation { command: Command::Ls { .. }, .. })));
+
+		let args = Args::parse(["--config", "grus.toml"].map(String::from)).ok().unwrap();
+		assert_eq!(args.overrides.config.as_deref(), Some(Path::new("grus.toml")));
+		assert!(args.invocation.is_none());
+
+		assert!(Args::parse(["--data"].map(String::from)).is_err());
+	}
+}
@@ -0,0 +1,39 @@
+use std::fmt::Debug;
+use chrono::{Local, NaiveDate, NaiveDateTime, Timelike};
+
+/// Where the current time comes from, which relative dates such as `tmrw` or `next fri` are
+/// resolved and displayed against: the system clock, or a fixed instant when dates are
+/// evaluated relative to another day, as in tests.
+pub trait Clock: Debug {
+	fn now(&self) -> NaiveDateTime;
+
+	fn today(&self) -> NaiveDate {
+		self.now().date()
+	}
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+	fn now(&self) -> NaiveDateTime {
+		(**self).now()
+	}
+}
+
+/// The local time of the system, to the second.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn now(&self) -> NaiveDateTime {
+		Local::now().naive_local().with_nanosecond(0).unwrap_or_default()
+	}
+}
+
+/// A clock stopped at the given instant.
+#[derive(Copy, Clone, Debug)]
+pub struct FixedClock(pub NaiveDateTime);
+
+impl Clock for FixedClock {
+	fn now(&self) -> NaiveDateTime {
+		self.0
+	}
+}
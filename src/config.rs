@@ -0,0 +1,161 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::app::StoreArgs;
+use crate::keymap::KeyConfig;
+use crate::node::TimeFormat;
+
+const DEFAULT_N_ROOTS: usize = 2;
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub data: Option<PathBuf>,
+	pub export: Option<PathBuf>,
+	pub calendar: Option<PathBuf>,
+	pub n_roots: Option<usize>,
+	pub show_done: bool,
+	pub sort_by_score: bool,
+	pub multiple_timers: bool,
+	/// Minutes before a due date or the start of a session at which it is reminded of.
+	pub remind_before: Option<u32>,
+	pub notify_command: Option<String>,
+	pub time_format: TimeFormat,
+	pub keys: KeyConfig,
+}
+
+impl Config {
+	/// Reads the config file and applies the environment and command-line overrides on top of it,
+	/// in increasing order of precedence.
+	pub fn load(overrides: &Overrides) -> Result<Self, Error> {
+		let explicit = overrides.config.clone().or_else(|| env::var_os("GRUS_CONFIG").map(PathBuf::from));
+		let mut config = match explicit {
+			Some(path) => Config::read(&path)?,
+			None => match default_config_path() {
+				Some(path) if path.exists() => Config::read(&path)?,
+				_ => Config::default(),
+			}
+		};
+
+		if let Some(data) = env::var_os("GRUS_DATA") { config.data = Some(data.into()) };
+		if let Some(export) = env::var_os("GRUS_EXPORT") { config.export = Some(export.into()) };
+		if let Some(n_roots) = env::var_os("GRUS_N_ROOTS") {
+			config.n_roots = Some(parse_n_roots(n_roots)?);
+		}
+
+		if let Some(data) = &overrides.data { config.data = Some(data.clone()) };
+		if let Some(export) = &overrides.export { config.export = Some(export.clone()) };
+		Ok(config)
+	}
+
+	pub fn parse(s: &str) -> Result<Self, Error> {
+		Ok(toml::from_str(s)?)
+	}
+
+	/// Resolves the configured paths, falling back to `<data dir>/grus/tasks` for the store,
+	/// `~/sync/tasks` for exports and the export path with an `.ics` suffix for calendars.
+	pub fn store_args(&self) -> Result<StoreArgs, Error> {
+		let path = match &self.data {
+			Some(path) => expand_tilde(path)?,
+			None => dirs::data_dir().ok_or(Error::NoDataDir)?.join("grus").join("tasks"),
+		};
+		let export_path = match &self.export {
+			Some(path) => expand_tilde(path)?,
+			None => dirs::home_dir().ok_or(Error::NoHomeDir)?.join("sync").join("tasks"),
+		};
+		let calendar_path = match &self.calendar {
+			Some(path) => expand_tilde(path)?,
+			None => {
+				let mut path = OsString::from(&export_path);
+				path.push(".ics");
+				path.into()
+			}
+		};
+		Ok(StoreArgs { n_roots: self.n_roots.unwrap_or(DEFAULT_N_ROOTS), path, export_path, calendar_path })
+	}
+
+	fn read(path: &Path) -> Result<Self, Error> {
+		let s = fs::read_to_string(path).map_err(|e| match e.kind() {
+			ErrorKind::NotFound => Error::NotFound(path.into()),
+			_ => Error::IoError(e),
+		})?;
+		Config::parse(&s)
+	}
+}
+
+/// Options given on the command line which take precedence over the config file.
+#[derive(Default)]
+pub struct Overrides {
+	pub config: Option<PathBuf>,
+	pub data: Option<PathBuf>,
+	pub export: Option<PathBuf>,
+}
+
+pub fn default_config_path() -> Option<PathBuf> {
+	let mut path = dirs::config_dir()?;
+	path.push("grus");
+	path.push("config.toml");
+	Some(path)
+}
+
+pub fn expand_tilde(path: &Path) -> Result<PathBuf, Error> {
+	match path.strip_prefix("~") {
+		Ok(rest) => Ok(dirs::home_dir().ok_or(Error::NoHomeDir)?.join(rest)),
+		Err(_) => Ok(path.into()),
+	}
+}
+
+fn parse_n_roots(s: OsString) -> Result<usize, Error> {
+	s.to_str().and_then(|s| s.parse().ok()).ok_or(Error::InvalidNRoots(s))
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("Config file {0} does not exist")]
+	NotFound(PathBuf),
+	#[error("Config Error: {0}")]
+	ParseError(#[from] toml::de::Error),
+	#[error("Invalid number of roots: {0:?}")]
+	InvalidNRoots(OsString),
+	#[error("Data directory could not be determined.")]
+	NoDataDir,
+	#[error("Home directory could not be determined.")]
+	NoHomeDir,
+	#[error("IO Error: {0}")]
+	IoError(#[from] io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::Path;
+	use crate::node::TimeFormat;
+	use super::Config;
+
+	#[test]
+	fn parse_config() {
+		let config = Config::parse("data = \"/tmp/tasks\"\nexport = \"~/backup/tasks\"\nn_roots = 3\n").unwrap();
+		let args = config.store_args().unwrap();
+		assert_eq!(args.path, Path::new("/tmp/tasks"));
+		assert_eq!(args.export_path, dirs::home_dir().unwrap().join("backup/tasks"));
+		assert_eq!(args.n_roots, 3);
+		assert_eq!(args.calendar_path, dirs::home_dir().unwrap().join("backup/tasks.ics"));
+
+		let args = Config::parse("").unwrap().store_args().unwrap();
+		assert_eq!(args.n_roots, 2);
+		assert!(args.path.ends_with("grus/tasks"));
+
+		assert!(Config::parse("data_path = \"/tmp/tasks\"").is_err());
+
+		assert_eq!(Config::parse("time_format = \"24h\"").unwrap().time_format, TimeFormat::TwentyFour);
+		assert_eq!(Config::parse("").unwrap().time_format, TimeFormat::Twelve);
+		assert!(Config::parse("time_format = \"13h\"").is_err());
+
+		let config = Config::parse("[keys.tree]\ncursor_down = [\"n\", \"down\"]\ndelete = \"d d\"\n").unwrap();
+		assert_eq!(config.keys.tree.len(), 2);
+		assert!(Config::parse("[keys.tree]\ncursor_down = \"hyper-n\"\n").is_err());
+		assert!(Config::parse("[keys.tree]\nfly = \"f\"\n").is_err());
+	}
+}
@@ -0,0 +1,85 @@
+use std::io;
+use crossterm::event::{self, Event};
+use crate::app::{Action, Error, View};
+use crate::clock::Clock;
+use crate::keymap::{ConflictAction, Keymap};
+use crate::node::TimeFormat;
+use crate::sync::Conflict;
+use crate::ui::{BufPrint, Screen};
+use crate::ui::backend::Backend;
+use crate::ui::conflict::ConflictView;
+use crate::ui::status::StatusView;
+
+pub struct ConflictViewController<'c> {
+	conflict_view: ConflictView<'c>,
+	status_view: StatusView<{View::Conflict as usize}>,
+	keymap: Keymap<ConflictAction>,
+}
+
+impl<'c> ConflictViewController<'c> {
+	pub fn new(clock: &'c dyn Clock, time_format: TimeFormat, keymap: Keymap<ConflictAction>, (w, h): (u16, u16)) -> Result<Self, Error> {
+		Ok(ConflictViewController {
+			conflict_view: ConflictView::new(Vec::new(), clock, time_format, w, h),
+			status_view: StatusView::new(w, h),
+			keymap,
+		})
+	}
+
+	pub fn run(&mut self) -> Result<Action, Error> {
+		self.status_view.clear_message();
+		match event::read()? {
+			Event::Key(kev) => match self.keymap.feed(kev) {
+				Some(ConflictAction::Quit) => return Ok(Action::Quit),
+				Some(ConflictAction::CursorDown) => self.conflict_view.cursor_down(),
+				Some(ConflictAction::CursorUp) => self.conflict_view.cursor_up(),
+				Some(ConflictAction::KeepLocal) => return Ok(Action::Resolve(false)),
+				Some(ConflictAction::TakeImported) => return Ok(Action::Resolve(true)),
+				Some(ConflictAction::SwitchTree) => return Ok(Action::Switch(View::Tree)),
+				Some(ConflictAction::SwitchSession) => return Ok(Action::Switch(View::Session)),
+				Some(ConflictAction::SwitchAgenda) => return Ok(Action::Switch(View::Agenda)),
+				Some(ConflictAction::SwitchWeek) => return Ok(Action::Switch(View::Week)),
+				Some(ConflictAction::SwitchReport) => return Ok(Action::Switch(View::Report)),
+				None => {},
+			}
+			Event::Resize(w, h) => self.resize(w, h),
+			_ => {}
+		}
+		Ok(Action::None)
+	}
+
+	pub fn set_message(&mut self, message: String) {
+		self.status_view.set_message(message);
+	}
+
+	pub fn set_info(&mut self, info: String) {
+		self.status_view.set_info(info);
+	}
+
+	pub fn resize(&mut self, w: u16, h: u16) {
+		self.conflict_view.constr.update(w, h);
+		self.status_view.constr.update(w, h);
+	}
+
+	pub fn reset(&mut self, conflicts: Vec<Conflict>) {
+		self.conflict_view.reset(conflicts);
+	}
+
+	pub fn take(&mut self) -> Option<Conflict> {
+		self.conflict_view.take()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.conflict_view.is_empty()
+	}
+}
+
+impl<B: Backend> BufPrint<ConflictViewController<'_>> for Screen<B> {
+	fn bufprint(&mut self, cvc: &ConflictViewController) -> io::Result<&mut Self> {
+		self
+			.clear()?
+			.bufprint(&cvc.status_view)?
+			.bufprint(&cvc.conflict_view)?
+			.flush()?;
+		Ok(self)
+	}
+}
@@ -4,6 +4,7 @@ use crate::node::Node;
 
 pub struct FlatTreeBuilder {
 	height: usize,
+	by_score: bool,
 	fnodes: Vec<FNode>,
 	queue: VecDeque<FChildIter>,
 	start: usize,
@@ -14,7 +15,14 @@ impl FlatTreeBuilder {
 	pub fn new(root: Node<'static>, height: usize) -> Self {
 		let filled = root.height();
 		let fnodes = vec![FNode { node: root, path: vec![0] }];
-		FlatTreeBuilder { height, fnodes, queue: VecDeque::new(), start: 0, filled }
+		FlatTreeBuilder { height, by_score: false, fnodes, queue: VecDeque::new(), start: 0, filled }
+	}
+
+	/// Orders siblings by decreasing score instead of their manual priority, so that the most
+	/// urgent tasks are the ones which fit.
+	pub fn by_score(mut self, by_score: bool) -> Self {
+		self.by_score = by_score;
+		self
 	}
 
 	pub fn step(&mut self) -> FlatTreeState {
@@ -53,7 +61,15 @@ impl FlatTreeBuilder {
 	}
 
 	pub fn fill(&mut self, mut children: Vec<Node<'static>>, last: usize) {
-		children.sort_by(|l, r| l.priority.det.cmp(&r.priority.det));
+		if self.by_score {
+			// Siblings are drawn by their priority, which thus follows the score.
+			children.sort_by(|l, r| r.score.total_cmp(&l.score).then(l.priority.det.cmp(&r.priority.det)));
+			for (i, child) in children.iter_mut().enumerate() {
+				child.priority.det = i as u64;
+			}
+		} else {
+			children.sort_by_key(|node| node.priority.det);
+		}
 		self.queue.push_back(FChildIter {
 			iter: children.into_iter(),
 			last
@@ -150,9 +166,11 @@ mod tests {
 			pid,
 			depth: 0,
 			name: name.into(),
+			done: false,
 			due_date: None,
 			session: Some(Session::default()),
 			priority: pri,
+			score: 0.0,
 			name_splits: vec![0, 1],
 			session_text: "".into(),
 			session_splits: vec![0, 0],
@@ -0,0 +1,321 @@
+use std::collections::{BTreeMap, HashMap};
+use chrono::NaiveDateTime;
+use grus_lib::Store;
+use grus_lib::types::Session;
+use grus_lib::writer::StoreWriter;
+use crate::app::Error;
+use crate::meta::{Meta, TaskMeta};
+use crate::snapshot::Snapshot;
+
+/// The operations made in the interface, each kept as the state of the tasks it changed from
+/// before and after the operation. Undoing a step writes the earlier state back into the store,
+/// so a deleted task is created again, with a new id. Steps refer to tasks by the ids they had
+/// when the step was recorded, which are mapped to the ids of the tasks created in their place.
+#[derive(Default)]
+pub struct History {
+	undo: Vec<Step>,
+	redo: Vec<Step>,
+	ids: HashMap<u64, u64>,
+}
+
+struct Step {
+	undo: Patch,
+	redo: Patch,
+}
+
+/// The state of some tasks, which are created if they do not exist.
+type Patch = BTreeMap<u64, Task>;
+
+#[derive(Clone, PartialEq)]
+struct Task {
+	name: String,
+	due_date: Option<NaiveDateTime>,
+	sessions: Vec<Session>,
+	children: Vec<u64>,
+	/// The attributes kept in `.meta`, except for the timer, which runs on regardless of steps
+	/// being undone or made again.
+	meta: TaskMeta,
+}
+
+/// The state of every task, read before an operation to find out what it changes.
+pub struct Checkpoint {
+	tasks: BTreeMap<u64, Task>,
+}
+
+impl Checkpoint {
+	pub fn read(store: &Store, meta: &Meta) -> Result<Self, Error> {
+		let snapshot = Snapshot::read(&store.reader()?)?;
+		let tasks = snapshot.tasks.into_iter().map(|(id, task)| (id, Task {
+			name: task.name,
+			due_date: task.due_date,
+			sessions: task.sessions,
+			children: task.children,
+			meta: meta.task(id).map_or_else(TaskMeta::default, |task| TaskMeta { timer: None, ..task.clone() }),
+		})).collect();
+		Ok(Checkpoint { tasks })
+	}
+
+	/// Returns the tasks of `to` which are missing or different in `self`.
+	fn diff(&self, to: &Checkpoint) -> Patch {
+		to.tasks.iter()
+			.filter(|&(id, task)| self.tasks.get(id) != Some(task))
+			.map(|(&id, task)| (id, task.clone()))
+			.collect()
+	}
+}
+
+impl History {
+	/// Records the changes made since the checkpoint as a single step, unless nothing changed.
+	pub fn record(&mut self, checkpoint: Checkpoint, store: &Store, meta: &Meta) -> Result<(), Error> {
+		let current = Checkpoint::read(store, meta)?;
+		let step = Step { undo: current.diff(&checkpoint), redo: checkpoint.diff(&current) };
+		if step.undo.is_empty() && step.redo.is_empty() { return Ok(()) };
+
+		self.undo.push(step);
+		self.redo.clear();
+		Ok(())
+	}
+
+	/// Reverts the last step and returns the ids of the tasks it created again, keyed by the ids
+	/// they had before. Returns None if there is nothing to undo.
+	pub fn undo(&mut self, store: &Store, meta: &mut Meta) -> Result<Option<HashMap<u64, u64>>, Error> {
+		let Some(step) = self.undo.pop() else { return Ok(None) };
+		let Some(created) = self.apply(&step.undo, store, meta)? else { return Ok(None) };
+		self.redo.push(step);
+		Ok(Some(created))
+	}
+
+	/// Makes the last undone step again, returning the same as [`History::undo`]. Returns None if
+	/// there is nothing to redo.
+	pub fn redo(&mut self, store: &Store, meta: &mut Meta) -> Result<Option<HashMap<u64, u64>>, Error> {
+		let Some(step) = self.redo.pop() else { return Ok(None) };
+		let Some(created) = self.apply(&step.redo, store, meta)? else { return Ok(None) };
+		self.undo.push(step);
+		Ok(Some(created))
+	}
+
+	/// Writes the patch into the store. If the store was changed in a way the history does not
+	/// know of, such that the patch does not apply, nothing is written and the history is
+	/// forgotten.
+	fn apply(&mut self, patch: &Patch, store: &Store, meta: &mut Meta) -> Result<Option<HashMap<u64, u64>>, Error> {
+		let mut writer = store.writer()?;
+		let mut ids = self.ids.clone();
+		if !apply_patch(patch, &mut writer, &mut ids)? {
+			*self = History::default();
+			return Ok(None);
+		}
+		writer.commit()?;
+
+		for (&id, task) in patch {
+			let id = resolve(&ids, id);
+			let timer = meta.task(id).and_then(|task| task.timer);
+			meta.set_task(id, TaskMeta { timer, ..task.meta.clone() });
+		}
+		let created = ids.iter()
+			.filter(|(id, _)| !self.ids.contains_key(id))
+			.map(|(&id, &new)| (id, new))
+			.collect();
+		self.ids = ids;
+		Ok(Some(created))
+	}
+}
+
+fn resolve(ids: &HashMap<u64, u64>, mut id: u64) -> u64 {
+	while let Some(&new) = ids.get(&id) { id = new };
+	id
+}
+
+/// Tasks which lose a parent are first given a temporary parent, so that a task is only deleted
+/// once all of the links are in place. Deleting the temporary parent at the end deletes the
+/// tasks which are left without any other parent.
+fn apply_patch(patch: &Patch, writer: &mut StoreWriter, ids: &mut HashMap<u64, u64>) -> Result<bool, Error> {
+	let holder = writer.add_child(0, "undo")?;
+	for (&id, task) in patch {
+		let id = resolve(ids, id);
+		if writer.name(id)?.is_none() {
+			let new = writer.add_child(holder, &task.name)?;
+			ids.insert(id, new);
+		}
+	}
+
+	let mut links = Vec::new();
+	for (&id, task) in patch {
+		let id = resolve(ids, id);
+		if writer.name(id)? != Some(task.name.as_str()) { writer.rename(id, &task.name)? };
+		match task.due_date {
+			Some(due_date) => writer.set_due_date(id, due_date)?,
+			None => writer.unset_due_date(id)?,
+		}
+		let sessions = writer.sessions(id)?.map(|entry| entry.map(|(_, &session)| session)).collect::<Result<Vec<_>, _>>()?;
+		for session in sessions.iter().filter(|session| !task.sessions.contains(session)) {
+			writer.delete_session(id, session)?;
+		}
+		for session in task.sessions.iter().filter(|session| !sessions.contains(session)) {
+			writer.add_session(id, session)?;
+		}
+
+		let children: Vec<u64> = task.children.iter().map(|&child| resolve(ids, child)).collect();
+		let current = writer.child_ids(id)?.collect::<Result<Vec<_>, _>>()?;
+		for &child in current.iter().filter(|&&child| child != holder && !children.contains(&child)) {
+			writer.share(child, holder)?;
+			writer.delete(id, child)?;
+		}
+		links.push((id, children));
+	}
+
+	for (id, children) in &links {
+		let current = writer.child_ids(*id)?.collect::<Result<Vec<_>, _>>()?;
+		for &child in children.iter().filter(|child| !current.contains(child)) {
+			if !writer.share(child, *id)? { return Ok(false) };
+		}
+	}
+	writer.delete(0, holder)?;
+
+	for (id, children) in &links {
+		for (i, &child) in children.iter().enumerate() {
+			let Some(position) = writer.child_ids(*id)?.position(|c| c.is_ok_and(|c| c == child)) else {
+				return Ok(false);
+			};
+			for _ in i..position {
+				writer.move_up(*id, child)?;
+			}
+		}
+	}
+	Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{Duration, NaiveDate};
+	use grus_lib::Store;
+	use grus_lib::types::Session;
+	use crate::meta::Meta;
+	use crate::recurrence::{Repeating, Rule};
+	use crate::snapshot::Snapshot;
+	use crate::testing::TempDir;
+	use super::{Checkpoint, History};
+
+	fn outline(store: &Store, meta: &Meta) -> String {
+		fn render(snapshot: &Snapshot, meta: &Meta, id: u64) -> String {
+			let task = &snapshot.tasks[&id];
+			let mut s = task.name.clone();
+			if meta.done(id).is_some() { s += "!" };
+			if let Some(due_date) = task.due_date { s += &format!("({})", due_date) };
+			if !task.sessions.is_empty() { s += &format!("{{{}}}", task.sessions.len()) };
+			let children: Vec<_> = task.children.iter().map(|&id| render(snapshot, meta, id)).collect();
+			if !children.is_empty() { s += &format!("[{}]", children.join(" ")) };
+			s
+		}
+		render(&Snapshot::read(&store.reader().unwrap()).unwrap(), meta, 0)
+	}
+
+	#[test]
+	fn undo_redo() {
+		let dir = TempDir::new();
+		let store = Store::open(dir.join("tasks"), 2).unwrap();
+		let mut meta = Meta::default();
+		let mut history = History::default();
+		let start = NaiveDate::from_ymd_opt(2023, 7, 1).unwrap().and_hms_opt(14, 0, 0).unwrap();
+
+		let mut writer = store.writer().unwrap();
+		let a = writer.add_child(0, "a").unwrap();
+		let b = writer.add_child(0, "b").unwrap();
+		let c = writer.add_child(a, "c").unwrap();
+		writer.add_child(c, "d").unwrap();
+		let e = writer.add_child(a, "e").unwrap();
+		writer.share(c, b).unwrap();
+		writer.set_due_date(c, start).unwrap();
+		writer.add_session(e, &Session { start, end: start + Duration::hours(1) }).unwrap();
+		writer.commit().unwrap();
+		meta.mark_done(e, start);
+		let mut states = vec![outline(&store, &meta)];
+
+		let checkpoint = Checkpoint::read(&store, &meta).unwrap();
+		let mut writer = store.writer().unwrap();
+		writer.move_down(a, e).unwrap();
+		writer.rename(b, "B").unwrap();
+		writer.commit().unwrap();
+		meta.unmark_done(e);
+		history.record(checkpoint, &store, &meta).unwrap();
+		states.push(outline(&store, &meta));
+
+		let checkpoint = Checkpoint::read(&store, &meta).unwrap();
+		let mut writer = store.writer().unwrap();
+		writer.delete(0, a).unwrap();
+		writer.commit().unwrap();
+		history.record(checkpoint, &store, &meta).unwrap();
+		states.push(outline(&store, &meta));
+
+		let checkpoint = Checkpoint::read(&store, &meta).unwrap();
+		let mut writer = store.writer().unwrap();
+		let f = writer.add_child(b, "f").unwrap();
+		writer.cut(b, c, f).unwrap();
+		writer.commit().unwrap();
+		history.record(checkpoint, &store, &meta).unwrap();
+		states.push(outline(&store, &meta));
+		assert_eq!(states[3], "/[B[f[c(2023-07-01 14:00:00)[d]]]]");
+
+		for state in states.iter().rev().skip(1) {
+			assert!(history.undo(&store, &mut meta).unwrap().is_some());
+			assert_eq!(&outline(&store, &meta), state);
+		}
+		assert!(history.undo(&store, &mut meta).unwrap().is_none());
+		assert_eq!(states[0], "/[B[c(2023-07-01 14:00:00)[d]] a[e!{1} c(2023-07-01 14:00:00)[d]]]".replace('B', "b"));
+
+		for state in states.iter().skip(1) {
+			assert!(history.redo(&store, &mut meta).unwrap().is_some());
+			assert_eq!(&outline(&store, &meta), state);
+		}
+		assert!(history.redo(&store, &mut meta).unwrap().is_none());
+
+		history.undo(&store, &mut meta).unwrap();
+		history.undo(&store, &mut meta).unwrap();
+		let checkpoint = Checkpoint::read(&store, &meta).unwrap();
+		let mut writer = store.writer().unwrap();
+		writer.add_child(0, "g").unwrap();
+		writer.commit().unwrap();
+		history.record(checkpoint, &store, &meta).unwrap();
+		assert!(history.redo(&store, &mut meta).unwrap().is_none());
+	}
+
+	#[test]
+	fn repetitions() {
+		let dir = TempDir::new();
+		let store = Store::open(dir.join("tasks"), 2).unwrap();
+		let mut meta = Meta::default();
+		let mut history = History::default();
+		let start = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap().and_hms_opt(9, 0, 0).unwrap();
+		let session = Repeating { start, end: start + Duration::hours(1), rule: Rule::Weeks(1, vec![]) };
+
+		let mut writer = store.writer().unwrap();
+		let a = writer.add_child(0, "a").unwrap();
+		writer.set_due_date(a, start).unwrap();
+		writer.add_session(a, &session.session()).unwrap();
+		let b = writer.add_child(0, "b").unwrap();
+		writer.commit().unwrap();
+		meta.set_repeat(a, Some(Rule::Days(1)));
+		meta.add_repeating(a, session.clone());
+
+		let checkpoint = Checkpoint::read(&store, &meta).unwrap();
+		let mut writer = store.writer().unwrap();
+		writer.delete(0, a).unwrap();
+		writer.rename(b, "B").unwrap();
+		writer.commit().unwrap();
+		history.record(checkpoint, &store, &meta).unwrap();
+		// Moving repeating sessions on forgets the ones of deleted tasks.
+		meta.set_repeating(a, Vec::new());
+
+		meta.start_timer(b, start);
+		let created = history.undo(&store, &mut meta).unwrap().unwrap();
+		assert_eq!(created.keys().collect::<Vec<_>>(), [&a]);
+		let a = created[&a];
+		assert_eq!(outline(&store, &meta), "/[b a(2023-07-03 09:00:00){1}]");
+		assert_eq!(meta.repeat(a), Some(&Rule::Days(1)));
+		assert_eq!(meta.repeating().collect::<Vec<_>>(), [(a, &[session][..])]);
+		assert_eq!(meta.timers().count(), 1);
+
+		assert!(history.redo(&store, &mut meta).unwrap().is_some());
+		assert_eq!(outline(&store, &meta), "/[B]");
+		assert_eq!(meta.timers().count(), 1);
+	}
+}
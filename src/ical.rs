@@ -0,0 +1,657 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Weekday};
+use grus_lib::Store;
+use grus_lib::types::Session;
+use serde::{Deserialize, Serialize};
+use crate::app::{self, StoreArgs};
+use crate::snapshot::Snapshot;
+use crate::sync::with_suffix;
+
+const DATETIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+const PRODID: &str = "-//grus//grus//EN";
+
+/// Renders the schedule of every task as an RFC 5545 calendar. Sessions become events and due
+/// dates become todos. Times are written as floating local times, since the store does not
+/// record a timezone. `stamp` is the UTC time of the export.
+pub fn render(snapshot: &Snapshot, stamp: NaiveDateTime) -> String {
+	let stamp = format!("{}Z", stamp.format(DATETIME_FORMAT));
+	let paths = paths(snapshot);
+	let mut cal = Calendar::default();
+	cal.line("BEGIN", "VCALENDAR");
+	cal.line("VERSION", "2.0");
+	cal.line("PRODID", PRODID);
+	for (&id, task) in &snapshot.tasks {
+		if id == 0 { continue };
+		let summary = escape(&task.name);
+		let description = escape(&paths[&id]);
+
+		for session in &task.sessions {
+			let start = session.start.format(DATETIME_FORMAT).to_string();
+			let end = session.end.format(DATETIME_FORMAT).to_string();
+			cal.line("BEGIN", "VEVENT");
+			cal.line("UID", &format!("session-{}-{}-{}@grus", id, start, end));
+			cal.line("DTSTAMP", &stamp);
+			cal.line("DTSTART", &start);
+			cal.line("DTEND", &end);
+			cal.line("SUMMARY", &summary);
+			cal.line("DESCRIPTION", &description);
+			cal.line("END", "VEVENT");
+		}
+		if let Some(due_date) = task.due_date {
+			cal.line("BEGIN", "VTODO");
+			cal.line("UID", &format!("due-{}@grus", id));
+			cal.line("DTSTAMP", &stamp);
+			cal.line("DUE", &due_date.format(DATETIME_FORMAT).to_string());
+			cal.line("SUMMARY", &summary);
+			cal.line("DESCRIPTION", &description);
+			cal.line("END", "VTODO");
+		}
+	}
+	cal.line("END", "VCALENDAR");
+	cal.0
+}
+
+#[derive(Default)]
+struct Calendar(String);
+
+impl Calendar {
+	/// Appends a content line, folding it so that no line is longer than 75 octets.
+	fn line(&mut self, name: &str, value: &str) {
+		let line = format!("{}:{}", name, value);
+		let mut len = 0;
+		for c in line.chars() {
+			if len + c.len_utf8() > 75 {
+				self.0.push_str("\r\n ");
+				len = 1;
+			}
+			self.0.push(c);
+			len += c.len_utf8();
+		}
+		self.0.push_str("\r\n");
+	}
+}
+
+fn escape(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'\\' | ';' | ',' => { escaped.push('\\'); escaped.push(c); }
+			'\n' => escaped.push_str("\\n"),
+			_ => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+/// Returns the path of names from the root task to every task. A shared task is given the
+/// shortest of its paths.
+fn paths(snapshot: &Snapshot) -> HashMap<u64, String> {
+	let mut paths = HashMap::from([(0, String::new())]);
+	let mut queue = VecDeque::from([0]);
+	while let Some(id) = queue.pop_front() {
+		for &child in &snapshot.tasks[&id].children {
+			if paths.contains_key(&child) { continue };
+			let path = format!("{}/{}", paths[&id], snapshot.tasks[&child].name);
+			paths.insert(child, path);
+			queue.push_back(child);
+		}
+	}
+	paths
+}
+
+/// An event read from a calendar. `key` identifies the event across imports: its UID, followed
+/// by the RECURRENCE-ID of events which override a single instance of a recurring event.
+pub struct Event {
+	pub key: String,
+	pub summary: String,
+	pub session: Session,
+}
+
+/// Where imported events are attached: as sessions of a task, or as sessions of new child
+/// tasks of a task, one per event.
+#[derive(Copy, Clone)]
+pub enum Target {
+	Task(u64),
+	Children(u64),
+}
+
+/// Reads the events of a calendar. Times with a timezone are converted to local time, and
+/// floating times are taken as local time. Only the first instance of a recurring event is read.
+pub fn parse(ics: &str) -> Result<Vec<Event>, Error> {
+	let calendar = Component::parse(ics)?;
+	let mut timezones = HashMap::new();
+	for component in calendar.components("VTIMEZONE") {
+		let tzid = component.require("TZID")?.value.clone();
+		timezones.insert(tzid, Timezone::parse(component)?);
+	}
+
+	let mut events = Vec::new();
+	for component in calendar.components("VEVENT") {
+		let uid = component.require("UID")?;
+		let mut key = uid.value.clone();
+		if let Some(recurrence_id) = component.property("RECURRENCE-ID") {
+			key = format!("{}/{}", key, recurrence_id.value);
+		}
+		let summary = component.property("SUMMARY").map_or("Untitled event".into(), |p| unescape(&p.value));
+
+		let dtstart = component.require("DTSTART")?;
+		let start = dtstart.date_time(&timezones)?;
+		let end = match (component.property("DTEND"), component.property("DURATION")) {
+			(Some(dtend), _) => dtend.date_time(&timezones)?,
+			(None, Some(duration)) => start + duration.duration()?,
+			(None, None) if dtstart.is_date() => start + Duration::days(1),
+			(None, None) => start,
+		};
+		events.push(Event { key, summary, session: Session { start, end } });
+	}
+	Ok(events)
+}
+
+/// Remembers the task and session created for each imported event, so that importing a
+/// calendar again only applies the changes made to it since.
+#[derive(Default, Deserialize, Serialize)]
+struct ImportState {
+	events: BTreeMap<String, Imported>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Imported {
+	id: u64,
+	start: NaiveDateTime,
+	end: NaiveDateTime,
+}
+
+impl ImportState {
+	fn load(path: &Path) -> Result<Self, app::Error> {
+		match fs::read(path) {
+			Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+			Err(e) if e.kind() == ErrorKind::NotFound => Ok(ImportState::default()),
+			Err(e) => Err(e.into()),
+		}
+	}
+
+	fn save(&self, path: &Path) -> Result<(), app::Error> {
+		fs::write(path, serde_json::to_vec(self)?)?;
+		Ok(())
+	}
+}
+
+/// Points the imported events of tasks which were deleted and created again under new ids, as
+/// undoing does, to the new ids, so that importing the calendar again updates them.
+pub fn remap(args: &StoreArgs, ids: &HashMap<u64, u64>) -> Result<(), app::Error> {
+	if ids.is_empty() { return Ok(()) };
+	let path = with_suffix(&args.path, ".ical");
+	let mut state = ImportState::load(&path)?;
+	let mut moved = false;
+	for imported in state.events.values_mut() {
+		if let Some(&id) = ids.get(&imported.id) {
+			imported.id = id;
+			moved = true;
+		}
+	}
+	if moved { state.save(&path)? };
+	Ok(())
+}
+
+/// Adds the events to the store and returns the number of sessions which were added or moved.
+/// An event which was imported before updates the session it created, and is skipped if its task
+/// has since been deleted.
+pub fn import(store: &Store, args: &StoreArgs, events: &[Event], target: Target) -> Result<usize, app::Error> {
+	let path = with_suffix(&args.path, ".ical");
+	let mut state = ImportState::load(&path)?;
+
+	let mut writer = store.writer()?;
+	let mut count = 0;
+	for event in events {
+		let session = event.session;
+		let id = match state.events.get(&event.key) {
+			Some(imported) if imported.start == session.start && imported.end == session.end => continue,
+			Some(imported) if writer.name(imported.id)?.is_none() => continue,
+			Some(imported) => {
+				writer.delete_session(imported.id, &Session { start: imported.start, end: imported.end })?;
+				imported.id
+			}
+			None => match target {
+				Target::Task(id) => id,
+				Target::Children(pid) => writer.add_child(pid, &event.summary)?,
+			},
+		};
+		writer.add_session(id, &session)?;
+		state.events.insert(event.key.clone(), Imported { id, start: session.start, end: session.end });
+		count += 1;
+	}
+	writer.commit()?;
+
+	state.save(&path)?;
+	Ok(count)
+}
+
+struct Property {
+	line: usize,
+	name: String,
+	params: Vec<(String, String)>,
+	value: String,
+}
+
+impl Property {
+	fn parse(line: usize, text: &str) -> Result<Self, Error> {
+		let mut quoted = false;
+		let Some(colon) = text.char_indices().find_map(|(i, c)| match c {
+			'"' => { quoted = !quoted; None }
+			':' if !quoted => Some(i),
+			_ => None,
+		}) else {
+			return Err(Error::Syntax(line, "missing ':'".into()));
+		};
+
+		let mut parts = text[..colon].split(';');
+		let name = parts.next().unwrap_or_default().to_ascii_uppercase();
+		let params = parts.filter_map(|param| {
+			let (key, value) = param.split_once('=')?;
+			Some((key.to_ascii_uppercase(), value.trim_matches('"').to_string()))
+		}).collect();
+		Ok(Property { line, name, params, value: text[colon + 1..].to_string() })
+	}
+
+	fn param(&self, name: &str) -> Option<&str> {
+		self.params.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+	}
+
+	fn is_date(&self) -> bool {
+		self.param("VALUE") == Some("DATE") || self.value.len() == 8
+	}
+
+	fn date_time(&self, timezones: &HashMap<String, Timezone>) -> Result<NaiveDateTime, Error> {
+		let invalid = || Error::Syntax(self.line, format!("invalid date-time '{}'", self.value));
+		if self.is_date() {
+			let date = NaiveDate::parse_from_str(&self.value, "%Y%m%d").map_err(|_| invalid())?;
+			return Ok(date.and_time(NaiveTime::MIN));
+		}
+
+		let (value, utc) = match self.value.strip_suffix('Z') {
+			Some(value) => (value, true),
+			None => (self.value.as_str(), false),
+		};
+		let dt = NaiveDateTime::parse_from_str(value, DATETIME_FORMAT).map_err(|_| invalid())?;
+		let utc = match self.param("TZID") {
+			_ if utc => dt,
+			None => return Ok(dt),
+			Some(tzid) => match timezones.get(tzid) {
+				Some(timezone) => dt - Duration::seconds(timezone.offset(dt).into()),
+				None if ["UTC", "Etc/UTC", "GMT", "Z"].contains(&tzid) => dt,
+				None => return Err(Error::UnknownTimezone(tzid.into())),
+			}
+		};
+		Ok(Local.from_utc_datetime(&utc).naive_local())
+	}
+
+	fn duration(&self) -> Result<Duration, Error> {
+		let invalid = || Error::Syntax(self.line, format!("invalid duration '{}'", self.value));
+		let (sign, value) = match self.value.strip_prefix('-') {
+			Some(value) => (-1, value),
+			None => (1, self.value.trim_start_matches('+')),
+		};
+		let value = value.strip_prefix('P').ok_or_else(invalid)?;
+
+		let mut duration = Duration::zero();
+		let mut n = String::new();
+		let mut time = false;
+		for c in value.chars() {
+			let unit = match c {
+				'0'..='9' => { n.push(c); continue }
+				'T' => { time = true; continue }
+				'W' if !time => Duration::weeks(1),
+				'D' if !time => Duration::days(1),
+				'H' if time => Duration::hours(1),
+				'M' if time => Duration::minutes(1),
+				'S' if time => Duration::seconds(1),
+				_ => return Err(invalid()),
+			};
+			duration = duration + unit * n.parse().map_err(|_| invalid())?;
+			n.clear();
+		}
+		if !n.is_empty() { return Err(invalid()) };
+		Ok(duration * sign)
+	}
+}
+
+struct Component {
+	name: String,
+	properties: Vec<Property>,
+	children: Vec<Component>,
+}
+
+impl Component {
+	/// Parses the outermost component of a calendar, unfolding content lines first.
+	fn parse(ics: &str) -> Result<Self, Error> {
+		let mut lines: Vec<(usize, String)> = Vec::new();
+		for (i, line) in ics.lines().enumerate() {
+			match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+				(Some(rest), Some((_, last))) => last.push_str(rest),
+				_ if line.is_empty() => {}
+				_ => lines.push((i + 1, line.to_string())),
+			}
+		}
+
+		let mut stack = vec![Component { name: String::new(), properties: Vec::new(), children: Vec::new() }];
+		for (line, text) in lines {
+			let property = Property::parse(line, &text)?;
+			match property.name.as_str() {
+				"BEGIN" => stack.push(Component {
+					name: property.value.to_ascii_uppercase(),
+					properties: Vec::new(),
+					children: Vec::new(),
+				}),
+				"END" => {
+					let component = stack.pop().filter(|c| c.name.eq_ignore_ascii_case(&property.value) && !stack.is_empty())
+						.ok_or_else(|| Error::Syntax(line, format!("unexpected END:{}", property.value)))?;
+					stack.last_mut().unwrap().children.push(component);
+				}
+				_ => stack.last_mut().unwrap().properties.push(property),
+			}
+		}
+		if stack.len() > 1 { return Err(Error::Syntax(0, format!("missing END:{}", stack[stack.len() - 1].name))) };
+
+		stack.pop().unwrap().children.into_iter().find(|c| c.name == "VCALENDAR")
+			.ok_or_else(|| Error::Syntax(0, "missing VCALENDAR".into()))
+	}
+
+	fn components<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Component> {
+		self.children.iter().filter(move |c| c.name == name)
+	}
+
+	fn property(&self, name: &str) -> Option<&Property> {
+		self.properties.iter().find(|p| p.name == name)
+	}
+
+	fn require(&self, name: &'static str) -> Result<&Property, Error> {
+		self.property(name).ok_or_else(|| Error::MissingProperty(self.name.clone(), name))
+	}
+}
+
+/// A VTIMEZONE, made of observances which each apply a UTC offset from their onsets on. Yearly
+/// rules of the form "the nth weekday of a month" are supported, which covers the usual
+/// daylight saving time rules; other observances only take effect at their start.
+struct Timezone {
+	observances: Vec<Observance>,
+}
+
+struct Observance {
+	start: NaiveDateTime,
+	offset: i32,
+	rule: Option<YearlyRule>,
+}
+
+struct YearlyRule {
+	month: u32,
+	week: i32,
+	weekday: Weekday,
+	until: Option<NaiveDateTime>,
+}
+
+impl Timezone {
+	fn parse(component: &Component) -> Result<Self, Error> {
+		let mut observances = Vec::new();
+		for observance in component.children.iter().filter(|c| c.name == "STANDARD" || c.name == "DAYLIGHT") {
+			let start = observance.require("DTSTART")?.date_time(&HashMap::new())?;
+			let offset = observance.require("TZOFFSETTO")?;
+			let offset = parse_offset(&offset.value).ok_or_else(|| Error::Syntax(offset.line, format!("invalid offset '{}'", offset.value)))?;
+			let rule = observance.property("RRULE").and_then(|rrule| YearlyRule::parse(&rrule.value));
+			observances.push(Observance { start, offset, rule });
+		}
+		Ok(Timezone { observances })
+	}
+
+	/// Returns the UTC offset in seconds at a local time of this timezone.
+	fn offset(&self, dt: NaiveDateTime) -> i32 {
+		let mut latest: Option<(NaiveDateTime, i32)> = None;
+		for observance in &self.observances {
+			let onsets: Vec<_> = match &observance.rule {
+				Some(rule) => [dt.year() - 1, dt.year()].into_iter()
+					.filter_map(|year| rule.onset(year, observance.start.time()))
+					.filter(|&onset| onset >= observance.start && rule.until.is_none_or(|until| onset <= until))
+					.collect(),
+				None => vec![observance.start],
+			};
+			for onset in onsets {
+				if onset <= dt && latest.is_none_or(|(latest, _)| onset > latest) {
+					latest = Some((onset, observance.offset));
+				}
+			}
+		}
+		latest.map(|(_, offset)| offset)
+			.or_else(|| self.observances.iter().min_by_key(|o| o.start).map(|o| o.offset))
+			.unwrap_or(0)
+	}
+}
+
+impl YearlyRule {
+	fn parse(rrule: &str) -> Option<Self> {
+		let parts: HashMap<&str, &str> = rrule.split(';').filter_map(|part| part.split_once('=')).collect();
+		if parts.get("FREQ") != Some(&"YEARLY") { return None };
+		let month = parts.get("BYMONTH")?.parse().ok()?;
+		let byday = parts.get("BYDAY")?;
+		let (week, weekday) = byday.split_at(byday.len().checked_sub(2)?);
+		let weekday = match weekday {
+			"MO" => Weekday::Mon,
+			"TU" => Weekday::Tue,
+			"WE" => Weekday::Wed,
+			"TH" => Weekday::Thu,
+			"FR" => Weekday::Fri,
+			"SA" => Weekday::Sat,
+			"SU" => Weekday::Sun,
+			_ => return None,
+		};
+		let week = week.trim_start_matches('+').parse().ok()?;
+		let until = match parts.get("UNTIL") {
+			Some(until) => Some(NaiveDateTime::parse_from_str(until.trim_end_matches('Z'), DATETIME_FORMAT).ok()?),
+			None => None,
+		};
+		Some(YearlyRule { month, week, weekday, until })
+	}
+
+	fn onset(&self, year: i32, time: NaiveTime) -> Option<NaiveDateTime> {
+		let date = if self.week > 0 {
+			NaiveDate::from_weekday_of_month_opt(year, self.month, self.weekday, self.week.try_into().ok()?)?
+		} else {
+			let (next_year, next_month) = if self.month == 12 { (year + 1, 1) } else { (year, self.month + 1) };
+			let last = NaiveDate::from_ymd_opt(next_year, next_month, 1)?.pred_opt()?;
+			let back = (last.weekday().num_days_from_monday() + 7 - self.weekday.num_days_from_monday()) % 7;
+			last - Duration::days(back.into()) - Duration::weeks((-self.week - 1).into())
+		};
+		Some(date.and_time(time))
+	}
+}
+
+fn parse_offset(s: &str) -> Option<i32> {
+	let sign = match s.get(..1)? {
+		"+" => 1,
+		"-" => -1,
+		_ => return None,
+	};
+	let digits = &s[1..];
+	if !matches!(digits.len(), 4 | 6) || !digits.bytes().all(|b| b.is_ascii_digit()) { return None };
+	let hours: i32 = digits[..2].parse().ok()?;
+	let minutes: i32 = digits[2..4].parse().ok()?;
+	let seconds: i32 = digits.get(4..).filter(|s| !s.is_empty()).map_or(Some(0), |s| s.parse().ok())?;
+	Some(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+fn unescape(text: &str) -> String {
+	let mut unescaped = String::with_capacity(text.len());
+	let mut chars = text.chars();
+	while let Some(c) = chars.next() {
+		match c {
+			'\\' => match chars.next() {
+				Some('n' | 'N') => unescaped.push('\n'),
+				Some(c) => unescaped.push(c),
+				None => {}
+			}
+			_ => unescaped.push(c),
+		}
+	}
+	unescaped
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("Calendar line {0}: {1}")]
+	Syntax(usize, String),
+	#[error("{0} is missing the {1} property")]
+	MissingProperty(String, &'static str),
+	#[error("Unknown timezone {0}")]
+	UnknownTimezone(String),
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+	use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+	use grus_lib::types::Session;
+	use crate::app::StoreArgs;
+	use crate::history::{Checkpoint, History};
+	use crate::meta::Meta;
+	use crate::snapshot::{Snapshot, Task};
+	use crate::testing::TempDir;
+	use super::{import, parse, remap, render, Target};
+
+	fn task(name: &str, children: Vec<u64>) -> Task {
+		Task { name: name.into(), due_date: None, sessions: Vec::new(), children }
+	}
+
+	#[test]
+	fn render_calendar() {
+		let start = NaiveDate::from_ymd_opt(2023, 7, 1).unwrap().and_hms_opt(14, 0, 0).unwrap();
+		let mut report = task("report, draft", vec![]);
+		report.due_date = Some(start);
+		report.sessions.push(Session { start, end: start + chrono::Duration::minutes(90) });
+		let snapshot = Snapshot { tasks: BTreeMap::from([
+			(0, task("/", vec![1, 2])),
+			(1, task("work", vec![3])),
+			(2, task("home", vec![3])),
+			(3, report),
+		]) };
+
+		let ics = render(&snapshot, start);
+		let lines: Vec<_> = ics.split("\r\n").collect();
+		assert_eq!(lines[..3], ["BEGIN:VCALENDAR", "VERSION:2.0", "PRODID:-//grus//grus//EN"]);
+		assert_eq!(lines[3..11], [
+			"BEGIN:VEVENT",
+			"UID:session-3-20230701T140000-20230701T153000@grus",
+			"DTSTAMP:20230701T140000Z",
+			"DTSTART:20230701T140000",
+			"DTEND:20230701T153000",
+			"SUMMARY:report\\, draft",
+			"DESCRIPTION:/work/report\\, draft",
+			"END:VEVENT",
+		]);
+		assert_eq!(lines[11..15], ["BEGIN:VTODO", "UID:due-3@grus", "DTSTAMP:20230701T140000Z", "DUE:20230701T140000"]);
+		assert_eq!(lines[18..], ["END:VCALENDAR", ""]);
+
+		let long = Snapshot { tasks: BTreeMap::from([(0, task("/", vec![1])), (1, Task {
+			sessions: vec![Session { start, end: start }],
+			..task(&"é".repeat(80), vec![])
+		})]) };
+		assert!(render(&long, start).split("\r\n").all(|line| line.len() <= 75));
+	}
+
+	fn local(utc: &str) -> NaiveDateTime {
+		let utc = NaiveDateTime::parse_from_str(utc, "%Y-%m-%d %H:%M").unwrap();
+		Local.from_utc_datetime(&utc).naive_local()
+	}
+
+	const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VTIMEZONE\r
+TZID:America/New_York\r
+BEGIN:DAYLIGHT\r
+DTSTART:20070311T020000\r
+TZOFFSETFROM:-0500\r
+TZOFFSETTO:-0400\r
+RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=2SU\r
+END:DAYLIGHT\r
+BEGIN:STANDARD\r
+DTSTART:20071104T020000\r
+TZOFFSETFROM:-0400\r
+TZOFFSETTO:-0500\r
+RRULE:FREQ=YEARLY;BYMONTH=11;BYDAY=1SU\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+UID:standup\r
+SUMMARY:Stand\\, up\r
+DTSTART;TZID=America/New_York:20230701T090000\r
+DTEND;TZID=\"America/New_York\":20230701T091500\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:review\r
+SUMMARY:Code\r
+  review\r
+DTSTART:20231215T150000Z\r
+DURATION:PT1H30M\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:holiday\r
+DTSTART;VALUE=DATE:20231225\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+	#[test]
+	fn parse_calendar() {
+		let events = parse(CALENDAR).unwrap();
+		assert_eq!(events.len(), 3);
+		assert_eq!(events[0].key, "standup");
+		assert_eq!(events[0].summary, "Stand, up");
+		assert_eq!(events[0].session.start, local("2023-07-01 13:00"));
+		assert_eq!(events[0].session.end, local("2023-07-01 13:15"));
+		assert_eq!(events[1].summary, "Code review");
+		assert_eq!(events[1].session.start, local("2023-12-15 15:00"));
+		assert_eq!(events[1].session.end, local("2023-12-15 16:30"));
+		assert_eq!(events[2].summary, "Untitled event");
+		assert_eq!(events[2].session.end - events[2].session.start, chrono::Duration::days(1));
+
+		let winter = CALENDAR.replace("20230701T09", "20230105T09");
+		assert_eq!(parse(&winter).unwrap()[0].session.start, local("2023-01-05 14:00"));
+		assert!(parse(&CALENDAR.replace("TZID:America/New_York", "TZID:Europe/Paris")).is_err());
+		assert!(parse("BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nEND:VCALENDAR\r\n").is_err());
+	}
+
+	#[test]
+	fn reimport_calendar() {
+		let dir = TempDir::new();
+		let args = StoreArgs {
+			n_roots: 2,
+			path: dir.join("tasks"),
+			export_path: dir.join("export"),
+			calendar_path: dir.join("tasks.ics"),
+		};
+		let store = args.open().unwrap();
+
+		let events = parse(CALENDAR).unwrap();
+		assert_eq!(import(&store, &args, &events, Target::Children(0)).unwrap(), 3);
+		assert_eq!(import(&store, &args, &events, Target::Children(0)).unwrap(), 0);
+
+		let moved = parse(&CALENDAR.replace("20231215T15", "20231215T16")).unwrap();
+		assert_eq!(import(&store, &args, &moved, Target::Children(0)).unwrap(), 1);
+
+		let snapshot = Snapshot::read(&store.reader().unwrap()).unwrap();
+		assert_eq!(snapshot.tasks.len(), 4);
+		let (&id, review) = snapshot.tasks.iter().find(|(_, task)| task.name == "Code review").unwrap();
+		assert_eq!(review.sessions, [Session { start: local("2023-12-15 16:00"), end: local("2023-12-15 17:30") }]);
+
+		let mut meta = Meta::default();
+		let mut history = History::default();
+		let checkpoint = Checkpoint::read(&store, &meta).unwrap();
+		let mut writer = store.writer().unwrap();
+		writer.delete(0, id).unwrap();
+		writer.commit().unwrap();
+		history.record(checkpoint, &store, &meta).unwrap();
+		remap(&args, &history.undo(&store, &mut meta).unwrap().unwrap()).unwrap();
+		assert_eq!(import(&store, &args, &parse(CALENDAR).unwrap(), Target::Children(0)).unwrap(), 1);
+		assert_eq!(Snapshot::read(&store.reader().unwrap()).unwrap().tasks.len(), 4);
+	}
+}
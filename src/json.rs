@@ -0,0 +1,179 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use chrono::NaiveDateTime;
+use grus_lib::types::Session;
+use grus_lib::writer::StoreWriter;
+use serde::{Deserialize, Serialize};
+use crate::meta::{Meta, TaskMeta};
+use crate::recurrence::{Repeating, Rule};
+use crate::snapshot::Snapshot;
+
+const VERSION: u32 = 1;
+
+/// The whole multitree in a format which does not depend on the storage format of the store.
+/// Links are stored as the ordered list of children of each task, so shared children appear in
+/// the list of every parent. The attributes kept in `.meta` come along, except for running
+/// timers.
+#[derive(Deserialize, Serialize)]
+pub struct Document {
+	pub version: u32,
+	pub tasks: Vec<Task>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Task {
+	pub id: u64,
+	pub name: String,
+	pub due_date: Option<NaiveDateTime>,
+	pub sessions: Vec<JsonSession>,
+	pub children: Vec<u64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub done: Option<NaiveDateTime>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub repeat: Option<Rule>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub repeating: Vec<Repeating>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct JsonSession {
+	pub start: NaiveDateTime,
+	pub end: NaiveDateTime,
+}
+
+impl Document {
+	pub fn new(snapshot: &Snapshot, meta: &Meta) -> Self {
+		let tasks = snapshot.tasks.iter().map(|(&id, task)| {
+			let task_meta = meta.task(id).cloned().unwrap_or_default();
+			Task {
+				id,
+				name: task.name.clone(),
+				due_date: task.due_date,
+				sessions: task.sessions.iter().map(|s| JsonSession { start: s.start, end: s.end }).collect(),
+				children: task.children.clone(),
+				done: task_meta.done,
+				repeat: task_meta.repeat,
+				repeating: task_meta.repeating,
+			}
+		}).collect();
+		Document { version: VERSION, tasks }
+	}
+
+	/// Adds every task of the document to the store. The children of the root task of the
+	/// document become children of the root task of the store, and the rest of the tasks get new
+	/// ids, under which their attributes are added to `meta`. Returns the number of tasks added.
+	pub fn write(&self, writer: &mut StoreWriter, meta: &mut Meta) -> Result<usize, Error> {
+		if self.version != VERSION { return Err(Error::Version(self.version)) };
+		let tasks: HashMap<u64, &Task> = self.tasks.iter().map(|task| (task.id, task)).collect();
+		if tasks.len() != self.tasks.len() { return Err(Error::Invalid("duplicate task ids".into())) };
+		if !tasks.contains_key(&0) { return Err(Error::Invalid("missing root task".into())) };
+
+		let mut ids = HashMap::from([(0, 0)]);
+		let mut visited = HashSet::from([0]);
+		let mut queue = VecDeque::from([0]);
+		while let Some(jpid) = queue.pop_front() {
+			let pid = ids[&jpid];
+			for &jid in tasks[&jpid].children.iter().rev() {
+				let Some(task) = tasks.get(&jid) else {
+					return Err(Error::Invalid(format!("task {} has missing child {}", jpid, jid)));
+				};
+				match ids.get(&jid) {
+					Some(&id) => if !writer.share(id, pid)? {
+						return Err(Error::Invalid(format!("link from task {} to {} forms a cycle", jpid, jid)));
+					},
+					None => {
+						let id = writer.add_child(pid, &task.name)?;
+						if let Some(due_date) = task.due_date { writer.set_due_date(id, due_date)? };
+						for session in &task.sessions {
+							writer.add_session(id, &Session { start: session.start, end: session.end })?;
+						}
+						meta.set_task(id, TaskMeta {
+							done: task.done,
+							timer: None,
+							repeat: task.repeat.clone(),
+							repeating: task.repeating.clone(),
+						});
+						ids.insert(jid, id);
+					}
+				}
+				if visited.insert(jid) { queue.push_back(jid) };
+			}
+		}
+		Ok(ids.len() - 1)
+	}
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("Unsupported version {0}")]
+	Version(u32),
+	#[error("Invalid document: {0}")]
+	Invalid(String),
+	#[error("Store Error: {0}")]
+	StoreError(#[from] sanakirja::Error),
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::NaiveDate;
+	use grus_lib::Store;
+	use grus_lib::types::Session;
+	use crate::meta::Meta;
+	use crate::recurrence::{Repeating, Rule};
+	use crate::snapshot::Snapshot;
+	use crate::testing::TempDir;
+	use super::Document;
+
+	fn outline(snapshot: &Snapshot, meta: &Meta, id: u64) -> String {
+		let task = &snapshot.tasks[&id];
+		let mut s = task.name.clone();
+		if meta.done(id).is_some() { s += "!" };
+		if let Some(rule) = meta.repeat(id) { s += &format!("<{}>", rule) };
+		if let Some(due_date) = task.due_date { s += &format!("({})", due_date) };
+		let sessions: Vec<_> = task.sessions.iter().map(|s| format!("{}-{}", s.start, s.end)).collect();
+		if !sessions.is_empty() { s += &format!("{{{}}}", sessions.join(",")) };
+		let children: Vec<_> = task.children.iter().map(|&id| outline(snapshot, meta, id)).collect();
+		if !children.is_empty() { s += &format!("[{}]", children.join(" ")) };
+		s
+	}
+
+	#[test]
+	fn round_trip() {
+		let dir = TempDir::new();
+		let store = Store::open(dir.join("src"), 2).unwrap();
+
+		let start = NaiveDate::from_ymd_opt(2023, 7, 1).unwrap().and_hms_opt(14, 0, 0).unwrap();
+		let session = Session { start, end: start + chrono::Duration::hours(2) };
+		let mut writer = store.writer().unwrap();
+		let a = writer.add_child(0, "a").unwrap();
+		let b = writer.add_child(0, "b").unwrap();
+		let c = writer.add_child(a, "c").unwrap();
+		writer.add_child(a, "d").unwrap();
+		writer.share(c, b).unwrap();
+		writer.set_due_date(c, start).unwrap();
+		writer.add_session(b, &session).unwrap();
+		writer.commit().unwrap();
+		let mut meta = Meta::default();
+		meta.mark_done(a, start);
+		meta.set_repeat(c, Some(Rule::Days(2)));
+		meta.add_repeating(b, Repeating { start: session.start, end: session.end, rule: Rule::Weeks(1, Vec::new()) });
+
+		let snapshot = Snapshot::read(&store.reader().unwrap()).unwrap();
+		let json = serde_json::to_string(&Document::new(&snapshot, &meta)).unwrap();
+		let document: Document = serde_json::from_str(&json).unwrap();
+		let copy = Store::open(dir.join("dst"), 2).unwrap();
+		let mut meta_copy = Meta::default();
+		let mut writer = copy.writer().unwrap();
+		assert_eq!(document.write(&mut writer, &mut meta_copy).unwrap(), 4);
+		writer.commit().unwrap();
+
+		let snapshot_copy = Snapshot::read(&copy.reader().unwrap()).unwrap();
+		assert_eq!(outline(&snapshot_copy, &meta_copy, 0), outline(&snapshot, &meta, 0));
+		assert_eq!(outline(&snapshot, &meta, 0), "/[b{2023-07-01 14:00:00-2023-07-01 16:00:00}[c<every 2 days>(2023-07-01 14:00:00)] a![d c<every 2 days>(2023-07-01 14:00:00)]]");
+		let repeating: Vec<_> = meta_copy.repeating().map(|(id, repeating)| (snapshot_copy.tasks[&id].name.as_str(), repeating.len())).collect();
+		assert_eq!(repeating, [("b", 1)]);
+
+		// Documents written before the attributes were exported are read as before.
+		let document: Document = serde_json::from_str(r#"{"version":1,"tasks":[{"id":0,"name":"/","due_date":null,"sessions":[],"children":[]}]}"#).unwrap();
+		assert!(document.tasks[0].done.is_none());
+	}
+}
@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::hash::Hash;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+/// Maps sequences of key chords to actions. Keys are fed one at a time; a key which begins a
+/// longer sequence is held until the sequence either completes or is broken.
+pub struct Keymap<A> {
+	bindings: HashMap<Vec<KeyChord>, A>,
+	pending: Vec<KeyChord>,
+}
+
+impl<A: Copy + Eq + Hash> Keymap<A> {
+	/// Builds a keymap from the default bindings, replacing the keys of any action which is
+	/// present in `custom`.
+	pub fn new(defaults: &[(A, &[&str])], custom: &HashMap<A, KeySequences>) -> Self {
+		let mut bindings = HashMap::new();
+		for &(action, keys) in defaults {
+			if custom.contains_key(&action) { continue };
+			for key in keys.iter() {
+				let seq = parse_sequence(key).expect("default key bindings are valid");
+				bindings.insert(seq, action);
+			}
+		}
+		for (&action, KeySequences(seqs)) in custom {
+			for seq in seqs {
+				bindings.insert(seq.clone(), action);
+			}
+		}
+		Keymap { bindings, pending: Vec::new() }
+	}
+
+	pub fn feed(&mut self, kev: KeyEvent) -> Option<A> {
+		let chord = KeyChord::from(kev);
+		self.pending.push(chord);
+		if let Some(&action) = self.bindings.get(&self.pending) {
+			self.pending.clear();
+			return Some(action);
+		}
+		if self.is_prefix() { return None };
+
+		let retry = self.pending.len() > 1;
+		self.pending.clear();
+		if retry { self.feed(kev) } else { None }
+	}
+
+	fn is_prefix(&self) -> bool {
+		self.bindings.keys().any(|seq| seq.starts_with(&self.pending))
+	}
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeAction {
+	Quit,
+	CursorDown,
+	CursorUp,
+	MoveOut,
+	MoveInto,
+	Select,
+	Share,
+	Cut,
+	AddChild,
+	Rename,
+	SetDueDate,
+	AddSession,
+	UnsetDueDate,
+	PriorityUp,
+	PriorityDown,
+	Delete,
+	ViewSessions,
+	SwitchSession,
+	Import,
+	Export,
+	ExportCalendar,
+	ImportCalendarSessions,
+	ImportCalendarTasks,
+	ExportOutline,
+	ImportOutline,
+	ToggleDone,
+	ToggleShowDone,
+	Undo,
+	Redo,
+	Search,
+	ToggleSortByScore,
+	SwitchAgenda,
+	SwitchWeek,
+	SwitchReport,
+	StartTimer,
+	StopTimer,
+}
+
+pub const TREE_DEFAULTS: &[(TreeAction, &[&str])] = &[
+	(TreeAction::Quit, &["q"]),
+	(TreeAction::CursorDown, &["j", "down"]),
+	(TreeAction::CursorUp, &["k", "up"]),
+	(TreeAction::MoveOut, &["h", "left"]),
+	(TreeAction::MoveInto, &["l", "right"]),
+	(TreeAction::Select, &["space"]),
+	(TreeAction::Share, &["."]),
+	(TreeAction::Cut, &["x"]),
+	(TreeAction::AddChild, &["a"]),
+	(TreeAction::Rename, &["r"]),
+	(TreeAction::SetDueDate, &["z"]),
+	(TreeAction::AddSession, &["s"]),
+	(TreeAction::UnsetDueDate, &["Z"]),
+	(TreeAction::PriorityUp, &["K"]),
+	(TreeAction::PriorityDown, &["J"]),
+	(TreeAction::Delete, &["D"]),
+	(TreeAction::ViewSessions, &["v"]),
+	(TreeAction::SwitchSession, &["2"]),
+	(TreeAction::Import, &["I"]),
+	(TreeAction::Export, &["E"]),
+	(TreeAction::ExportCalendar, &["C"]),
+	(TreeAction::ImportCalendarSessions, &["c s"]),
+	(TreeAction::ImportCalendarTasks, &["c t"]),
+	(TreeAction::ExportOutline, &["O"]),
+	(TreeAction::ImportOutline, &["o"]),
+	(TreeAction::ToggleDone, &["X"]),
+	(TreeAction::ToggleShowDone, &["H"]),
+	(TreeAction::Undo, &["u"]),
+	(TreeAction::Redo, &["ctrl-r"]),
+	(TreeAction::Search, &["/"]),
+	(TreeAction::ToggleSortByScore, &["S"]),
+	(TreeAction::SwitchAgenda, &["3"]),
+	(TreeAction::SwitchWeek, &["4"]),
+	(TreeAction::SwitchReport, &["5"]),
+	(TreeAction::StartTimer, &["t"]),
+	(TreeAction::StopTimer, &["T"]),
+];
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionAction {
+	Quit,
+	CursorDown,
+	CursorUp,
+	Delete,
+	ToggleMode,
+	SwitchTree,
+	SwitchAgenda,
+	SwitchWeek,
+	SwitchReport,
+	Undo,
+	Redo,
+}
+
+pub const SESSION_DEFAULTS: &[(SessionAction, &[&str])] = &[
+	(SessionAction::Quit, &["q"]),
+	(SessionAction::CursorDown, &["j", "down"]),
+	(SessionAction::CursorUp, &["k", "up"]),
+	(SessionAction::Delete, &["D"]),
+	(SessionAction::ToggleMode, &["v"]),
+	(SessionAction::SwitchTree, &["1"]),
+	(SessionAction::SwitchAgenda, &["3"]),
+	(SessionAction::SwitchWeek, &["4"]),
+	(SessionAction::SwitchReport, &["5"]),
+	(SessionAction::Undo, &["u"]),
+	(SessionAction::Redo, &["ctrl-r"]),
+];
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictAction {
+	Quit,
+	CursorDown,
+	CursorUp,
+	KeepLocal,
+	TakeImported,
+	SwitchTree,
+	SwitchSession,
+	SwitchAgenda,
+	SwitchWeek,
+	SwitchReport,
+}
+
+pub const CONFLICT_DEFAULTS: &[(ConflictAction, &[&str])] = &[
+	(ConflictAction::Quit, &["q"]),
+	(ConflictAction::CursorDown, &["j", "down"]),
+	(ConflictAction::CursorUp, &["k", "up"]),
+	(ConflictAction::KeepLocal, &["l"]),
+	(ConflictAction::TakeImported, &["i"]),
+	(ConflictAction::SwitchTree, &["1"]),
+	(ConflictAction::SwitchSession, &["2"]),
+	(ConflictAction::SwitchAgenda, &["3"]),
+	(ConflictAction::SwitchWeek, &["4"]),
+	(ConflictAction::SwitchReport, &["5"]),
+];
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgendaAction {
+	Quit,
+	CursorDown,
+	CursorUp,
+	Delete,
+	ViewSessions,
+	ShowTask,
+	SwitchTree,
+	SwitchSession,
+	SwitchWeek,
+	SwitchReport,
+	Undo,
+	Redo,
+}
+
+pub const AGENDA_DEFAULTS: &[(AgendaAction, &[&str])] = &[
+	(AgendaAction::Quit, &["q"]),
+	(AgendaAction::CursorDown, &["j", "down"]),
+	(AgendaAction::CursorUp, &["k", "up"]),
+	(AgendaAction::Delete, &["D"]),
+	(AgendaAction::ViewSessions, &["v"]),
+	(AgendaAction::ShowTask, &["enter"]),
+	(AgendaAction::SwitchTree, &["1"]),
+	(AgendaAction::SwitchSession, &["2"]),
+	(AgendaAction::SwitchWeek, &["4"]),
+	(AgendaAction::SwitchReport, &["5"]),
+	(AgendaAction::Undo, &["u"]),
+	(AgendaAction::Redo, &["ctrl-r"]),
+];
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WeekAction {
+	Quit,
+	CursorDown,
+	CursorUp,
+	CursorLeft,
+	CursorRight,
+	NextWeek,
+	PreviousWeek,
+	Today,
+	NextSession,
+	PreviousSession,
+	Select,
+	AddSession,
+	Delete,
+	ViewSessions,
+	SwitchTree,
+	SwitchSession,
+	SwitchAgenda,
+	SwitchReport,
+	Undo,
+	Redo,
+}
+
+pub const WEEK_DEFAULTS: &[(WeekAction, &[&str])] = &[
+	(WeekAction::Quit, &["q"]),
+	(WeekAction::CursorDown, &["j", "down"]),
+	(WeekAction::CursorUp, &["k", "up"]),
+	(WeekAction::CursorLeft, &["h", "left"]),
+	(WeekAction::CursorRight, &["l", "right"]),
+	(WeekAction::NextWeek, &["L", "pagedown"]),
+	(WeekAction::PreviousWeek, &["H", "pageup"]),
+	(WeekAction::Today, &["t"]),
+	(WeekAction::NextSession, &["n"]),
+	(WeekAction::PreviousSession, &["N"]),
+	(WeekAction::Select, &["space"]),
+	(WeekAction::AddSession, &["s"]),
+	(WeekAction::Delete, &["D"]),
+	(WeekAction::ViewSessions, &["v"]),
+	(WeekAction::SwitchTree, &["1"]),
+	(WeekAction::SwitchSession, &["2"]),
+	(WeekAction::SwitchAgenda, &["3"]),
+	(WeekAction::SwitchReport, &["5"]),
+	(WeekAction::Undo, &["u"]),
+	(WeekAction::Redo, &["ctrl-r"]),
+];
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportAction {
+	Quit,
+	CursorDown,
+	CursorUp,
+	NextPeriod,
+	PreviousPeriod,
+	TogglePeriod,
+	ShowTask,
+	SwitchTree,
+	SwitchSession,
+	SwitchAgenda,
+	SwitchWeek,
+}
+
+pub const REPORT_DEFAULTS: &[(ReportAction, &[&str])] = &[
+	(ReportAction::Quit, &["q"]),
+	(ReportAction::CursorDown, &["j", "down"]),
+	(ReportAction::CursorUp, &["k", "up"]),
+	(ReportAction::NextPeriod, &["L", "pagedown"]),
+	(ReportAction::PreviousPeriod, &["H", "pageup"]),
+	(ReportAction::TogglePeriod, &["p"]),
+	(ReportAction::ShowTask, &["enter"]),
+	(ReportAction::SwitchTree, &["1"]),
+	(ReportAction::SwitchSession, &["2"]),
+	(ReportAction::SwitchAgenda, &["3"]),
+	(ReportAction::SwitchWeek, &["4"]),
+];
+
+/// The `[keys]` table of the config file.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyConfig {
+	pub tree: HashMap<TreeAction, KeySequences>,
+	pub session: HashMap<SessionAction, KeySequences>,
+	pub conflict: HashMap<ConflictAction, KeySequences>,
+	pub agenda: HashMap<AgendaAction, KeySequences>,
+	pub week: HashMap<WeekAction, KeySequences>,
+	pub report: HashMap<ReportAction, KeySequences>,
+}
+
+/// One or more key sequences bound to a single action, written either as a string or as an
+/// array of strings.
+#[derive(Deserialize)]
+#[serde(try_from = "OneOrMany")]
+pub struct KeySequences(Vec<Vec<KeyChord>>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+	One(String),
+	Many(Vec<String>),
+}
+
+impl TryFrom<OneOrMany> for KeySequences {
+	type Error = InvalidKey;
+
+	fn try_from(keys: OneOrMany) -> Result<Self, InvalidKey> {
+		let keys = match keys {
+			OneOrMany::One(key) => vec![key],
+			OneOrMany::Many(keys) => keys,
+		};
+		keys.iter().map(|key| parse_sequence(key)).collect::<Result<_, _>>().map(KeySequences)
+	}
+}
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct KeyChord {
+	code: KeyCode,
+	modifiers: KeyModifiers,
+}
+
+impl From<KeyEvent> for KeyChord {
+	fn from(kev: KeyEvent) -> Self {
+		KeyChord::new(kev.code, kev.modifiers)
+	}
+}
+
+impl KeyChord {
+	/// Shift is folded into the character itself, since terminals report `J` both with and
+	/// without the shift modifier.
+	fn new(code: KeyCode, mut modifiers: KeyModifiers) -> Self {
+		let code = match code {
+			KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+				modifiers.remove(KeyModifiers::SHIFT);
+				KeyCode::Char(c.to_ascii_uppercase())
+			}
+			code => code,
+		};
+		KeyChord { code, modifiers: modifiers & (KeyModifiers::SHIFT | KeyModifiers::CONTROL | KeyModifiers::ALT) }
+	}
+}
+
+/// Parses a space separated sequence of chords such as `g g` or `ctrl-r`.
+pub fn parse_sequence(s: &str) -> Result<Vec<KeyChord>, InvalidKey> {
+	let seq: Vec<_> = s.split_whitespace().map(parse_chord).collect::<Result<_, _>>()?;
+	if seq.is_empty() { return Err(InvalidKey(s.into())) };
+	Ok(seq)
+}
+
+fn parse_chord(s: &str) -> Result<KeyChord, InvalidKey> {
+	let mut modifiers = KeyModifiers::NONE;
+	let mut key = s;
+	while let Some((modifier, rest)) = key.split_once('-') {
+		if rest.is_empty() { break };
+		modifiers |= match modifier.to_ascii_lowercase().as_str() {
+			"ctrl" => KeyModifiers::CONTROL,
+			"alt" => KeyModifiers::ALT,
+			"shift" => KeyModifiers::SHIFT,
+			_ => return Err(InvalidKey(s.into())),
+		};
+		key = rest;
+	}
+
+	let mut chars = key.chars();
+	let code = match (chars.next(), chars.next()) {
+		(Some(c), None) => KeyCode::Char(c),
+		_ => match key.to_ascii_lowercase().as_str() {
+			"space" => KeyCode::Char(' '),
+			"up" => KeyCode::Up,
+			"down" => KeyCode::Down,
+			"left" => KeyCode::Left,
+			"right" => KeyCode::Right,
+			"enter" => KeyCode::Enter,
+			"esc" => KeyCode::Esc,
+			"tab" => KeyCode::Tab,
+			"backspace" => KeyCode::Backspace,
+			"delete" => KeyCode::Delete,
+			"home" => KeyCode::Home,
+			"end" => KeyCode::End,
+			"pageup" => KeyCode::PageUp,
+			"pagedown" => KeyCode::PageDown,
+			f => match f.strip_prefix('f').and_then(|n| n.parse().ok()) {
+				Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+				_ => return Err(InvalidKey(s.into())),
+			}
+		}
+	};
+	Ok(KeyChord::new(code, modifiers))
+}
+
+#[derive(Debug)]
+pub struct InvalidKey(String);
+
+impl Display for InvalidKey {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "invalid key '{}'", self.0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+	use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+	use super::{parse_sequence, Keymap, KeySequences, TreeAction, TREE_DEFAULTS};
+
+	fn key(c: char) -> KeyEvent {
+		KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+	}
+
+	#[test]
+	fn parse_keys() {
+		assert_eq!(parse_sequence("J").unwrap(), parse_sequence("shift-j").unwrap());
+		assert_eq!(parse_sequence("ctrl-r").unwrap(), parse_sequence("Ctrl-r").unwrap());
+		assert_eq!(parse_sequence("g g").unwrap().len(), 2);
+		assert_eq!(parse_sequence("-").unwrap().len(), 1);
+		assert_ne!(parse_sequence("ctrl--").unwrap(), parse_sequence("-").unwrap());
+		assert!(parse_sequence("hyper-x").is_err());
+		assert!(parse_sequence("f13").is_err());
+		assert!(parse_sequence("").is_err());
+	}
+
+	#[test]
+	fn keymap_lookup() {
+		let custom = HashMap::from([
+			(TreeAction::CursorDown, KeySequences(vec![parse_sequence("n").unwrap()])),
+			(TreeAction::Delete, KeySequences(vec![parse_sequence("d d").unwrap()])),
+		]);
+		let mut keymap = Keymap::new(TREE_DEFAULTS, &custom);
+
+		assert_eq!(keymap.feed(key('n')), Some(TreeAction::CursorDown));
+		assert_eq!(keymap.feed(key('j')), None);
+		assert_eq!(keymap.feed(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE)), None);
+		assert_eq!(keymap.feed(KeyEvent::new(KeyCode::Char('K'), KeyModifiers::SHIFT)), Some(TreeAction::PriorityUp));
+
+		assert_eq!(keymap.feed(key('d')), None);
+		assert_eq!(keymap.feed(key('d')), Some(TreeAction::Delete));
+		assert_eq!(keymap.feed(key('D')), None);
+
+		assert_eq!(keymap.feed(key('d')), None);
+		assert_eq!(keymap.feed(key('a')), Some(TreeAction::AddChild));
+	}
+}
@@ -1,8 +1,30 @@
 pub mod app;
+pub mod avc;
+pub mod cli;
+pub mod clock;
+pub mod config;
+pub mod cvc;
 pub mod flattree;
 pub mod global;
+pub mod history;
+pub mod ical;
+pub mod json;
+pub mod keymap;
+pub mod meta;
 pub mod node;
+pub mod outline;
 pub mod parser;
+pub mod recurrence;
+pub mod remind;
+pub mod report;
+pub mod rvc;
+pub mod score;
+pub mod search;
+pub mod snapshot;
 pub mod svc;
+pub mod sync;
 pub mod tvc;
+pub mod wvc;
 pub mod ui;
+#[cfg(test)]
+mod testing;
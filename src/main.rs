@@ -1,25 +1,62 @@
 use std::fs;
-use grus::app::{Application, Error};
+use std::process::ExitCode;
+use grus::app::{Application, Error, StoreArgs};
+use grus::cli::Args;
+use grus::clock::SystemClock;
+use grus::config::Config;
 use grus::global::{TermLock, set_panic_hook};
 
-fn main() -> Result<(), Error> {
-	let Some(mut data_path) = dirs::data_dir() else {
-		eprintln!("Error: Data directory could not be determined.");
-		return Ok(())
+fn main() -> ExitCode {
+	let args = match Args::parse(std::env::args().skip(1)) {
+		Ok(args) => args,
+		Err(e) => {
+			eprintln!("Error: {}", e);
+			return e.exit_code()
+		}
 	};
-	data_path.push("grus");
-	fs::create_dir_all(&data_path)?;
-	data_path.push("tasks");
 
-	let Some(mut export_path) = dirs::home_dir() else {
-		eprintln!("Error: Home directory could not be determined.");
-		return Ok(())
+	let (config, store_args) = match Config::load(&args.overrides)
+		.and_then(|config| config.store_args().map(|store_args| (config, store_args)))
+	{
+		Ok(loaded) => loaded,
+		Err(e) => {
+			eprintln!("Error: {}", e);
+			return ExitCode::FAILURE
+		}
 	};
-	export_path.push("sync");
-	fs::create_dir_all(&export_path)?;
-	export_path.push("tasks");
+	if let Some(dir) = store_args.path.parent() {
+		if let Err(e) = fs::create_dir_all(dir) {
+			eprintln!("Error: {}", e);
+			return ExitCode::FAILURE
+		}
+	}
+
+	if let Some(invocation) = args.invocation {
+		let result = store_args.open().map_err(Into::into).and_then(|store| invocation.run(&store, &store_args, &config, &SystemClock));
+		return match result {
+			Ok(()) => ExitCode::SUCCESS,
+			Err(e) => {
+				eprintln!("Error: {}", e);
+				e.exit_code()
+			}
+		}
+	}
+
+	match run_tui(store_args, &config) {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(e) => {
+			eprintln!("Error: {}", e);
+			ExitCode::FAILURE
+		}
+	}
+}
+
+fn run_tui(store_args: StoreArgs, config: &Config) -> Result<(), Error> {
+	if let Some(dir) = store_args.export_path.parent() {
+		fs::create_dir_all(dir)?;
+	}
 
 	let _lock = TermLock::new()?;
 	set_panic_hook();
-	Application::init(data_path, 2, export_path)?.run()
+	Application::init(store_args, config, &SystemClock)?.run()
 }
@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use crate::app::{Error, StoreArgs};
+use crate::recurrence::{Repeating, Rule};
+use crate::sync::with_suffix;
+
+/// Attributes of tasks which the store has no room for, kept as JSON in `<data path>.meta`.
+/// Tasks without any attributes have no entry.
+#[derive(Default, Deserialize, Serialize)]
+pub struct Meta {
+	tasks: BTreeMap<u64, TaskMeta>,
+	#[serde(skip)]
+	path: PathBuf,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct TaskMeta {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub done: Option<NaiveDateTime>,
+	/// The time at which the timer of the task was started, while it is running.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub timer: Option<NaiveDateTime>,
+	/// How the due date moves on when the task is completed.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub repeat: Option<Rule>,
+	/// The latest occurrence of each repeating session of the task.
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub repeating: Vec<Repeating>,
+}
+
+impl Meta {
+	pub fn load(args: &StoreArgs) -> Result<Self, Error> {
+		let path = with_suffix(&args.path, ".meta");
+		let mut meta: Meta = match fs::read(&path) {
+			Ok(bytes) => serde_json::from_slice(&bytes)?,
+			Err(e) if e.kind() == ErrorKind::NotFound => Meta::default(),
+			Err(e) => return Err(e.into()),
+		};
+		meta.path = path;
+		Ok(meta)
+	}
+
+	pub fn save(&self) -> Result<(), Error> {
+		fs::write(&self.path, serde_json::to_vec(self)?)?;
+		Ok(())
+	}
+
+	pub fn task(&self, id: u64) -> Option<&TaskMeta> {
+		self.tasks.get(&id)
+	}
+
+	/// Returns the time at which the task was completed, if it is done.
+	pub fn done(&self, id: u64) -> Option<NaiveDateTime> {
+		self.task(id).and_then(|task| task.done)
+	}
+
+	/// Marks the task as completed at `now`, unless it is done already.
+	pub fn mark_done(&mut self, id: u64, now: NaiveDateTime) {
+		self.update(id, |task| { task.done.get_or_insert(now); });
+	}
+
+	pub fn unmark_done(&mut self, id: u64) {
+		self.update(id, |task| task.done = None);
+	}
+
+	/// Returns the tasks whose timer is running, along with the time it was started.
+	pub fn timers(&self) -> impl Iterator<Item = (u64, NaiveDateTime)> + '_ {
+		self.tasks.iter().filter_map(|(&id, task)| Some((id, task.timer?)))
+	}
+
+	/// Starts the timer of the task at `now`, unless it is running already.
+	pub fn start_timer(&mut self, id: u64, now: NaiveDateTime) {
+		self.update(id, |task| { task.timer.get_or_insert(now); });
+	}
+
+	/// Stops the timer of the task, returning the time it was started if it was running.
+	pub fn stop_timer(&mut self, id: u64) -> Option<NaiveDateTime> {
+		let mut start = None;
+		self.update(id, |task| start = task.timer.take());
+		start
+	}
+
+	/// Returns how the due date of the task repeats, if it does.
+	pub fn repeat(&self, id: u64) -> Option<&Rule> {
+		self.task(id).and_then(|task| task.repeat.as_ref())
+	}
+
+	pub fn set_repeat(&mut self, id: u64, rule: Option<Rule>) {
+		self.update(id, |task| task.repeat = rule);
+	}
+
+	/// Returns the tasks which have repeating sessions, along with those sessions.
+	pub fn repeating(&self) -> impl Iterator<Item = (u64, &[Repeating])> + '_ {
+		self.tasks.iter()
+			.filter(|(_, task)| !task.repeating.is_empty())
+			.map(|(&id, task)| (id, task.repeating.as_slice()))
+	}
+
+	pub fn add_repeating(&mut self, id: u64, repeating: Repeating) {
+		self.update(id, |task| task.repeating.push(repeating));
+	}
+
+	pub fn set_repeating(&mut self, id: u64, repeating: Vec<Repeating>) {
+		self.update(id, |task| task.repeating = repeating);
+	}
+
+	/// Replaces all of the attributes of the task.
+	pub fn set_task(&mut self, id: u64, task: TaskMeta) {
+		self.update(id, |current| *current = task);
+	}
+
+	fn update(&mut self, id: u64, f: impl FnOnce(&mut TaskMeta)) {
+		let task = self.tasks.entry(id).or_default();
+		f(task);
+		if *task == TaskMeta::default() { self.tasks.remove(&id); }
+	}
+}
@@ -1,8 +1,10 @@
 use std::borrow::Cow;
 use std::cmp::max;
 use std::fmt::{self, Display, Formatter};
-use chrono::{Datelike, NaiveDateTime, Local, Timelike};
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Timelike};
 use grus_lib::types::Session;
+use serde::Deserialize;
+use crate::clock::Clock;
 
 #[cfg_attr(test, derive(Clone, Debug, PartialEq))]
 pub struct Node<'a> {
@@ -10,9 +12,12 @@ pub struct Node<'a> {
 	pub pid: u64,
 	pub depth: usize,
 	pub name: Cow<'a, str>,
+	pub done: bool,
 	pub due_date: Option<NaiveDateTime>,
 	pub session: Option<Session>,
 	pub priority: Priority,
+	/// The urgency of the task, which orders siblings when sorting by score.
+	pub score: f64,
 	pub name_splits: Vec<usize>,
 	pub session_text: String,
 	pub session_splits: Vec<usize>,
@@ -64,16 +69,18 @@ impl Default for Priority {
 	}
 }
 
-pub struct Displayable<T>(pub Option<T>);
+/// An optional date or session written relative to the day of the clock, as in `Tmrw 9 AM` or
+/// `today`, with times in the given format, or as nothing if there is none.
+pub struct Displayable<T, C>(pub Option<T>, pub C, pub TimeFormat);
 
-impl Display for Displayable<NaiveDateTime> {
+impl<C: Clock> Display for Displayable<NaiveDateTime, C> {
 	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-		let Displayable(Some(dt)) = self else { return Ok(()) };
-		let now = Local::now().naive_local();
+		let Displayable(Some(dt), clock, time_format) = self else { return Ok(()) };
+		let now = clock.now();
 
 		let diff = (dt.date() - now.date()).num_days();
 		if diff == 1 {
-			write!(f, "{}", "Tmrw")?;
+			write!(f, "Tmrw")?;
 		} else if diff > 1 && diff < 7 {
 			write!(f, "{}", dt.format("%a"))?;
 		} else if dt.year() != now.year() {
@@ -86,22 +93,39 @@ impl Display for Displayable<NaiveDateTime> {
 
 		if dt.hour() == 0 { return Ok(()) };
 		if dt.date() != now.date() { write!(f, " ")? };
+		write!(f, "{}", time_format.format(dt.time()))
+	}
+}
 
-		if dt.minute() == 0 {
-			write!(f, "{}", dt.format("%-I %p"))?;
-		} else {
-			write!(f, "{}", dt.format("%-I:%M %p"))?;
-		}
+/// How times of day are written, chosen by the `time_format` config key.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+pub enum TimeFormat {
+	/// As in 9 AM or 2:30 PM.
+	#[default]
+	#[serde(rename = "12h")]
+	Twelve,
+	/// As in 09:00 or 14:30.
+	#[serde(rename = "24h")]
+	TwentyFour,
+}
 
-		Ok(())
+impl TimeFormat {
+	/// Formats a time of day to the minute, leaving out zero minutes on the 12-hour clock.
+	pub fn format(self, time: NaiveTime) -> String {
+		let format = match (self, time.minute()) {
+			(TimeFormat::Twelve, 0) => "%-I %p",
+			(TimeFormat::Twelve, _) => "%-I:%M %p",
+			(TimeFormat::TwentyFour, _) => "%H:%M",
+		};
+		time.format(format).to_string()
 	}
 }
 
-impl Display for Displayable<Session> {
+impl<C: Clock> Display for Displayable<Session, C> {
 	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-		let Displayable(Some(session)) = self else { return Ok(()) };
+		let Displayable(Some(session), clock, time_format) = self else { return Ok(()) };
 
-		write!(f, "{} to {}", Displayable(Some(session.start)), Displayable(Some(session.end)))
+		write!(f, "{} to {}", Displayable(Some(session.start), clock, *time_format), Displayable(Some(session.end), clock, *time_format))
 	}
 }
 
@@ -115,7 +139,7 @@ pub fn wrap_text(text: &str, w: usize) -> Vec<usize> {
 	let mut long_word = false;
 	let mut d = 0;
 
-	for (j, (pos, ch)) in text.char_indices().chain(([(text.len(), ' ')]).into_iter()).enumerate() {
+	for (j, (pos, ch)) in text.char_indices().chain([(text.len(), ' ')]).enumerate() {
 		let diff = (j + d) / w - (i + d) / w;
 		if ch == ' ' {
 			if in_a_word {
@@ -170,7 +194,7 @@ pub fn wrap_text(text: &str, w: usize) -> Vec<usize> {
 		}
 	}
 
-	if text.len() > 0 && splits[splits.len() - 1] != text.len() {
+	if !text.is_empty() && splits[splits.len() - 1] != text.len() {
 		splits.push(text.len());
 	}
 
@@ -179,7 +203,41 @@ pub fn wrap_text(text: &str, w: usize) -> Vec<usize> {
 
 #[cfg(test)]
 mod tests {
-	use super::wrap_text;
+	use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+	use grus_lib::types::Session;
+	use crate::clock::FixedClock;
+	use super::{wrap_text, Displayable, TimeFormat};
+
+	#[test]
+	fn time_formats() {
+		let times = [(9, 0, 0, "9 AM", "09:00"), (14, 30, 0, "2:30 PM", "14:30"), (0, 5, 9, "12:05 AM", "00:05")];
+		for (h, m, s, twelve, twenty_four) in times {
+			let time = NaiveTime::from_hms_opt(h, m, s).unwrap();
+			assert_eq!(TimeFormat::Twelve.format(time), twelve);
+			assert_eq!(TimeFormat::TwentyFour.format(time), twenty_four);
+		}
+	}
+
+	#[test]
+	fn relative_dates() {
+		let at = |d, h, m| NaiveDate::from_ymd_opt(2027, 3, d).unwrap().and_hms_opt(h, m, 0).unwrap();
+		let clock = FixedClock(at(3, 10, 15));
+		let show = |dt: NaiveDateTime| Displayable(Some(dt), clock, TimeFormat::Twelve).to_string();
+		assert_eq!(show(at(3, 0, 0)), "today");
+		assert_eq!(show(at(3, 14, 30)), "2:30 PM");
+		assert_eq!(show(at(4, 9, 0)), "Tmrw 9 AM");
+		assert_eq!(show(at(5, 17, 0)), "Fri 5 PM");
+		assert_eq!(show(at(10, 0, 0)), "10 Mar");
+		assert_eq!(show(at(2, 9, 0)), " 2 Mar 9 AM");
+		assert_eq!(show(at(3, 0, 0) + chrono::Duration::days(300)), "28 Dec");
+		assert_eq!(show(at(3, 0, 0) + chrono::Duration::days(310)), " 7 Jan 2028");
+		assert_eq!(Displayable(None::<NaiveDateTime>, clock, TimeFormat::Twelve).to_string(), "");
+
+		let session = Session { start: at(4, 9, 0), end: at(4, 10, 30) };
+		assert_eq!(Displayable(Some(session), clock, TimeFormat::Twelve).to_string(), "Tmrw 9 AM to Tmrw 10:30 AM");
+		assert_eq!(Displayable(Some(session), clock, TimeFormat::TwentyFour).to_string(), "Tmrw 09:00 to Tmrw 10:30");
+		assert_eq!(Displayable(Some(session), FixedClock(at(4, 8, 0)), TimeFormat::Twelve).to_string(), "9 AM to 10:30 AM");
+	}
 
 	#[test]
 	fn wrap_text_test() {
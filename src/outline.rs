@@ -0,0 +1,302 @@
+use std::collections::{HashMap, HashSet};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use grus_lib::reader::StoreReader;
+use grus_lib::types::Session;
+use grus_lib::writer::StoreWriter;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Renders the subtree rooted at `id` as nested Markdown bullets, annotated with the due date
+/// and first session of each task. A task with more than one parent in the subtree is written
+/// out once with an anchor, and every other occurrence links back to it. The root task of the
+/// store is left out, so that its children become the top-level bullets.
+pub fn render(reader: &StoreReader, id: u64) -> Result<String, sanakirja::Error> {
+	let mut parents = HashMap::new();
+	count_parents(reader, id, &mut parents, &mut HashSet::new())?;
+
+	let mut out = String::new();
+	let mut rendered = HashSet::new();
+	if id == 0 {
+		for child in reader.child_ids(0)? {
+			render_task(reader, child?, 0, &parents, &mut rendered, &mut out)?;
+		}
+	} else {
+		render_task(reader, id, 0, &parents, &mut rendered, &mut out)?;
+	}
+	Ok(out)
+}
+
+fn count_parents(reader: &StoreReader, id: u64, parents: &mut HashMap<u64, usize>, visited: &mut HashSet<u64>) -> Result<(), sanakirja::Error> {
+	if !visited.insert(id) { return Ok(()) };
+	for child in reader.child_ids(id)? {
+		let child = child?;
+		*parents.entry(child).or_default() += 1;
+		count_parents(reader, child, parents, visited)?;
+	}
+	Ok(())
+}
+
+fn render_task(
+	reader: &StoreReader,
+	id: u64,
+	depth: usize,
+	parents: &HashMap<u64, usize>,
+	rendered: &mut HashSet<u64>,
+	out: &mut String,
+) -> Result<(), sanakirja::Error> {
+	let name = reader.name(id)?.unwrap_or_default();
+	out.push_str(&"  ".repeat(depth));
+	if !rendered.insert(id) {
+		out.push_str(&format!("- [{}](#t{})\n", name, id));
+		return Ok(());
+	}
+
+	out.push_str("- ");
+	out.push_str(name);
+	if let Some(due_date) = reader.due_date(id)? {
+		out.push_str(&format!(" `due {}`", format_datetime(due_date)));
+	}
+	if let Some(session) = reader.first_session(id)? {
+		out.push_str(&format!(" `session {} to {}`", format_datetime(session.start), format_datetime(session.end)));
+	}
+	if parents.get(&id).is_some_and(|&n| n > 1) {
+		out.push_str(&format!(" {{#t{}}}", id));
+	}
+	out.push('\n');
+
+	for child in reader.child_ids(id)? {
+		render_task(reader, child?, depth + 1, parents, rendered, out)?;
+	}
+	Ok(())
+}
+
+fn format_datetime(dt: NaiveDateTime) -> String {
+	if dt.time() == NaiveTime::MIN {
+		dt.format(DATE_FORMAT).to_string()
+	} else {
+		dt.format(DATETIME_FORMAT).to_string()
+	}
+}
+
+/// A bullet of an outline, which is either a task or a link to a task written out elsewhere.
+#[derive(Debug, PartialEq)]
+pub struct Item {
+	pub line: usize,
+	pub kind: ItemKind,
+	pub children: Vec<Item>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ItemKind {
+	Task { name: String, due_date: Option<NaiveDateTime>, session: Option<Session>, anchor: Option<String> },
+	Link { anchor: String },
+}
+
+/// Parses an indented outline. Each non-empty line is an item, optionally starting with a `-`,
+/// `*` or `+` bullet, and is nested under the closest preceding line with less indentation.
+/// Headings are skipped.
+pub fn parse(text: &str) -> Result<Vec<Item>, Error> {
+	let mut roots = Vec::new();
+	let mut stack: Vec<(usize, Item)> = Vec::new();
+	for (i, line) in text.lines().enumerate() {
+		let content = line.trim_start();
+		if content.is_empty() || content.starts_with('#') { continue };
+		let indent: usize = line[..line.len() - content.len()].chars().map(|c| if c == '\t' { 4 } else { 1 }).sum();
+		let item = Item { line: i + 1, kind: parse_item(i + 1, content)?, children: Vec::new() };
+
+		while stack.last().is_some_and(|&(level, _)| level >= indent) {
+			pop_item(&mut stack, &mut roots);
+		}
+		stack.push((indent, item));
+	}
+	while !stack.is_empty() {
+		pop_item(&mut stack, &mut roots);
+	}
+	Ok(roots)
+}
+
+fn pop_item(stack: &mut Vec<(usize, Item)>, roots: &mut Vec<Item>) {
+	let Some((_, item)) = stack.pop() else { return };
+	match stack.last_mut() {
+		Some((_, parent)) => parent.children.push(item),
+		None => roots.push(item),
+	}
+}
+
+fn parse_item(line: usize, content: &str) -> Result<ItemKind, Error> {
+	let mut text = content;
+	for bullet in ["- ", "* ", "+ "] {
+		if let Some(rest) = text.strip_prefix(bullet) {
+			text = rest.trim_start();
+			break;
+		}
+	}
+
+	if let Some(anchor) = text.strip_prefix('[').and_then(|rest| rest.split_once("](#")?.1.strip_suffix(')')) {
+		return Ok(ItemKind::Link { anchor: anchor.into() });
+	}
+
+	let mut anchor = None;
+	if let Some((rest, id)) = text.strip_suffix('}').and_then(|rest| rest.rsplit_once(" {#")) {
+		anchor = Some(id.to_string());
+		text = rest;
+	}
+
+	let mut due_date = None;
+	let mut session = None;
+	while let Some((rest, annotation)) = text.strip_suffix('`').and_then(|rest| rest.rsplit_once(" `")) {
+		let invalid = || Error::Annotation(line, annotation.into());
+		if let Some(dt) = annotation.strip_prefix("due ") {
+			due_date = Some(parse_datetime(dt).ok_or_else(invalid)?);
+		} else if let Some(range) = annotation.strip_prefix("session ") {
+			let (start, end) = range.split_once(" to ").ok_or_else(invalid)?;
+			let start = parse_datetime(start).ok_or_else(invalid)?;
+			let end = parse_datetime(end).ok_or_else(invalid)?;
+			session = Some(Session { start, end });
+		} else {
+			break;
+		}
+		text = rest;
+	}
+
+	Ok(ItemKind::Task { name: text.trim_end().into(), due_date, session, anchor })
+}
+
+fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+	NaiveDateTime::parse_from_str(s, DATETIME_FORMAT).ok()
+		.or_else(|| NaiveDate::parse_from_str(s, DATE_FORMAT).ok().map(|date| date.and_time(NaiveTime::MIN)))
+}
+
+/// Creates the tasks of an outline under `pid`, keeping the order of the outline, and shares
+/// the linked tasks with the parents of their links. Returns the number of tasks created.
+pub fn write(items: &[Item], writer: &mut StoreWriter, pid: u64) -> Result<usize, Error> {
+	let mut anchors = HashMap::new();
+	let mut links = Vec::new();
+	let mut count = 0;
+	add_items(items, writer, pid, &mut anchors, &mut links, &mut count)?;
+	links.sort_by_key(|&(pid, position, _, _)| (pid, position));
+
+	// Shared tasks are inserted at the head of the list of children, so each link is moved
+	// down to its position. Links are shared in increasing order of position, so the children
+	// before each link are already in place.
+	for (pid, position, anchor, line) in links {
+		let &id = anchors.get(&anchor).ok_or(Error::UnknownAnchor(line, anchor))?;
+		if !writer.share(id, pid)? { return Err(Error::Cycle(line)) };
+		for _ in 0..position {
+			writer.move_down(pid, id)?;
+		}
+	}
+	Ok(count)
+}
+
+fn add_items(
+	items: &[Item],
+	writer: &mut StoreWriter,
+	pid: u64,
+	anchors: &mut HashMap<String, u64>,
+	links: &mut Vec<(u64, usize, String, usize)>,
+	count: &mut usize,
+) -> Result<(), Error> {
+	for (position, item) in items.iter().enumerate().rev() {
+		match &item.kind {
+			ItemKind::Task { name, due_date, session, anchor } => {
+				let id = writer.add_child(pid, name)?;
+				*count += 1;
+				if let Some(due_date) = due_date { writer.set_due_date(id, *due_date)? };
+				if let Some(session) = session { writer.add_session(id, session)? };
+				if let Some(anchor) = anchor {
+					if anchors.insert(anchor.clone(), id).is_some() {
+						return Err(Error::DuplicateAnchor(item.line, anchor.clone()));
+					}
+				}
+				add_items(&item.children, writer, id, anchors, links, count)?;
+			}
+			ItemKind::Link { anchor } => links.push((pid, position, anchor.clone(), item.line)),
+		}
+	}
+	Ok(())
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+	#[error("Line {0}: invalid annotation '{1}'")]
+	Annotation(usize, String),
+	#[error("Line {0}: link to unknown task #{1}")]
+	UnknownAnchor(usize, String),
+	#[error("Line {0}: task #{1} is defined twice")]
+	DuplicateAnchor(usize, String),
+	#[error("Line {0}: the linked task is already a child here or would become its own descendent")]
+	Cycle(usize),
+	#[error("Store Error: {0}")]
+	StoreError(#[from] sanakirja::Error),
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::NaiveDate;
+	use grus_lib::Store;
+	use grus_lib::types::Session;
+	use crate::testing::TempDir;
+	use super::{parse, render, write, ItemKind};
+
+	/// Numbers anchors in order of appearance, since task ids differ between stores.
+	fn normalize(outline: &str) -> String {
+		let mut anchors = Vec::new();
+		outline.split("#t").enumerate().map(|(i, part)| {
+			if i == 0 { return part.to_string() };
+			let digits = part.len() - part.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+			let anchor = &part[..digits];
+			let n = anchors.iter().position(|a| a == anchor).unwrap_or_else(|| {
+				anchors.push(anchor.to_string());
+				anchors.len() - 1
+			});
+			format!("#a{}{}", n, &part[digits..])
+		}).collect()
+	}
+
+	#[test]
+	fn round_trip() {
+		let dir = TempDir::new();
+		let store = Store::open(dir.join("src"), 2).unwrap();
+
+		let start = NaiveDate::from_ymd_opt(2023, 7, 1).unwrap().and_hms_opt(14, 0, 0).unwrap();
+		let mut writer = store.writer().unwrap();
+		let project = writer.add_child(0, "project").unwrap();
+		let b = writer.add_child(project, "b").unwrap();
+		let a = writer.add_child(project, "a").unwrap();
+		let c = writer.add_child(a, "c").unwrap();
+		writer.add_child(c, "d").unwrap();
+		writer.share(c, b).unwrap();
+		writer.add_child(b, "e").unwrap();
+		writer.set_due_date(a, start.date().and_hms_opt(0, 0, 0).unwrap()).unwrap();
+		writer.add_session(c, &Session { start, end: start + chrono::Duration::hours(2) }).unwrap();
+		writer.commit().unwrap();
+
+		let outline = render(&store.reader().unwrap(), project).unwrap();
+		assert_eq!(outline, format!("\
+- project
+  - a `due 2023-07-01`
+    - c `session 2023-07-01 14:00 to 2023-07-01 16:00` {{#t{c}}}
+      - d
+  - b
+    - e
+    - [c](#t{c})
+"));
+
+		let items = parse(&outline).unwrap();
+		let ItemKind::Task { anchor: Some(_), .. } = &items[0].children[0].children[0].kind else { panic!() };
+		let copy = Store::open(dir.join("dst"), 2).unwrap();
+		let mut writer = copy.writer().unwrap();
+		assert_eq!(write(&items, &mut writer, 0).unwrap(), 6);
+		writer.commit().unwrap();
+		assert_eq!(normalize(&render(&copy.reader().unwrap(), 0).unwrap()), normalize(&outline));
+
+		let items = parse("Plan\n\tone\n\t\ttwo\n\tthree [x](#t9)\n  * [missing](#t9)\n").unwrap();
+		assert_eq!(items[0].children.len(), 3);
+		let mut writer = copy.writer().unwrap();
+		assert!(write(&items, &mut writer, 0).is_err());
+		drop(writer);
+		assert!(parse("- a `due tomorrow`").is_err());
+	}
+}
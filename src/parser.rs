@@ -1,124 +1,586 @@
-use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Weekday};
 use chrono::naive::Days;
 use grus_lib::types::Session;
 use winnow::{Parser, PResult};
-use winnow::combinator::{alt, separated_pair};
-use winnow::token::{tag, tag_no_case, take, take_until1, take_while};
-use winnow::error::{ContextError, ErrMode, ParseError};
-use winnow::stream::AsChar;
+use winnow::ascii::{digit1, space0, space1};
+use winnow::combinator::{alt, cut_err, eof, opt, peek, preceded, separated1, terminated};
+use winnow::token::{tag, tag_no_case, take, take_while};
+use winnow::error::{AddContext, ContextError, ErrMode, ParseError, StrContext, StrContextValue};
+use winnow::stream::{AsChar, Stateful};
+use crate::clock::{Clock, SystemClock};
+use crate::recurrence::Rule;
 
-pub fn parse_session(s: &str) -> Result<Session, ParseError<&str, ContextError>> {
+/// The text being parsed, along with the clock which relative dates such as `tmrw` are
+/// resolved against.
+pub type Input<'s> = Stateful<&'s str, &'s dyn Clock>;
+
+pub type Error<'s> = ParseError<Input<'s>, ContextError>;
+
+pub fn parse_session<'s>(s: &'s str, clock: &'s dyn Clock) -> Result<Session, Error<'s>> {
+	session.parse(Input { input: s, state: clock })
+}
+
+pub fn parse_datetime<'s>(s: &'s str, clock: &'s dyn Clock) -> Result<NaiveDateTime, Error<'s>> {
+	any_datetime.parse(Input { input: s, state: clock })
+}
+
+/// Parses a session which may repeat, either as a session followed by a rule, as in
+/// `tmrw 9am to 10am every 2 weeks`, or as a rule followed by times, as in
+/// `every mon 9am to 10am` or `every mon 9am for 1h`, which starts on the first day the rule
+/// allows.
+pub fn parse_repeating_session<'s>(s: &'s str, clock: &'s dyn Clock) -> Result<(Session, Option<Rule>), Error<'s>> {
 	alt((
-		separated_pair(date_with_opt_time, tag(" to "), date_with_opt_time)
-			.map(|(dt1, dt2)| Session { start: dt1, end: dt2 }),
-		separated_pair(date_with_opt_time, tag(" to "), time)
-			.map(|(dt, time)| Session { start: dt, end: dt.date().and_time(time) }),
-		separated_pair(time, tag(" to "), date_with_opt_time).map(|(time, dt)| Session {
-			start: Local::now().date_naive().and_time(time),
-			end: dt
-		}),
-		separated_pair(time, tag(" to "), time).map(|(t1, t2)| Session {
-			start: Local::now().date_naive().and_time(t1),
-			end: Local::now().date_naive().and_time(t2)
-		}),
-	)).parse(s)
+		rule_first_session,
+		(session, opt(preceded(space1, every)))
+			.map(|(session, rule)| (session, rule.map(|rule| rule.anchored(session.start.date())))),
+	)).parse(Input { input: s, state: clock })
 }
 
-pub fn parse_datetime(s: &str) -> Result<NaiveDateTime, ParseError<&str, ContextError>> {
+/// Parses a due date which may repeat, in the same two ways as a session, as in
+/// `fri 5pm every week` or `every month on day 1 9am`.
+pub fn parse_repeating_datetime<'s>(s: &'s str, clock: &'s dyn Clock) -> Result<(NaiveDateTime, Option<Rule>), Error<'s>> {
 	alt((
+		(every, opt(preceded(space1, cut_err(time)))).map(|(rule, time)| {
+			let date = rule.first(clock.today());
+			(date.and_time(time.unwrap_or_default()), Some(rule.anchored(date)))
+		}),
+		(any_datetime, opt(preceded(space1, every)))
+			.map(|(datetime, rule)| (datetime, rule.map(|rule| rule.anchored(datetime.date())))),
+	)).parse(Input { input: s, state: clock })
+}
+
+/// Parses a recurrence rule on its own, such as `every 3 days` or `every mon, thu`, which does
+/// not depend on the current day.
+pub fn parse_rule(s: &str) -> Result<Rule, Error<'_>> {
+	every.parse(Input { input: s, state: &SystemClock })
+}
+
+/// Describes where and why the input could not be parsed, as in "expected time after
+/// 'tomorrow' at column 10".
+pub fn describe(error: &Error) -> String {
+	let input = error.input().input;
+	let (before, after) = input.split_at(error.offset());
+	let rest = after.trim_start();
+	let expected = error.inner().context().find_map(|context| match context {
+		StrContext::Expected(value) => Some(value),
+		_ => None,
+	});
+	let mut message = match (expected, rest.split_whitespace().next()) {
+		(Some(expected), _) => format!("expected {}", expected),
+		(None, Some(word)) => format!("unexpected '{}'", word),
+		(None, None) => "unexpected end".into(),
+	};
+	if let Some(word) = before.split_whitespace().next_back() {
+		message += &format!(" after '{}'", word);
+	}
+	let column = input[..input.len() - rest.len()].chars().count() + 1;
+	message + &format!(" at column {}", column)
+}
+
+/// Names what the parser expects, for the message shown when the input cannot be parsed. If
+/// the parser fails, the error points at where it started and the name replaces those of the
+/// alternatives it tried, unless it failed past a point of no return, after which the innermost
+/// name is the one which tells what went wrong.
+fn expect<'s, O>(name: &'static str, mut parser: impl Parser<Input<'s>, O, ContextError>) -> impl Parser<Input<'s>, O, ContextError> {
+	let context = StrContext::Expected(StrContextValue::Description(name));
+	move |s: &mut Input<'s>| {
+		let start = *s;
+		parser.parse_next(s).map_err(|e| match e {
+			ErrMode::Backtrack(_) => {
+				*s = start;
+				ErrMode::Backtrack(ContextError::new().add_context(s, context.clone()))
+			}
+			e => e.map(|e| e.add_context(s, context.clone())),
+		})
+	}
+}
+
+/// A word which does not merely start a longer word, such as `to` but not the start of
+/// `tomorrow`.
+fn keyword<'s>(word: &'static str) -> impl Parser<Input<'s>, &'s str, ContextError> {
+	terminated(tag_no_case(word), peek(alt((space1, eof))))
+}
+
+/// A rule followed by the times of a session, which is on the first day the rule allows.
+fn rule_first_session(s: &mut Input) -> PResult<(Session, Option<Rule>)> {
+	let rule = every(s)?;
+	let start = cut_err(expect("time", preceded(space1, time))).parse_next(s)?;
+	let date = rule.first(s.state.today());
+	let start = date.and_time(start);
+	let end = cut_err(end(start)).parse_next(s)?;
+	Ok((Session { start, end }, Some(rule.anchored(date))))
+}
+
+/// A session given by its start and either its end or its length, as in `tmrw 9am to 10am` or
+/// `3pm for 90m`, or by its end alone, as in `until 5pm`, in which case it starts now and the
+/// end has to be later.
+fn session(s: &mut Input) -> PResult<Session> {
+	let now = now(s.state);
+	if opt(keyword("until")).parse_next(s)?.is_some() {
+		let later = cut_err(expect("time after now", cut_err(any_datetime).verify(move |&end| end > now)));
+		let end = cut_err(expect("date or time", preceded(space1, later))).parse_next(s)?;
+		return Ok(Session { start: now, end });
+	}
+	let start = expect("date or time", alt((keyword("now").map(move |_| now), any_datetime))).parse_next(s)?;
+	let end = cut_err(end(start)).parse_next(s)?;
+	Ok(Session { start, end })
+}
+
+/// The end of a session which starts at `start`, given by `to` and a date, a time or both, or
+/// by `for` and a length.
+fn end<'s>(start: NaiveDateTime) -> impl Parser<Input<'s>, NaiveDateTime, ContextError> {
+	expect("'to' or 'for'", alt((
+		preceded((space1, keyword("to")), cut_err(expect("date or time", preceded(space1, alt((
+			date_with_opt_time,
+			time.map(move |time| start.date().and_time(time)),
+		)))))),
+		preceded((space1, keyword("for")), cut_err(expect("duration", preceded(space1, duration)
+			.verify_map(move |length| start.checked_add_signed(length))))),
+	)))
+}
+
+/// The current time to the minute, which starts sessions entered as `now for 25m` or
+/// `until 5pm`.
+fn now(clock: &dyn Clock) -> NaiveDateTime {
+	let now = clock.now();
+	now.date().and_hms_opt(now.hour(), now.minute(), 0).unwrap_or(now)
+}
+
+/// A positive length of time in hours and minutes, as in `45m`, `2h`, `1h30m` or `1.5h`.
+fn duration(s: &mut Input) -> PResult<Duration> {
+	expect("duration", alt((
+		terminated((digit1, tag("."), digit1).recognize(), tag_no_case("h"))
+			.try_map(str::parse::<f64>)
+			.verify(|&hours| hours <= f64::from(u32::MAX))
+			.map(|hours| Duration::minutes((hours * 60.0).round() as i64)),
+		(terminated(number, tag_no_case("h")), opt(terminated(number, tag_no_case("m"))))
+			.map(|(hours, minutes)| Duration::hours(hours.into()) + Duration::minutes(minutes.unwrap_or(0).into())),
+		terminated(number, tag_no_case("m")).map(|minutes| Duration::minutes(minutes.into())),
+	)).verify(|length| *length > Duration::zero())).parse_next(s)
+}
+
+fn number(s: &mut Input) -> PResult<u32> {
+	digit1.try_map(str::parse).parse_next(s)
+}
+
+fn any_datetime(s: &mut Input) -> PResult<NaiveDateTime> {
+	let today = s.state.today();
+	expect("date or time", alt((
 		datetime,
-		time.map(|time| NaiveDateTime::new(Local::now().date_naive(), time)),
+		time.map(move |time| NaiveDateTime::new(today, time)),
 		date.map(|date| NaiveDateTime::new(date, NaiveTime::default())),
-	)).parse(s)
+	))).parse_next(s)
+}
+
+/// A rule starting with `every`, followed by `day`, `week`, `month`, `N days`, `N weeks`,
+/// `N weeks on` weekdays, `month on day N` or a list of weekdays.
+fn every(s: &mut Input) -> PResult<Rule> {
+	preceded(keyword("every"), cut_err(expect("recurrence rule", preceded(space1, alt((
+		tag_no_case("day").map(|_| Rule::Days(1)),
+		tag_no_case("week").map(|_| Rule::Weeks(1, Vec::new())),
+		preceded(tag_no_case("month"), opt(preceded((space1, tag_no_case("on day"), space1), cut_err(day_of_month))))
+			.map(Rule::Monthly),
+		terminated(count, (space1, tag_no_case("days"))).map(Rule::Days),
+		(terminated(count, (space1, tag_no_case("weeks"))), opt(preceded((space1, tag_no_case("on"), space1), cut_err(weekdays))))
+			.map(|(n, weekdays)| Rule::Weeks(n, weekdays.unwrap_or_default())),
+		weekdays.map(|weekdays| Rule::Weeks(1, weekdays)),
+	)))))).parse_next(s)
+}
+
+fn count(s: &mut Input) -> PResult<u32> {
+	expect("positive number", digit1.try_map(str::parse).verify(|&n| n > 0)).parse_next(s)
 }
 
-fn date_with_opt_time(s: &mut &str) -> PResult<NaiveDateTime> {
+fn day_of_month(s: &mut Input) -> PResult<u32> {
+	expect("day of the month", digit1.try_map(str::parse).verify(|n| (1..=31).contains(n))).parse_next(s)
+}
+
+fn weekdays(s: &mut Input) -> PResult<Vec<Weekday>> {
+	separated1(weekday_name, (tag(","), space0)).parse_next(s)
+}
+
+fn date_with_opt_time(s: &mut Input) -> PResult<NaiveDateTime> {
 	alt((
 		datetime,
 		date.map(|date| NaiveDateTime::new(date, NaiveTime::default())),
 	)).parse_next(s)
 }
 
-fn datetime(s: &mut &str) -> PResult<NaiveDateTime> {
+/// A date followed by a time, separated by spaces or, as in ISO 8601, by a `T`. Once a date
+/// is followed by anything but the words which go on to the end of a session or a rule, that
+/// must be a time.
+fn datetime(s: &mut Input) -> PResult<NaiveDateTime> {
 	let date = date(s)?;
-	take_while(0.., AsChar::is_space).parse_next(s)?;
-	let time = time(s)?;
+	alt((tag("T"), space0)).parse_next(s)?;
+	if s.is_empty() || peek(opt(alt((keyword("to"), keyword("for"), keyword("every"))))).parse_next(s)?.is_some() {
+		return Err(ErrMode::Backtrack(ContextError::new()));
+	}
+	let time = cut_err(time).parse_next(s)?;
 	Ok(NaiveDateTime::new(date, time))
 }
 
-fn time(s: &mut &str) -> PResult<NaiveTime> {
-	alt((
+fn time(s: &mut Input) -> PResult<NaiveTime> {
+	expect("time", alt((
+		tag_no_case("noon").map(|_| NaiveTime::from_hms_opt(12, 0, 0).unwrap()),
+		tag_no_case("midnight").map(|_| NaiveTime::MIN),
 		proper_time,
 		quick_time,
-	)).parse_next(s)
+		compact_time,
+	))).parse_next(s)
+}
+
+/// A time with minutes and maybe seconds, on the 12-hour clock if it ends in am or pm and on
+/// the 24-hour clock otherwise, as in `2:30 pm`, `14:30` or `14:30:15`.
+fn proper_time(s: &mut Input) -> PResult<NaiveTime> {
+	(
+		take_while(1..=2, AsChar::is_dec_digit).try_map(str::parse),
+		preceded(tag(":"), two_digits),
+		opt(preceded(tag(":"), two_digits)),
+		opt(preceded(space0, meridiem)),
+	)
+		.verify_map(|(hour, minute, second, meridiem)| to_time(hour, minute, second.unwrap_or(0), meridiem))
+		.parse_next(s)
+}
+
+/// An hour on the 12-hour clock, as in `4 am`.
+fn quick_time(s: &mut Input) -> PResult<NaiveTime> {
+	(digit1.try_map(str::parse), space0, meridiem)
+		.verify_map(|(hour, _, meridiem)| to_time(hour, 0, 0, Some(meridiem)))
+		.parse_next(s)
+}
+
+/// Four digits on the 24-hour clock, as in `0930`, which must end the input or be followed by
+/// a space so that years of dates such as `2027-03-05` are not taken for times.
+fn compact_time(s: &mut Input) -> PResult<NaiveTime> {
+	terminated((two_digits, two_digits), peek(alt((eof, space1))))
+		.verify_map(|(hour, minute)| to_time(hour, minute, 0, None))
+		.parse_next(s)
+}
+
+fn two_digits(s: &mut Input) -> PResult<u32> {
+	take_while(2, AsChar::is_dec_digit).try_map(str::parse).parse_next(s)
 }
 
-fn proper_time(s: &mut &str) -> PResult<NaiveTime> {
-	let hour = take_until1(":").parse_next(s)?;
-	tag(":").parse_next(s)?;
-	let minute = take(2usize).parse_next(s)?;
-	take_while(0.., AsChar::is_space).parse_next(s)?;
-	let delta = alt((
-		alt((tag("am"), tag("AM"))).map(|_| 0),
-		alt((tag("pm"), tag("PM"))).map(|_| 12),
-	)).parse_next(s)?;
-	let mut h = hour.parse().map_err(|_| ErrMode::Cut(ContextError::new()))?;
-	if h > 12 { return Err(ErrMode::Cut(ContextError::new())) }
-	if h == 12 { h = 0 }
-	h += delta;
-	let m = minute.parse().map_err(|_| ErrMode::Cut(ContextError::new()))?;
-	Ok(NaiveTime::from_hms_opt(h, m, 0).ok_or(ErrMode::Cut(ContextError::new()))?)
-}
-
-fn quick_time(s: &mut &str) -> PResult<NaiveTime> {
-	let hour = take_while(1.., AsChar::is_dec_digit).parse_next(s)?;
-	take_while(0.., AsChar::is_space).parse_next(s)?;
-	let delta = alt((
-		alt((tag("am"), tag("AM"))).map(|_| 0),
-		alt((tag("pm"), tag("PM"))).map(|_| 12),
-	)).parse_next(s)?;
-	let mut h = hour.parse().map_err(|_| ErrMode::Cut(ContextError::new()))?;
-	if h > 12 { return Err(ErrMode::Cut(ContextError::new())) }
-	if h == 12 { h = 0 }
-	h += delta;
-	Ok(NaiveTime::from_hms_opt(h, 0, 0).ok_or(ErrMode::Cut(ContextError::new()))?)
-}
-
-fn date(s: &mut &str) -> PResult<NaiveDate> {
+/// The hours to add to a time on the 12-hour clock.
+fn meridiem(s: &mut Input) -> PResult<u32> {
 	alt((
-		tag_no_case("today").map(|_| Local::now().date_naive()),
-		tag_no_case("yesterday").map(|_| Local::now().date_naive() - Days::new(1)),
+		tag_no_case("am").map(|_| 0),
+		tag_no_case("pm").map(|_| 12),
+	)).parse_next(s)
+}
+
+fn to_time(hour: u32, minute: u32, second: u32, meridiem: Option<u32>) -> Option<NaiveTime> {
+	let hour = match meridiem {
+		Some(delta) if hour <= 12 => hour % 12 + delta,
+		Some(_) => return None,
+		None => hour,
+	};
+	NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+fn date(s: &mut Input) -> PResult<NaiveDate> {
+	let today = s.state.today();
+	expect("date", alt((
+		tag_no_case("today").map(move |_| today),
+		tag_no_case("yesterday").map(move |_| today - Days::new(1)),
 		alt((tag_no_case("tmrw"), tag_no_case("tomorrow")))
-			.map(|_| Local::now().date_naive() + Days::new(1)),
+			.map(move |_| today + Days::new(1)),
+		in_days,
+		next,
+		tag_no_case("end of month").verify_map(move |_| {
+			Some(today.with_day(1)?.checked_add_months(Months::new(1))? - Days::new(1))
+		}),
 		weekday,
+		iso_date,
 		ddmmyyyy,
-	)).parse_next(s)
+		month_date,
+	))).parse_next(s)
+}
+
+/// A date a number of days, weeks or months from today, as in `in 3 days`.
+fn in_days(s: &mut Input) -> PResult<NaiveDate> {
+	let today = s.state.today();
+	preceded(keyword("in"), cut_err(expect("number of days, weeks or months", preceded(space1, alt((
+		terminated(count, (space1, alt((tag_no_case("days"), tag_no_case("day")))))
+			.map(move |n| today + Days::new(n.into())),
+		terminated(count, (space1, alt((tag_no_case("weeks"), tag_no_case("week")))))
+			.map(move |n| today + Days::new(7 * u64::from(n))),
+		terminated(count, (space1, alt((tag_no_case("months"), tag_no_case("month")))))
+			.verify_map(move |n| today.checked_add_months(Months::new(n))),
+	)))))).parse_next(s)
 }
 
-fn weekday(s: &mut &str) -> PResult<NaiveDate> {
-	let weekday = alt((
+/// The first day of the next month, or the next day after today with the given weekday, so
+/// that `next fri` on a Friday is a week away.
+fn next(s: &mut Input) -> PResult<NaiveDate> {
+	let today = s.state.today();
+	preceded(keyword("next"), cut_err(expect("weekday or month", preceded(space1, alt((
+		tag_no_case("month").verify_map(move |_| today.with_day(1)?.checked_add_months(Months::new(1))),
+		weekday_name.map(move |weekday| {
+			let delta = (weekday.num_days_from_monday() + 6 - today.weekday().num_days_from_monday()) % 7 + 1;
+			today + Days::new(delta.into())
+		}),
+	)))))).parse_next(s)
+}
+
+fn weekday(s: &mut Input) -> PResult<NaiveDate> {
+	let weekday = weekday_name(s)?;
+	let today = s.state.today();
+	let delta = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+	Ok(today + Days::new(delta.into()))
+}
+
+fn weekday_name(s: &mut Input) -> PResult<Weekday> {
+	expect("weekday", alt((
 		alt((tag_no_case("monday"), tag_no_case("mon"))).map(|_| Weekday::Mon),
-		alt((tag_no_case("tuesday"), tag_no_case("tue"))).map(|_| Weekday::Tue),
+		alt((tag_no_case("tuesday"), tag_no_case("tues"), tag_no_case("tue"))).map(|_| Weekday::Tue),
 		alt((tag_no_case("wednesday"), tag_no_case("wed"))).map(|_| Weekday::Wed),
-		alt((tag_no_case("thursday"), tag_no_case("thu"))).map(|_| Weekday::Thu),
+		alt((tag_no_case("thursday"), tag_no_case("thurs"), tag_no_case("thur"), tag_no_case("thu"))).map(|_| Weekday::Thu),
 		alt((tag_no_case("friday"), tag_no_case("fri"))).map(|_| Weekday::Fri),
 		alt((tag_no_case("saturday"), tag_no_case("sat"))).map(|_| Weekday::Sat),
 		alt((tag_no_case("sunday"), tag_no_case("sun"))).map(|_| Weekday::Sun),
-	)).parse_next(s)?;
-	let today = Local::now().date_naive();
-	let delta = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
-	Ok(today + Days::new(delta.into()))
+	))).parse_next(s)
+}
+
+fn ddmmyyyy(s: &mut Input) -> PResult<NaiveDate> {
+	(take(2usize), tag("/"), take(2usize), tag("/"), take(4usize))
+		.verify_map(|(day, _, month, _, year): (&str, _, &str, _, &str)| {
+			NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+		})
+		.parse_next(s)
 }
 
-fn ddmmyyyy(s: &mut &str) -> PResult<NaiveDate> {
-	let day = take(2usize).parse_next(s)?;
-	tag("/").parse_next(s)?;
-	let month = take(2usize).parse_next(s)?;
-	tag("/").parse_next(s)?;
-	let year = take(4usize).parse_next(s)?;
-	Ok(NaiveDate::from_ymd_opt(
-		year.parse().map_err(|_| ErrMode::Cut(ContextError::new()))?,
-		month.parse().map_err(|_| ErrMode::Cut(ContextError::new()))?,
-		day.parse().map_err(|_| ErrMode::Cut(ContextError::new()))?,
-	).ok_or(ErrMode::Cut(ContextError::new()))?)
+fn iso_date(s: &mut Input) -> PResult<NaiveDate> {
+	(take_while(4, AsChar::is_dec_digit), tag("-"), take_while(2, AsChar::is_dec_digit), tag("-"), take_while(2, AsChar::is_dec_digit))
+		.verify_map(|(year, _, month, _, day): (&str, _, &str, _, &str)| {
+			NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+		})
+		.parse_next(s)
+}
+
+/// A date with the name of its month, as in `5 Mar` or `March 5 2027`. Without a year, it is
+/// the next such date from today on.
+fn month_date(s: &mut Input) -> PResult<NaiveDate> {
+	let today = s.state.today();
+	alt((
+		(day_of_month, space1, month_name, opt(preceded(space1, year)))
+			.map(|(day, _, month, year)| (day, month, year)),
+		(month_name, space1, day_of_month, opt(preceded((opt(tag(",")), space1), year)))
+			.map(|(month, _, day, year)| (day, month, year)),
+	)).verify_map(move |(day, month, year)| {
+		match year {
+			Some(year) => NaiveDate::from_ymd_opt(year, month, day),
+			None => NaiveDate::from_ymd_opt(today.year(), month, day)
+				.filter(|&date| date >= today)
+				.or_else(|| NaiveDate::from_ymd_opt(today.year() + 1, month, day)),
+		}
+	}).parse_next(s)
+}
+
+/// A year from 1900 to 2099, so that four digits after a date which cannot be a year, as in
+/// `5 mar 1430`, are left to be read as a time.
+fn year(s: &mut Input) -> PResult<i32> {
+	(alt((tag("19"), tag("20"))), take_while(2, AsChar::is_dec_digit))
+		.recognize()
+		.try_map(str::parse)
+		.parse_next(s)
+}
+
+fn month_name(s: &mut Input) -> PResult<u32> {
+	expect("month", alt((
+		alt((tag_no_case("january"), tag_no_case("jan"))).map(|_| 1),
+		alt((tag_no_case("february"), tag_no_case("feb"))).map(|_| 2),
+		alt((tag_no_case("march"), tag_no_case("mar"))).map(|_| 3),
+		alt((tag_no_case("april"), tag_no_case("apr"))).map(|_| 4),
+		tag_no_case("may").map(|_| 5),
+		alt((tag_no_case("june"), tag_no_case("jun"))).map(|_| 6),
+		alt((tag_no_case("july"), tag_no_case("jul"))).map(|_| 7),
+		alt((tag_no_case("august"), tag_no_case("aug"))).map(|_| 8),
+		alt((tag_no_case("september"), tag_no_case("sept"), tag_no_case("sep"))).map(|_| 9),
+		alt((tag_no_case("october"), tag_no_case("oct"))).map(|_| 10),
+		alt((tag_no_case("november"), tag_no_case("nov"))).map(|_| 11),
+		alt((tag_no_case("december"), tag_no_case("dec"))).map(|_| 12),
+	))).parse_next(s)
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+	use crate::clock::FixedClock;
+	use crate::recurrence::Rule;
+	use super::{describe, parse_datetime, parse_repeating_datetime, parse_repeating_session, parse_rule, parse_session};
+
+	fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> NaiveDateTime {
+		NaiveDate::from_ymd_opt(y, mo, d).unwrap().and_hms_opt(h, mi, 0).unwrap()
+	}
+
+	/// Wednesday, 3 March 2027, a little after 10:15.
+	fn wednesday() -> FixedClock {
+		FixedClock(at(2027, 3, 3, 10, 15) + Duration::seconds(42))
+	}
+
+	/// Friday, 31 December 2027, late in the evening of the last day of a year before a leap year.
+	fn new_years_eve() -> FixedClock {
+		FixedClock(at(2027, 12, 31, 23, 50))
+	}
+
+	#[test]
+	fn times() {
+		let clock = wednesday();
+		let time = |s| parse_datetime(s, &clock).ok().filter(|datetime| datetime.date() == clock.0.date()).map(|datetime| datetime.time());
+		let hms = |h, m, s| NaiveTime::from_hms_opt(h, m, s);
+		assert_eq!(time("4 am"), hms(4, 0, 0));
+		assert_eq!(time("12:03 PM"), hms(12, 3, 0));
+		assert_eq!(time("12am"), hms(0, 0, 0));
+		assert_eq!(time("14:30"), hms(14, 30, 0));
+		assert_eq!(time("9:05"), hms(9, 5, 0));
+		assert_eq!(time("0930"), hms(9, 30, 0));
+		assert_eq!(time("23:59:30"), hms(23, 59, 30));
+		assert_eq!(time("2:30:15 pm"), hms(14, 30, 15));
+		assert_eq!(time("noon"), hms(12, 0, 0));
+		assert_eq!(time("Midnight"), hms(0, 0, 0));
+		assert!(parse_datetime("24:00", &clock).is_err());
+		assert!(parse_datetime("14:30 pm", &clock).is_err());
+		assert!(parse_datetime("13pm", &clock).is_err());
+		assert!(parse_datetime("2460", &clock).is_err());
+
+		assert_eq!(parse_datetime("2027-03-05T14:30", &clock).unwrap(), at(2027, 3, 5, 14, 30));
+		assert_eq!(parse_datetime("2027-03-05T14:30:15", &clock).unwrap(), at(2027, 3, 5, 14, 30) + Duration::seconds(15));
+		assert_eq!(parse_datetime("2027-03-05 0930", &clock).unwrap(), at(2027, 3, 5, 9, 30));
+		assert_eq!(parse_datetime("2027-03-05 midnight", &clock).unwrap(), at(2027, 3, 5, 0, 0));
+		assert_eq!(parse_datetime("5 mar 1430", &clock).unwrap(), at(2027, 3, 5, 14, 30));
+		assert_eq!(parse_datetime("mar 5 0930", &clock).unwrap(), at(2027, 3, 5, 9, 30));
+		assert_eq!(parse_datetime("5 mar 2028 1430", &clock).unwrap(), at(2028, 3, 5, 14, 30));
+		assert_eq!(parse_datetime("2027-03-05", &clock).unwrap(), at(2027, 3, 5, 0, 0));
+		assert_eq!(parse_datetime("tmrw 5pm", &new_years_eve()).unwrap(), at(2028, 1, 1, 17, 0));
+	}
+
+	#[test]
+	fn dates() {
+		// Each date as read on a Wednesday in March and on new year's eve.
+		let dates = [
+			("today", (2027, 3, 3), (2027, 12, 31)),
+			("yesterday", (2027, 3, 2), (2027, 12, 30)),
+			("tmrw", (2027, 3, 4), (2028, 1, 1)),
+			("Tomorrow", (2027, 3, 4), (2028, 1, 1)),
+			("wed", (2027, 3, 3), (2028, 1, 5)),
+			("friday", (2027, 3, 5), (2027, 12, 31)),
+			("tues", (2027, 3, 9), (2028, 1, 4)),
+			("next wed", (2027, 3, 10), (2028, 1, 5)),
+			("next fri", (2027, 3, 5), (2028, 1, 7)),
+			("in 1 day", (2027, 3, 4), (2028, 1, 1)),
+			("in 3 days", (2027, 3, 6), (2028, 1, 3)),
+			("In 2 Weeks", (2027, 3, 17), (2028, 1, 14)),
+			("in 1 month", (2027, 4, 3), (2028, 1, 31)),
+			("in 2 months", (2027, 5, 3), (2028, 2, 29)),
+			("next month", (2027, 4, 1), (2028, 1, 1)),
+			("end of month", (2027, 3, 31), (2027, 12, 31)),
+			("5 Mar", (2027, 3, 5), (2028, 3, 5)),
+			("mar 2", (2028, 3, 2), (2028, 3, 2)),
+			("29 feb", (2028, 2, 29), (2028, 2, 29)),
+			("March 5 2027", (2027, 3, 5), (2027, 3, 5)),
+			("5 march 2027", (2027, 3, 5), (2027, 3, 5)),
+			("Sept 30, 2027", (2027, 9, 30), (2027, 9, 30)),
+			("2027-03-05", (2027, 3, 5), (2027, 3, 5)),
+			("05/03/2027", (2027, 3, 5), (2027, 3, 5)),
+		];
+		let date = |s, clock| parse_datetime(s, &clock).ok().map(|datetime| datetime.date());
+		let ymd = |(y, m, d)| NaiveDate::from_ymd_opt(y, m, d);
+		for (s, on_wednesday, on_new_years_eve) in dates {
+			assert_eq!(date(s, wednesday()), ymd(on_wednesday), "{}", s);
+			assert_eq!(date(s, new_years_eve()), ymd(on_new_years_eve), "{}", s);
+		}
+
+		for s in ["in 0 days", "30 feb 2027", "2027-02-30", "31/04/2027", "next year", "5 smarch"] {
+			assert!(parse_datetime(s, &wednesday()).is_err(), "{}", s);
+		}
+	}
+
+	#[test]
+	fn sessions() {
+		let clock = wednesday();
+		let session = |s| parse_session(s, &clock).ok().map(|session| (session.start, session.end));
+		assert_eq!(session("9am to 10:30am"), Some((at(2027, 3, 3, 9, 0), at(2027, 3, 3, 10, 30))));
+		assert_eq!(session("tmrw 9am to 10am"), Some((at(2027, 3, 4, 9, 0), at(2027, 3, 4, 10, 0))));
+		assert_eq!(session("11pm to tmrw 1am"), Some((at(2027, 3, 3, 23, 0), at(2027, 3, 4, 1, 0))));
+		assert_eq!(session("fri to sat"), Some((at(2027, 3, 5, 0, 0), at(2027, 3, 6, 0, 0))));
+		assert_eq!(session("05/03/2027 0930 to 17:45"), Some((at(2027, 3, 5, 9, 30), at(2027, 3, 5, 17, 45))));
+		assert_eq!(session("in 2 days noon to 5pm"), Some((at(2027, 3, 5, 12, 0), at(2027, 3, 5, 17, 0))));
+		assert_eq!(session("5 Mar 2027 9am to March 6 2027 10am"), Some((at(2027, 3, 5, 9, 0), at(2027, 3, 6, 10, 0))));
+
+		assert_eq!(session("3pm for 90m"), Some((at(2027, 3, 3, 15, 0), at(2027, 3, 3, 16, 30))));
+		assert_eq!(session("today 9am for 45m"), Some((at(2027, 3, 3, 9, 0), at(2027, 3, 3, 9, 45))));
+		assert_eq!(session("9:15 for 1h30m"), Some((at(2027, 3, 3, 9, 15), at(2027, 3, 3, 10, 45))));
+		assert_eq!(session("10am for 2h"), Some((at(2027, 3, 3, 10, 0), at(2027, 3, 3, 12, 0))));
+		assert_eq!(session("10am FOR 1.5H"), Some((at(2027, 3, 3, 10, 0), at(2027, 3, 3, 11, 30))));
+		assert_eq!(session("now for 25m"), Some((at(2027, 3, 3, 10, 15), at(2027, 3, 3, 10, 40))));
+		assert_eq!(session("until 5pm"), Some((at(2027, 3, 3, 10, 15), at(2027, 3, 3, 17, 0))));
+		assert_eq!(session("until 2027-03-05 5pm"), Some((at(2027, 3, 3, 10, 15), at(2027, 3, 5, 17, 0))));
+		assert_eq!(parse_session("now for 10m", &new_years_eve()).unwrap().end, at(2028, 1, 1, 0, 0));
+		for s in ["10am for 0m", "10am for 1.5m", "10am for 4000000000h", "10am for 99999999999h", "9am", "9am to", "until", "until 9am", "until 10:15"] {
+			assert!(session(s).is_none(), "{}", s);
+		}
+	}
+
+	#[test]
+	fn recurrence() {
+		let rules = [
+			("every day", Rule::Days(1)),
+			("every 3 days", Rule::Days(3)),
+			("every week", Rule::Weeks(1, vec![])),
+			("every 2 weeks", Rule::Weeks(2, vec![])),
+			("every mon, thu", Rule::Weeks(1, vec![Weekday::Mon, Weekday::Thu])),
+			("every 2 weeks on fri", Rule::Weeks(2, vec![Weekday::Fri])),
+			("every month", Rule::Monthly(None)),
+			("every month on day 15", Rule::Monthly(Some(15))),
+		];
+		for (s, rule) in rules {
+			assert_eq!(parse_rule(s).ok(), Some(rule.clone()));
+			assert_eq!(rule.to_string(), s);
+		}
+		assert!(parse_rule("every 0 days").is_err());
+		assert!(parse_rule("every month on day 32").is_err());
+		assert_eq!(parse_rule("Every Monday,Wednesday").ok(), Some(Rule::Weeks(1, vec![Weekday::Mon, Weekday::Wed])));
+
+		let clock = wednesday();
+		let session = |s| parse_repeating_session(s, &clock).ok().map(|(session, rule)| (session.start, session.end, rule));
+		let weekly = |weekdays: &[Weekday]| Some(Rule::Weeks(1, weekdays.to_vec()));
+		assert_eq!(session("every mon 9am to 10am"), Some((at(2027, 3, 8, 9, 0), at(2027, 3, 8, 10, 0), weekly(&[Weekday::Mon]))));
+		assert_eq!(session("every mon 14:00 to 15:30"), Some((at(2027, 3, 8, 14, 0), at(2027, 3, 8, 15, 30), weekly(&[Weekday::Mon]))));
+		assert_eq!(session("every wed, fri 4pm for 1h"), Some((at(2027, 3, 3, 16, 0), at(2027, 3, 3, 17, 0), weekly(&[Weekday::Wed, Weekday::Fri]))));
+		assert_eq!(session("tmrw 8am for 30m every day"), Some((at(2027, 3, 4, 8, 0), at(2027, 3, 4, 8, 30), Some(Rule::Days(1)))));
+		assert_eq!(session("31/01/2023 9am to 11am every 2 days"), Some((at(2023, 1, 31, 9, 0), at(2023, 1, 31, 11, 0), Some(Rule::Days(2)))));
+		assert_eq!(session("31/01/2023 9am to 10am"), Some((at(2023, 1, 31, 9, 0), at(2023, 1, 31, 10, 0), None)));
+
+		let due = |s, clock| parse_repeating_datetime(s, &clock).ok();
+		assert_eq!(due("31/01/2023 every month", wednesday()), Some((at(2023, 1, 31, 0, 0), Some(Rule::Monthly(Some(31))))));
+		assert_eq!(due("fri 5pm every week", wednesday()), Some((at(2027, 3, 5, 17, 0), Some(Rule::Weeks(1, vec![])))));
+		assert_eq!(due("every month", wednesday()), Some((at(2027, 3, 3, 0, 0), Some(Rule::Monthly(Some(3))))));
+		assert_eq!(due("every month on day 1 9am", wednesday()), Some((at(2027, 4, 1, 9, 0), Some(Rule::Monthly(Some(1))))));
+		assert_eq!(due("every month on day 1 9am", new_years_eve()), Some((at(2028, 1, 1, 9, 0), Some(Rule::Monthly(Some(1))))));
+		assert!(due("every fortnight", wednesday()).is_none());
+	}
+
+	#[test]
+	fn errors() {
+		let clock = wednesday();
+		let session = |s| describe(&parse_repeating_session(s, &clock).unwrap_err());
+		assert_eq!(session("tomorrow 25pm to 26pm"), "expected time after 'tomorrow' at column 10");
+		assert_eq!(session("9am"), "expected 'to' or 'for' after '9am' at column 4");
+		assert_eq!(session("9am to"), "expected date or time after 'to' at column 7");
+		assert_eq!(session("9am for 0m"), "expected duration after 'for' at column 9");
+		assert_eq!(session("9am for 1h blah"), "unexpected 'blah' after '1h' at column 12");
+		assert_eq!(session("every mon"), "expected time after 'mon' at column 10");
+		assert_eq!(session("9am to 10am every fortnight"), "expected recurrence rule after 'every' at column 19");
+		assert_eq!(session(""), "expected date or time at column 1");
+		assert_eq!(session("until 9am"), "expected time after now after 'until' at column 7");
+		assert_eq!(session("until"), "expected date or time after 'until' at column 6");
+		assert_eq!(session("until 9zz"), "expected date or time after 'until' at column 7");
+
+		let datetime = |s| describe(&parse_repeating_datetime(s, &clock).unwrap_err());
+		assert_eq!(datetime("every month on day 32"), "expected day of the month after 'day' at column 20");
+		assert_eq!(datetime("in 3 lightyears"), "expected number of days, weeks or months after 'in' at column 4");
+		assert_eq!(datetime("next week"), "expected weekday or month after 'next' at column 6");
+		assert_eq!(datetime("fri 5pm tomorrow"), "unexpected 'tomorrow' after '5pm' at column 9");
+	}
 }
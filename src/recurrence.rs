@@ -0,0 +1,239 @@
+use std::fmt::{self, Display, Formatter};
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, Weekday};
+use grus_lib::Store;
+use grus_lib::types::Session;
+use grus_lib::writer::StoreWriter;
+use serde::{Deserialize, Serialize};
+use crate::app::Error;
+use crate::meta::Meta;
+use crate::parser::{describe, parse_rule};
+
+/// How often a due date or a session repeats.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Rule {
+	/// Every `n` days.
+	Days(u32),
+	/// Every `n` weeks on the given weekdays, or on the weekday of the occurrence if none are
+	/// given.
+	Weeks(u32, Vec<Weekday>),
+	/// Every month on the given day, or on the day of the occurrence if none is given. Days past
+	/// the end of a month fall on its last day.
+	Monthly(Option<u32>),
+}
+
+impl Rule {
+	/// Returns the first day on or after `today` on which the rule has an occurrence.
+	pub fn first(&self, today: NaiveDate) -> NaiveDate {
+		match self {
+			Rule::Weeks(_, weekdays) if !weekdays.is_empty() => {
+				today.iter_days().find(|date| weekdays.contains(&date.weekday())).unwrap_or(today)
+			}
+			&Rule::Monthly(Some(day)) if today.day() > day => month_day(today, 1, day),
+			&Rule::Monthly(Some(day)) => month_day(today, 0, day),
+			_ => today,
+		}
+	}
+
+	/// Fixes the day of a monthly rule to the day of its first occurrence, so that the occurrences
+	/// do not drift towards the start of the month after a short month.
+	pub fn anchored(self, first: NaiveDate) -> Self {
+		match self {
+			Rule::Monthly(None) => Rule::Monthly(Some(first.day())),
+			rule => rule,
+		}
+	}
+
+	/// Returns the occurrence after the one at `time`, at the same time of day.
+	pub fn next(&self, time: NaiveDateTime) -> NaiveDateTime {
+		let date = time.date();
+		let next = match self {
+			&Rule::Days(n) => date + Duration::days(n.into()),
+			Rule::Weeks(n, weekdays) if weekdays.is_empty() => date + Duration::weeks((*n).into()),
+			Rule::Weeks(n, weekdays) => {
+				// Weeks in between are skipped whenever the next occurrence falls in a new week.
+				let mut next = date;
+				loop {
+					next += Duration::days(1);
+					if next.weekday() == Weekday::Mon { next += Duration::weeks(i64::from(*n) - 1) };
+					if weekdays.contains(&next.weekday()) { break next };
+				}
+			}
+			&Rule::Monthly(day) => month_day(date, 1, day.unwrap_or(date.day())),
+		};
+		next.and_time(time.time())
+	}
+}
+
+/// Returns the given day of the month `months` after the month of `date`, or the last day of
+/// that month if it is shorter.
+fn month_day(date: NaiveDate, months: u32, day: u32) -> NaiveDate {
+	let first = date.with_day(1).unwrap_or(date) + Months::new(months);
+	(1..=day).rev().find_map(|day| first.with_day(day)).unwrap_or(first)
+}
+
+impl Display for Rule {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		let weekdays = |weekdays: &[Weekday]| {
+			weekdays.iter().map(|weekday| weekday.to_string().to_lowercase()).collect::<Vec<_>>().join(", ")
+		};
+		match self {
+			Rule::Days(1) => write!(f, "every day"),
+			Rule::Days(n) => write!(f, "every {} days", n),
+			Rule::Weeks(1, days) if days.is_empty() => write!(f, "every week"),
+			Rule::Weeks(n, days) if days.is_empty() => write!(f, "every {} weeks", n),
+			Rule::Weeks(1, days) => write!(f, "every {}", weekdays(days)),
+			Rule::Weeks(n, days) => write!(f, "every {} weeks on {}", n, weekdays(days)),
+			Rule::Monthly(None) => write!(f, "every month"),
+			Rule::Monthly(Some(day)) => write!(f, "every month on day {}", day),
+		}
+	}
+}
+
+impl TryFrom<String> for Rule {
+	type Error = String;
+
+	fn try_from(s: String) -> Result<Self, Self::Error> {
+		parse_rule(&s).map_err(|e| format!("invalid recurrence '{}': {}", s, describe(&e)))
+	}
+}
+
+impl From<Rule> for String {
+	fn from(rule: Rule) -> Self {
+		rule.to_string()
+	}
+}
+
+/// A session which is followed by another one whenever it has ended.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Repeating {
+	pub start: NaiveDateTime,
+	pub end: NaiveDateTime,
+	pub rule: Rule,
+}
+
+impl Repeating {
+	pub fn session(&self) -> Session {
+		Session { start: self.start, end: self.end }
+	}
+
+	/// Returns the occurrence after this one, lasting as long.
+	pub fn next(&self) -> Repeating {
+		let start = self.rule.next(self.start);
+		Repeating { start, end: start + (self.end - self.start), rule: self.rule.clone() }
+	}
+
+	/// Returns the occurrences after this one which start before `until`.
+	pub fn upcoming(&self, until: NaiveDateTime) -> impl Iterator<Item = Session> {
+		let mut current = self.clone();
+		std::iter::from_fn(move || {
+			current = current.next();
+			(current.start < until).then(|| current.session())
+		})
+	}
+}
+
+/// Completes the current occurrence of a repeating due date by moving the due date to the first
+/// occurrence after both the current one and `now`. Returns false if the due date of the task
+/// does not repeat, in which case the task is to be marked as done instead.
+pub fn complete(writer: &mut StoreWriter, meta: &Meta, id: u64, now: NaiveDateTime) -> Result<bool, sanakirja::Error> {
+	let (Some(rule), Some(mut due_date)) = (meta.repeat(id), writer.due_date(id)?) else { return Ok(false) };
+	loop {
+		due_date = rule.next(due_date);
+		if due_date > now { break };
+	}
+	writer.set_due_date(id, due_date)?;
+	Ok(true)
+}
+
+/// Adds the next occurrence of every repeating session which has ended, skipping the ones which
+/// have ended as well, and forgets the repetition of sessions which were deleted. Returns whether
+/// the repetitions changed, in which case they are to be saved. The store is left alone if no
+/// session has ended.
+pub fn advance(store: &Store, meta: &mut Meta, now: NaiveDateTime) -> Result<bool, Error> {
+	let tasks: Vec<(u64, Vec<Repeating>)> = meta.repeating().map(|(id, repeating)| (id, repeating.to_vec())).collect();
+	// Repetitions of deleted sessions are forgotten once they would have ended as well.
+	if !tasks.iter().flat_map(|(_, repeating)| repeating).any(|occurrence| occurrence.end <= now) {
+		return Ok(false);
+	}
+
+	let mut changed = false;
+	let mut writer = store.writer()?;
+	for (id, repeating) in tasks {
+		let sessions = writer.sessions(id)?.map(|entry| entry.map(|(_, &session)| session)).collect::<Result<Vec<_>, _>>()?;
+		let mut kept = Vec::new();
+		for mut occurrence in repeating {
+			if !sessions.contains(&occurrence.session()) {
+				changed = true;
+				continue;
+			}
+			if occurrence.end <= now {
+				while occurrence.end <= now { occurrence = occurrence.next() };
+				writer.add_session(id, &occurrence.session())?;
+				changed = true;
+			}
+			kept.push(occurrence);
+		}
+		meta.set_repeating(id, kept);
+	}
+	writer.commit()?;
+	Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{NaiveDate, Weekday};
+	use grus_lib::Store;
+	use grus_lib::types::Session;
+	use crate::meta::Meta;
+	use crate::testing::TempDir;
+	use super::{advance, Repeating, Rule};
+
+	#[test]
+	fn next_occurrence() {
+		let at = |m, d, h| NaiveDate::from_ymd_opt(2023, m, d).unwrap().and_hms_opt(h, 0, 0).unwrap();
+		// 3 July 2023 is a Monday.
+		assert_eq!(Rule::Days(1).next(at(7, 3, 9)), at(7, 4, 9));
+		assert_eq!(Rule::Days(3).next(at(7, 31, 9)), at(8, 3, 9));
+		assert_eq!(Rule::Weeks(2, vec![]).next(at(7, 3, 9)), at(7, 17, 9));
+		let mon_thu = Rule::Weeks(1, vec![Weekday::Mon, Weekday::Thu]);
+		assert_eq!(mon_thu.next(at(7, 3, 9)), at(7, 6, 9));
+		assert_eq!(mon_thu.next(at(7, 6, 9)), at(7, 10, 9));
+		assert_eq!(Rule::Weeks(2, vec![Weekday::Mon, Weekday::Thu]).next(at(7, 6, 9)), at(7, 17, 9));
+		assert_eq!(Rule::Monthly(Some(31)).next(at(1, 31, 9)), at(2, 28, 9));
+		assert_eq!(Rule::Monthly(Some(31)).next(at(2, 28, 9)), at(3, 31, 9));
+
+		let today = NaiveDate::from_ymd_opt(2023, 7, 5).unwrap();
+		assert_eq!(mon_thu.first(today), NaiveDate::from_ymd_opt(2023, 7, 6).unwrap());
+		assert_eq!(Rule::Monthly(Some(2)).first(today), NaiveDate::from_ymd_opt(2023, 8, 2).unwrap());
+		assert_eq!(Rule::Monthly(None).anchored(today), Rule::Monthly(Some(5)));
+	}
+
+	#[test]
+	fn advance_ended() {
+		let dir = TempDir::new();
+		let store = Store::open(dir.join("tasks"), 2).unwrap();
+		let mut meta = Meta::default();
+		let at = |d, h| NaiveDate::from_ymd_opt(2023, 7, d).unwrap().and_hms_opt(h, 0, 0).unwrap();
+		let sessions = |store: &Store, id| store.reader().unwrap().sessions(id).unwrap()
+			.map(|entry| entry.map(|(_, &session)| session)).collect::<Result<Vec<_>, _>>().unwrap();
+
+		let mut writer = store.writer().unwrap();
+		let a = writer.add_child(0, "a").unwrap();
+		let b = writer.add_child(0, "b").unwrap();
+		writer.add_session(a, &Session { start: at(3, 9), end: at(3, 10) }).unwrap();
+		writer.commit().unwrap();
+		meta.add_repeating(a, Repeating { start: at(3, 9), end: at(3, 10), rule: Rule::Days(1) });
+		// The session of b was deleted, but is only forgotten once it would have ended.
+		meta.add_repeating(b, Repeating { start: at(3, 11), end: at(3, 12), rule: Rule::Days(1) });
+
+		assert!(!advance(&store, &mut meta, at(3, 9)).unwrap());
+		assert_eq!(sessions(&store, a).len(), 1);
+		assert_eq!(meta.repeating().count(), 2);
+
+		assert!(advance(&store, &mut meta, at(5, 12)).unwrap());
+		assert_eq!(sessions(&store, a), [Session { start: at(3, 9), end: at(3, 10) }, Session { start: at(6, 9), end: at(6, 10) }]);
+		assert_eq!(meta.repeating().map(|(id, repeating)| (id, repeating.len())).collect::<Vec<_>>(), [(a, 1)]);
+		assert!(!advance(&store, &mut meta, at(5, 13)).unwrap());
+	}
+}
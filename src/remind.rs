@@ -0,0 +1,233 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::process::Command;
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+use grus_lib::Store;
+use serde::{Deserialize, Serialize};
+use crate::app::{Error, StoreArgs};
+use crate::clock::FixedClock;
+use crate::config::Config;
+use crate::meta::Meta;
+use crate::node::{Displayable, TimeFormat};
+use crate::snapshot::Snapshot;
+use crate::sync::with_suffix;
+
+const DEFAULT_REMIND_BEFORE: u32 = 10;
+
+/// How often the interface and the daemon look for reminders which have come up.
+pub const INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+	Due,
+	Session,
+}
+
+/// A due date or the start of a session, which is reminded of once.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub struct Reminder {
+	pub id: u64,
+	pub kind: Kind,
+	pub at: NaiveDateTime,
+}
+
+/// A reminder along with the name of its task, ready to be delivered.
+pub struct Notice {
+	pub reminder: Reminder,
+	pub name: String,
+	/// When the reminder came up, which its time is written relative to.
+	pub now: NaiveDateTime,
+	pub time_format: TimeFormat,
+}
+
+impl Notice {
+	pub fn body(&self) -> String {
+		match self.reminder.kind {
+			Kind::Due => format!("due {}", Displayable(Some(self.reminder.at), FixedClock(self.now), self.time_format)),
+			Kind::Session => format!("starts {}", Displayable(Some(self.reminder.at), FixedClock(self.now), self.time_format)),
+		}
+	}
+}
+
+impl Display for Notice {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "{}: {}", self.name, self.body())
+	}
+}
+
+/// Finds the reminders which have come up and delivers them, by running the configured command
+/// or by printing them.
+pub struct Notifier {
+	before: Duration,
+	command: Option<String>,
+	time_format: TimeFormat,
+}
+
+impl Notifier {
+	pub fn new(config: &Config) -> Self {
+		Notifier {
+			before: Duration::minutes(config.remind_before.unwrap_or(DEFAULT_REMIND_BEFORE).into()),
+			command: config.notify_command.clone(),
+			time_format: config.time_format,
+		}
+	}
+
+	pub fn has_command(&self) -> bool {
+		self.command.is_some()
+	}
+
+	/// Returns the reminders which have come up and were not delivered yet according to
+	/// `<data path>.reminders`. The file is read again on every check, so that the interface and
+	/// a daemon deliver each reminder only once between them, as long as each marks the reminders
+	/// it delivered.
+	pub fn check(&self, store: &Store, meta: &Meta, store_args: &StoreArgs, now: NaiveDateTime) -> Result<Vec<Notice>, Error> {
+		if self.before.is_zero() { return Ok(Vec::new()) };
+		let path = with_suffix(&store_args.path, ".reminders");
+		let mut delivered = Delivered::load(&path)?;
+		let snapshot = Snapshot::read(&store.reader()?)?;
+
+		// Reminders of times which have passed cannot come up again, so they are forgotten.
+		let count = delivered.reminders.len();
+		delivered.reminders.retain(|reminder| reminder.at > now);
+		if delivered.reminders.len() != count { delivered.save(&path)? };
+
+		Ok(pending(&snapshot, meta, now, self.before, self.time_format).into_iter()
+			.filter(|notice| !delivered.reminders.contains(&notice.reminder))
+			.collect())
+	}
+
+	/// Records the reminders as delivered, so that later checks no longer return them.
+	pub fn mark_delivered(&self, store_args: &StoreArgs, reminders: &[Reminder]) -> Result<(), Error> {
+		if reminders.is_empty() { return Ok(()) };
+		let path = with_suffix(&store_args.path, ".reminders");
+		let mut delivered = Delivered::load(&path)?;
+		delivered.reminders.extend(reminders.iter().cloned());
+		delivered.save(&path)
+	}
+
+	/// Runs the notification command through the shell with the name of the task and the
+	/// reminder as its last two arguments, as in `notify-send <name> "due 5 PM"`, or writes the
+	/// reminder to `out` if no command is configured. Whatever the command prints goes to `out`.
+	pub fn deliver(&self, notice: &Notice, out: &mut impl io::Write) -> Result<(), Error> {
+		let Some(command) = &self.command else {
+			writeln!(out, "{}", notice)?;
+			return Ok(());
+		};
+		let output = Command::new("sh")
+			.arg("-c")
+			.arg(format!("{} \"$@\"", command))
+			.arg("grus")
+			.arg(&notice.name)
+			.arg(notice.body())
+			.output()?;
+		out.write_all(&output.stdout)?;
+		if !output.status.success() {
+			let mut message = format!("notification command failed with {}", output.status);
+			let stderr = String::from_utf8_lossy(&output.stderr);
+			if !stderr.trim().is_empty() { message = format!("{}: {}", message, stderr.trim()) };
+			return Err(io::Error::other(message).into());
+		}
+		Ok(())
+	}
+}
+
+/// Lists the due dates of tasks which are not done and the starts of sessions which lie within
+/// `before` after `now`. Due dates without a time of day are not reminded of.
+fn pending(snapshot: &Snapshot, meta: &Meta, now: NaiveDateTime, before: Duration, time_format: TimeFormat) -> Vec<Notice> {
+	let upcoming = |at: NaiveDateTime| now < at && at - before <= now;
+	let mut notices = Vec::new();
+	for (&id, task) in &snapshot.tasks {
+		if id == 0 { continue };
+		let mut notify = |kind, at| notices.push(Notice { reminder: Reminder { id, kind, at }, name: task.name.clone(), now, time_format });
+		if let Some(due_date) = task.due_date {
+			if due_date.time() != NaiveTime::MIN && upcoming(due_date) && meta.done(id).is_none() {
+				notify(Kind::Due, due_date);
+			}
+		}
+		for session in task.sessions.iter().filter(|session| upcoming(session.start)) {
+			notify(Kind::Session, session.start);
+		}
+	}
+	notices.sort_by_key(|notice| notice.reminder.at);
+	notices
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct Delivered {
+	reminders: BTreeSet<Reminder>,
+}
+
+impl Delivered {
+	fn load(path: &Path) -> Result<Self, Error> {
+		match fs::read(path) {
+			Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+			Err(e) if e.kind() == ErrorKind::NotFound => Ok(Delivered::default()),
+			Err(e) => Err(e.into()),
+		}
+	}
+
+	fn save(&self, path: &Path) -> Result<(), Error> {
+		fs::write(path, serde_json::to_vec(self)?)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{Duration, NaiveDate};
+	use grus_lib::Store;
+	use grus_lib::types::Session;
+	use crate::app::StoreArgs;
+	use crate::config::Config;
+	use crate::node::TimeFormat;
+	use crate::meta::Meta;
+	use crate::testing::TempDir;
+	use super::{Kind, Notifier};
+
+	#[test]
+	fn deliver_once() {
+		let dir = TempDir::new();
+		let path = dir.join("tasks");
+		let store_args = StoreArgs { n_roots: 2, path: path.clone(), export_path: dir.join("export"), calendar_path: dir.join("ics") };
+		let store = Store::open(&path, 2).unwrap();
+		let now = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap().and_hms_opt(9, 0, 0).unwrap();
+
+		let mut writer = store.writer().unwrap();
+		let report = writer.add_child(0, "report").unwrap();
+		writer.set_due_date(report, now + Duration::minutes(5)).unwrap();
+		let call = writer.add_child(0, "call").unwrap();
+		writer.add_session(call, &Session { start: now + Duration::minutes(30), end: now + Duration::hours(1) }).unwrap();
+		let later = writer.add_child(0, "later").unwrap();
+		writer.set_due_date(later, now.date().and_hms_opt(0, 0, 0).unwrap() + Duration::days(1)).unwrap();
+		writer.commit().unwrap();
+
+		let config = Config { remind_before: Some(10), ..Config::default() };
+		let notifier = Notifier::new(&config);
+		let meta = Meta::default();
+		let notices = notifier.check(&store, &meta, &store_args, now).unwrap();
+		assert_eq!(notices.len(), 1);
+		assert_eq!((notices[0].reminder.id, notices[0].reminder.kind), (report, Kind::Due));
+		// A reminder which was not delivered comes up again.
+		assert_eq!(notifier.check(&store, &meta, &store_args, now).unwrap().len(), 1);
+		notifier.mark_delivered(&store_args, &[notices[0].reminder.clone()]).unwrap();
+		assert!(notifier.check(&store, &meta, &store_args, now + Duration::minutes(1)).unwrap().is_empty());
+
+		let notices = notifier.check(&store, &meta, &store_args, now + Duration::minutes(25)).unwrap();
+		assert_eq!(notices.len(), 1);
+		assert_eq!((notices[0].reminder.id, notices[0].reminder.kind), (call, Kind::Session));
+
+		let mut out = Vec::new();
+		notifier.deliver(&notices[0], &mut out).unwrap();
+		assert_eq!(String::from_utf8(out).unwrap(), "call: starts 9:30 AM\n");
+		let twenty_four = Notifier::new(&Config { remind_before: Some(10), time_format: TimeFormat::TwentyFour, ..Config::default() });
+		assert_eq!(twenty_four.check(&store, &meta, &store_args, now + Duration::minutes(25)).unwrap()[0].body(), "starts 09:30");
+
+		let failing = Notifier::new(&Config { notify_command: Some("false".into()), ..config });
+		assert!(failing.deliver(&notices[0], &mut Vec::new()).is_err());
+		assert_eq!(notifier.check(&store, &meta, &store_args, now + Duration::minutes(25)).unwrap().len(), 1);
+	}
+}
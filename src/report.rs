@@ -0,0 +1,120 @@
+use std::collections::{HashMap, HashSet};
+use chrono::{Duration, NaiveDateTime};
+use grus_lib::reader::StoreReader;
+
+/// The time spent on a task within a period. `own` sums the sessions of the task itself and
+/// `total` those of the task and all of its descendants, where a task reachable through several
+/// paths is counted only once.
+pub struct Row {
+	pub id: u64,
+	pub name: String,
+	pub depth: usize,
+	pub own: Duration,
+	pub total: Duration,
+}
+
+/// Sessions are counted from `from` up to, but not including, `to`. Missing bounds leave the
+/// period open on that side.
+#[derive(Copy, Clone, Default)]
+pub struct Period {
+	pub from: Option<NaiveDateTime>,
+	pub to: Option<NaiveDateTime>,
+}
+
+impl Period {
+	/// Returns the part of the session which lies within the period.
+	fn overlap(&self, start: NaiveDateTime, end: NaiveDateTime) -> Duration {
+		let start = self.from.map_or(start, |from| start.max(from));
+		let end = self.to.map_or(end, |to| end.min(to));
+		(end - start).max(Duration::zero())
+	}
+}
+
+/// Lists the tasks of the subtree at `root` which have time within the period, in depth-first
+/// order. A task with several parents in the subtree is listed once, under the first of them.
+pub fn build(reader: &StoreReader, root: u64, period: Period) -> Result<Vec<Row>, sanakirja::Error> {
+	let mut order = Vec::new();
+	let mut children: HashMap<u64, Vec<u64>> = HashMap::new();
+	let mut own = HashMap::new();
+	let mut stack = vec![(root, 0)];
+	let mut visited = HashSet::new();
+	while let Some((id, depth)) = stack.pop() {
+		if !visited.insert(id) { continue };
+		order.push((id, depth));
+
+		let mut time = Duration::zero();
+		for entry in reader.sessions(id)? {
+			let (_, session) = entry?;
+			time = time + period.overlap(session.start, session.end);
+		}
+		own.insert(id, time);
+
+		let ids = reader.child_ids(id)?.collect::<Result<Vec<_>, _>>()?;
+		stack.extend(ids.iter().rev().map(|&child| (child, depth + 1)));
+		children.insert(id, ids);
+	}
+
+	let mut rows = Vec::new();
+	for (id, depth) in order {
+		let mut total = Duration::zero();
+		let mut stack = vec![id];
+		let mut descendants = HashSet::from([id]);
+		while let Some(id) = stack.pop() {
+			total = total + own[&id];
+			for &child in &children[&id] {
+				if descendants.insert(child) { stack.push(child) };
+			}
+		}
+		if total.is_zero() { continue };
+		let name = reader.name(id)?.unwrap_or_default().into();
+		rows.push(Row { id, name, depth, own: own[&id], total });
+	}
+	Ok(rows)
+}
+
+/// Formats a duration as hours and minutes, such as 12:05.
+pub fn format_duration(duration: Duration) -> String {
+	let minutes = duration.num_minutes();
+	format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{Duration, NaiveDate};
+	use grus_lib::Store;
+	use grus_lib::types::Session;
+	use crate::testing::TempDir;
+	use super::{build, format_duration, Period};
+
+	#[test]
+	fn roll_up() {
+		let dir = TempDir::new();
+		let store = Store::open(dir.join("tasks"), 2).unwrap();
+		let day = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap().and_hms_opt(0, 0, 0).unwrap();
+		let session = |hour, hours| Session { start: day + Duration::hours(hour), end: day + Duration::hours(hour + hours) };
+
+		let mut writer = store.writer().unwrap();
+		let work = writer.add_child(0, "work").unwrap();
+		let a = writer.add_child(work, "a").unwrap();
+		let b = writer.add_child(work, "b").unwrap();
+		let shared = writer.add_child(a, "shared").unwrap();
+		writer.share(shared, b).unwrap();
+		writer.add_child(work, "idle").unwrap();
+		writer.add_session(a, &session(8, 1)).unwrap();
+		writer.add_session(shared, &session(10, 2)).unwrap();
+		writer.add_session(b, &session(23, 2)).unwrap();
+		writer.commit().unwrap();
+
+		let reader = store.reader().unwrap();
+		let rows = build(&reader, work, Period::default()).unwrap();
+		let table: Vec<_> = rows.iter()
+			.map(|row| format!("{}{} {} {}", " ".repeat(row.depth), row.name, format_duration(row.own), format_duration(row.total)))
+			.collect();
+		assert_eq!(table, ["work 0:00 5:00", " b 2:00 4:00", "  shared 2:00 2:00", " a 1:00 3:00"]);
+
+		let period = Period { from: Some(day + Duration::hours(9)), to: Some(day + Duration::hours(24)) };
+		let rows = build(&reader, work, period).unwrap();
+		assert_eq!(rows[0].total, Duration::hours(3));
+		assert_eq!(rows.len(), 4);
+	}
+}
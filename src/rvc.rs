@@ -0,0 +1,130 @@
+use std::io;
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use crossterm::event::{self, Event};
+use grus_lib::Store;
+use crate::app::{Action, Error, View};
+use crate::clock::Clock;
+use crate::keymap::{Keymap, ReportAction};
+use crate::report::{self, Period};
+use crate::ui::{BufPrint, Screen};
+use crate::ui::backend::Backend;
+use crate::ui::report::ReportView;
+use crate::ui::status::StatusView;
+
+pub struct ReportViewController {
+	report_view: ReportView,
+	status_view: StatusView<{View::Report as usize}>,
+	keymap: Keymap<ReportAction>,
+	span: Span,
+	/// A day within the period shown.
+	date: NaiveDate,
+}
+
+/// The length of the period shown, which is a calendar week or month, or all time.
+#[derive(Copy, Clone)]
+enum Span {
+	Week,
+	Month,
+	All,
+}
+
+impl ReportViewController {
+	/// Shows the week of the day of the clock.
+	pub fn new(clock: &dyn Clock, keymap: Keymap<ReportAction>, (w, h): (u16, u16)) -> Result<Self, Error> {
+		Ok(ReportViewController {
+			report_view: ReportView::new(w, h),
+			status_view: StatusView::new(w, h),
+			keymap,
+			span: Span::Week,
+			date: clock.today(),
+		})
+	}
+
+	pub fn run(&mut self, store: &Store) -> Result<Action, Error> {
+		self.status_view.clear_message();
+		match event::read()? {
+			Event::Key(kev) => match self.keymap.feed(kev) {
+				Some(ReportAction::Quit) => return Ok(Action::Quit),
+				Some(ReportAction::CursorDown) => self.report_view.cursor_down(),
+				Some(ReportAction::CursorUp) => self.report_view.cursor_up(),
+				Some(ReportAction::NextPeriod) => self.shift(store, 1)?,
+				Some(ReportAction::PreviousPeriod) => self.shift(store, -1)?,
+				Some(ReportAction::TogglePeriod) => {
+					self.span = match self.span {
+						Span::Week => Span::Month,
+						Span::Month => Span::All,
+						Span::All => Span::Week,
+					};
+					self.update_report_view(store)?;
+				}
+				Some(ReportAction::ShowTask) => if let Some(path) = self.report_view.cursor_path() {
+					return Ok(Action::ShowTask(path));
+				},
+				Some(ReportAction::SwitchTree) => return Ok(Action::Switch(View::Tree)),
+				Some(ReportAction::SwitchSession) => return Ok(Action::Switch(View::Session)),
+				Some(ReportAction::SwitchAgenda) => return Ok(Action::Switch(View::Agenda)),
+				Some(ReportAction::SwitchWeek) => return Ok(Action::Switch(View::Week)),
+				None => {},
+			}
+			Event::Resize(w, h) => self.resize(w, h),
+			_ => {}
+		}
+		Ok(Action::None)
+	}
+
+	pub fn set_message(&mut self, message: String) {
+		self.status_view.set_message(message);
+	}
+
+	pub fn set_info(&mut self, info: String) {
+		self.status_view.set_info(info);
+	}
+
+	pub fn resize(&mut self, w: u16, h: u16) {
+		self.report_view.constr.update(w, h);
+		self.status_view.constr.update(w, h);
+	}
+
+	pub fn update_report_view(&mut self, store: &Store) -> Result<(), Error> {
+		let (title, period) = match self.span {
+			Span::Week => {
+				let monday = self.date - Duration::days(self.date.weekday().num_days_from_monday().into());
+				(format!("Week of {}", monday.format("%-d %b %Y")), period(monday, monday + Duration::weeks(1)))
+			}
+			Span::Month => {
+				let first = self.date.with_day(1).unwrap_or(self.date);
+				(first.format("%B %Y").to_string(), period(first, first + Months::new(1)))
+			}
+			Span::All => ("All time".into(), Period::default()),
+		};
+		let rows = report::build(&store.reader()?, 0, period)?;
+		self.report_view.reset(title, rows);
+		Ok(())
+	}
+
+	/// Moves to the next or previous period of the same length.
+	fn shift(&mut self, store: &Store, by: i32) -> Result<(), Error> {
+		self.date = match self.span {
+			Span::Week => self.date + Duration::weeks(by.into()),
+			Span::Month if by > 0 => self.date + Months::new(by.unsigned_abs()),
+			Span::Month => self.date - Months::new(by.unsigned_abs()),
+			Span::All => return Ok(()),
+		};
+		self.update_report_view(store)
+	}
+}
+
+fn period(from: NaiveDate, to: NaiveDate) -> Period {
+	Period { from: from.and_hms_opt(0, 0, 0), to: to.and_hms_opt(0, 0, 0) }
+}
+
+impl<B: Backend> BufPrint<ReportViewController> for Screen<B> {
+	fn bufprint(&mut self, rvc: &ReportViewController) -> io::Result<&mut Self> {
+		self
+			.clear()?
+			.bufprint(&rvc.status_view)?
+			.bufprint(&rvc.report_view)?
+			.flush()?;
+		Ok(self)
+	}
+}
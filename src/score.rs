@@ -0,0 +1,66 @@
+use chrono::NaiveDateTime;
+use grus_lib::types::Session;
+use crate::node::Priority;
+
+const PRIORITY: f64 = 4.0;
+const DUE: f64 = 8.0;
+const OVERDUE: f64 = 12.0;
+const SESSION: f64 = 4.0;
+const SHARED: f64 = 1.0;
+
+/// What is known of a task when ranking it among its siblings.
+pub struct Urgency {
+	pub priority: Priority,
+	pub due_date: Option<NaiveDateTime>,
+	/// The earliest session which has not ended yet.
+	pub next_session: Option<Session>,
+	pub parents: usize,
+}
+
+impl Urgency {
+	/// Scores how urgent the task is, higher being more urgent. The manual priority among
+	/// siblings gives up to 4 points, a due date up to 8 points as it draws near and 12 points
+	/// once it has passed, an upcoming session up to 4 points as it draws near, and every parent
+	/// beyond the first 1 point, since a shared task holds up more than one task.
+	pub fn score(&self, now: NaiveDateTime) -> f64 {
+		let mut score = PRIORITY * (1.0 - self.priority.det as f64 / self.priority.total.max(1) as f64);
+		if let Some(due_date) = self.due_date {
+			score += if due_date < now { OVERDUE } else { DUE / (1.0 + days(due_date - now)) };
+		}
+		if let Some(session) = self.next_session {
+			score += SESSION / (1.0 + days(session.start - now).max(0.0));
+		}
+		score + SHARED * self.parents.saturating_sub(1) as f64
+	}
+}
+
+fn days(duration: chrono::Duration) -> f64 {
+	duration.num_minutes() as f64 / (24.0 * 60.0)
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{Duration, NaiveDate};
+	use grus_lib::types::Session;
+	use crate::node::Priority;
+	use super::Urgency;
+
+	#[test]
+	fn urgency_order() {
+		let now = NaiveDate::from_ymd_opt(2023, 7, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+		let urgency = |det, due_date: Option<Duration>, session: Option<Duration>, parents| Urgency {
+			priority: Priority { det, total: 3 },
+			due_date: due_date.map(|d| now + d),
+			next_session: session.map(|d| Session { start: now + d, end: now + d + Duration::hours(1) }),
+			parents,
+		}.score(now);
+
+		assert!(urgency(0, None, None, 1) > urgency(2, None, None, 1));
+		assert!(urgency(2, Some(Duration::days(-3)), None, 1) > urgency(2, Some(Duration::hours(2)), None, 1));
+		assert!(urgency(2, Some(Duration::days(1)), None, 1) > urgency(0, Some(Duration::days(30)), None, 1));
+		assert!(urgency(2, None, Some(Duration::hours(1)), 1) > urgency(0, None, None, 1));
+		assert!(urgency(2, None, Some(Duration::minutes(-30)), 1) > urgency(2, None, Some(Duration::days(2)), 1));
+		assert!(urgency(1, None, None, 3) > urgency(1, None, None, 1));
+		assert_eq!(urgency(0, None, None, 1), 4.0);
+	}
+}
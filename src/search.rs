@@ -0,0 +1,130 @@
+use std::collections::{HashMap, VecDeque};
+use crate::snapshot::Snapshot;
+use crate::ui::search::Item;
+
+/// Every task of the store with the shortest path of ids leading to it from the root task,
+/// which is what fuzzy search matches against.
+pub struct Index {
+	entries: Vec<Entry>,
+}
+
+pub struct Entry {
+	pub id: u64,
+	pub name: String,
+	/// Ids from the root task to the task, both included.
+	pub path: Vec<u64>,
+	/// Names of the ancestors of the task, as in `/work/report`.
+	pub location: String,
+}
+
+impl Index {
+	pub fn new(snapshot: &Snapshot) -> Self {
+		let mut paths = HashMap::from([(0, (vec![0], String::new()))]);
+		let mut entries = Vec::new();
+		let mut queue = VecDeque::from([0]);
+		while let Some(id) = queue.pop_front() {
+			let (path, location) = paths[&id].clone();
+			for &child in &snapshot.tasks[&id].children {
+				if paths.contains_key(&child) { continue };
+				let name = snapshot.tasks[&child].name.clone();
+				let mut child_path = path.clone();
+				child_path.push(child);
+				paths.insert(child, (child_path.clone(), format!("{}/{}", location, name)));
+				entries.push(Entry {
+					id: child,
+					name,
+					path: child_path,
+					location: if location.is_empty() { "/".into() } else { location.clone() },
+				});
+				queue.push_back(child);
+			}
+		}
+		Index { entries }
+	}
+
+	pub fn entries(&self) -> &[Entry] {
+		&self.entries
+	}
+
+	/// Returns the tasks whose names match every word of the query, best match first.
+	pub fn rank(&self, query: &str) -> Vec<&Entry> {
+		let words: Vec<&str> = query.split_whitespace().collect();
+		if words.is_empty() { return Vec::new() };
+
+		let mut matches: Vec<(i64, &Entry)> = self.entries.iter().filter_map(|entry| {
+			let score = words.iter().map(|word| score(word, &entry.name)).sum::<Option<i64>>()?;
+			Some((score, entry))
+		}).collect();
+		matches.sort_by(|(a, ea), (b, eb)| b.cmp(a).then(ea.name.len().cmp(&eb.name.len())).then(ea.id.cmp(&eb.id)));
+		matches.into_iter().map(|(_, entry)| entry).collect()
+	}
+
+	/// Ranks the tasks against the query as the lines of the search view.
+	pub fn items(&self, query: &str) -> Vec<Item> {
+		self.rank(query).into_iter().map(|entry| Item {
+			name: entry.name.clone(),
+			location: entry.location.clone(),
+			path: entry.path.clone(),
+		}).collect()
+	}
+}
+
+const MATCH: i64 = 16;
+const CONSECUTIVE: i64 = 8;
+const BOUNDARY: i64 = 10;
+
+/// Scores how well `pattern` matches `text` as a subsequence, or returns None if it does not.
+/// Matches at the start of words and runs of consecutive characters score higher, and every
+/// character skipped between the first and last match costs a point. Matching ignores case,
+/// unless the pattern contains an upper case letter.
+pub fn score(pattern: &str, text: &str) -> Option<i64> {
+	let fold = !pattern.chars().any(char::is_uppercase);
+	let normalize = |c: char| if fold { c.to_lowercase().next().unwrap_or(c) } else { c };
+	let pattern: Vec<char> = pattern.chars().map(normalize).collect();
+	let text: Vec<char> = text.chars().collect();
+	let first = *pattern.first()?;
+
+	// Each occurrence of the first character is tried as the start of the match, taking the
+	// following characters greedily.
+	(0..text.len()).filter(|&i| normalize(text[i]) == first).filter_map(|start| {
+		let mut score = 0;
+		let mut prev: Option<usize> = None;
+		let mut chars = pattern.iter().peekable();
+		for (i, &c) in text.iter().enumerate().skip(start) {
+			let Some(&&p) = chars.peek() else { break };
+			if normalize(c) != p { continue };
+			chars.next();
+			score += MATCH;
+			if is_boundary(&text, i) { score += BOUNDARY };
+			match prev {
+				Some(prev) if prev + 1 == i => score += CONSECUTIVE,
+				Some(prev) => score -= (i - prev - 1) as i64,
+				None => {},
+			}
+			prev = Some(i);
+		}
+		chars.peek().is_none().then_some(score)
+	}).max()
+}
+
+fn is_boundary(text: &[char], i: usize) -> bool {
+	let Some(&prev) = i.checked_sub(1).and_then(|i| text.get(i)) else { return true };
+	!prev.is_alphanumeric() || (prev.is_lowercase() && text[i].is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::score;
+
+	#[test]
+	fn fuzzy_score() {
+		assert_eq!(score("rpt", "write report"), Some(3 * 16 + 10 - 1 - 2));
+		assert_eq!(score("xyz", "write report"), None);
+		assert_eq!(score("", "write report"), None);
+		assert!(score("rep", "write report") > score("rep", "prepare"));
+		assert!(score("wr", "write report") > score("wr", "awkward"));
+		assert!(score("fb", "fooBar") > score("fb", "foobar"));
+		assert_eq!(score("R", "report"), None);
+		assert!(score("Rep", "Report").is_some());
+	}
+}
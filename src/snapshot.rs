@@ -0,0 +1,57 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use chrono::NaiveDateTime;
+use grus_lib::reader::StoreReader;
+use grus_lib::types::Session;
+
+/// A copy of every task reachable from the root task, keyed by id.
+pub struct Snapshot {
+	pub tasks: BTreeMap<u64, Task>,
+}
+
+pub struct Task {
+	pub name: String,
+	pub due_date: Option<NaiveDateTime>,
+	pub sessions: Vec<Session>,
+	pub children: Vec<u64>,
+}
+
+impl Snapshot {
+	pub fn read(reader: &StoreReader) -> Result<Self, sanakirja::Error> {
+		let mut tasks = BTreeMap::new();
+		let mut queue = VecDeque::from([0]);
+		while let Some(id) = queue.pop_front() {
+			if tasks.contains_key(&id) { continue };
+
+			let mut sessions = Vec::new();
+			for entry in reader.sessions(id)? {
+				let (_, &session) = entry?;
+				sessions.push(session);
+			}
+			let children = reader.child_ids(id)?.collect::<Result<Vec<_>, _>>()?;
+			queue.extend(children.iter().copied());
+
+			tasks.insert(id, Task {
+				name: reader.name(id)?.unwrap_or_default().into(),
+				due_date: reader.due_date(id)?,
+				sessions,
+				children,
+			});
+		}
+		Ok(Snapshot { tasks })
+	}
+
+	/// Returns the ids of all tasks in breadth-first order from the root task, so that every
+	/// task comes after at least one of its parents.
+	pub fn bfs(&self) -> Vec<u64> {
+		let mut order = Vec::new();
+		let mut visited = HashSet::from([0]);
+		let mut queue = VecDeque::from([0]);
+		while let Some(id) = queue.pop_front() {
+			order.push(id);
+			for &child in &self.tasks[&id].children {
+				if visited.insert(child) { queue.push_back(child) };
+			}
+		}
+		order
+	}
+}
@@ -1,49 +1,68 @@
 use std::io;
-use crossterm::terminal;
+use chrono::Duration;
 use crossterm::event::{self, KeyCode, Event};
 use grus_lib::Store;
 use grus_lib::reader::StoreReader;
+use grus_lib::types::Session;
 use crate::app::{Action, Error, View};
-use crate::node::{wrap_text, Displayable};
+use crate::clock::Clock;
+use crate::history::{Checkpoint, History};
+use crate::keymap::{Keymap, SessionAction};
+use crate::meta::Meta;
+use crate::node::{wrap_text, Displayable, TimeFormat};
+use crate::recurrence::{Repeating, Rule};
 use crate::ui::{BufPrint, Screen, SessionViewMode};
+use crate::ui::backend::Backend;
 use crate::ui::session::{Item, SessionView};
 use crate::ui::status::{Mode, StatusView};
 
-pub struct SessionViewController {
+pub struct SessionViewController<'c> {
+	clock: &'c dyn Clock,
+	time_format: TimeFormat,
 	session_view: SessionView,
 	status_view: StatusView<{View::Session as usize}>,
+	keymap: Keymap<SessionAction>,
 }
 
-impl SessionViewController {
-	pub fn new(store: &Store) -> Result<Self, Error> {
+impl<'c> SessionViewController<'c> {
+	pub fn new(store: &Store, meta: &Meta, clock: &'c dyn Clock, time_format: TimeFormat, keymap: Keymap<SessionAction>, (w, h): (u16, u16)) -> Result<Self, Error> {
 		let mut svc = SessionViewController {
-			session_view: SessionView::new(Vec::new())?,
-			status_view: StatusView::new()?,
+			clock,
+			time_format,
+			session_view: SessionView::new(Vec::new(), w, h),
+			status_view: StatusView::new(w, h),
+			keymap,
 		};
-		svc.update_session_view(store)?;
+		svc.update_session_view(store, meta)?;
 		Ok(svc)
 	}
 
-	pub fn run(&mut self, store: &Store) -> Result<Action, Error> {
+	pub fn run(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<Action, Error> {
+		self.status_view.clear_message();
 		match event::read()? {
 			Event::Key(kev) => match self.status_view.mode {
-				Mode::Normal => match kev.code {
-					KeyCode::Char('q') => return Ok(Action::Quit),
-					KeyCode::Char('j') | KeyCode::Down => self.session_view.cursor_down(),
-					KeyCode::Char('k') | KeyCode::Up => self.session_view.cursor_up(),
-					KeyCode::Char('D') => self.delete(store)?,
-					KeyCode::Char('v') if self.session_view.constr.mode == SessionViewMode::Normal
-					=> if let Some((id, _)) = self.session_view.session_and_id() {
-						self.change_mode(store, SessionViewMode::Task(id))?;
+				Mode::Normal => match self.keymap.feed(kev) {
+					Some(SessionAction::Quit) => return Ok(Action::Quit),
+					Some(SessionAction::CursorDown) => self.session_view.cursor_down(),
+					Some(SessionAction::CursorUp) => self.session_view.cursor_up(),
+					Some(SessionAction::Delete) => self.delete(store, meta, history)?,
+					Some(SessionAction::ToggleMode) => match self.session_view.constr.mode {
+						SessionViewMode::Normal => if let Some((id, _)) = self.session_view.session_and_id() {
+							self.change_mode(store, meta, SessionViewMode::Task(id))?;
+						}
+						SessionViewMode::Task(_) => self.change_mode(store, meta, SessionViewMode::Normal)?,
 					}
-					KeyCode::Char('v') if self.session_view.constr.mode != SessionViewMode::Normal
-						=> self.change_mode(store, SessionViewMode::Normal)?,
-					KeyCode::Char('1') => return Ok(Action::Switch(View::Tree)),
-					_ => {},
+					Some(SessionAction::Undo) => return Ok(Action::Undo),
+					Some(SessionAction::Redo) => return Ok(Action::Redo),
+					Some(SessionAction::SwitchTree) => return Ok(Action::Switch(View::Tree)),
+					Some(SessionAction::SwitchAgenda) => return Ok(Action::Switch(View::Agenda)),
+					Some(SessionAction::SwitchWeek) => return Ok(Action::Switch(View::Week)),
+					Some(SessionAction::SwitchReport) => return Ok(Action::Switch(View::Report)),
+					None => {},
 				}
 				Mode::Command(_) => match kev.code {
 					KeyCode::Char(c) => self.status_view.insert(c),
-					KeyCode::Backspace => _ = self.status_view.delete(),
+					KeyCode::Backspace => self.status_view.delete(),
 					KeyCode::Left => self.status_view.move_left(),
 					KeyCode::Right => self.status_view.move_right(),
 					KeyCode::Esc => self.cancel(),
@@ -56,49 +75,61 @@ impl SessionViewController {
 		Ok(Action::None)
 	}
 
+	pub fn set_message(&mut self, message: String) {
+		self.status_view.set_message(message);
+	}
+
+	pub fn set_info(&mut self, info: String) {
+		self.status_view.set_info(info);
+	}
+
 	pub fn resize(&mut self, w: u16, h: u16) {
 		self.session_view.constr.update(w, h);
 		self.status_view.constr.update(w, h);
 		self.session_view.resize(self.session_view.constr.tasks_width(), self.session_view.constr.session_width());
 	}
 
-	pub fn update_session_view(&mut self, store: &Store) -> Result<(), Error> {
+	pub fn update_session_view(&mut self, store: &Store, meta: &Meta) -> Result<(), Error> {
 		let reader = SessionViewReader {
 			reader: store.reader()?,
+			clock: self.clock,
+			time_format: self.time_format,
 			tasks_width: self.session_view.constr.tasks_width(),
 			session_width: self.session_view.constr.session_width(),
 		};
 
 		match self.session_view.constr.mode {
 			SessionViewMode::Normal => {
-				let items = reader.get_items()?;
+				let items = reader.get_items(meta)?;
 				self.session_view.reset(items);
 			}
 			SessionViewMode::Task(id) => {
-				let items = reader.get_task_items(id)?;
+				let items = reader.get_task_items(id, meta)?;
 				self.session_view.reset(items);
 			}
 		}
 		Ok(())
 	}
 
-	pub fn change_mode(&mut self, store: &Store, mode: SessionViewMode) -> Result<(), Error> {
+	pub fn change_mode(&mut self, store: &Store, meta: &Meta, mode: SessionViewMode) -> Result<(), Error> {
+		let (w, h) = self.session_view.constr.size();
+		self.session_view = SessionView::new(Vec::new(), w, h);
 		self.session_view.constr.mode = mode;
-		let (w, h) = terminal::size()?;
 		self.session_view.constr.update(w, h);
-		self.status_view.constr.update(w, h);
-		self.session_view = SessionView::new(Vec::new())?;
-		self.update_session_view(store)
+		self.update_session_view(store, meta)
 	}
 
-	fn delete(&mut self, store: &Store) -> Result<(), Error> {
+	fn delete(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<(), Error> {
 		let Some((id, session)) = self.session_view.session_and_id() else { return Ok(()) };
+		if self.session_view.is_upcoming() { return Ok(()) };
 
+		let checkpoint = Checkpoint::read(store, meta)?;
 		let mut writer = store.writer()?;
 		writer.delete_session(id, session)?;
 		writer.commit()?;
+		history.record(checkpoint, store, meta)?;
 
-		self.update_session_view(store)?;
+		self.update_session_view(store, meta)?;
 		Ok(())
 	}
 
@@ -110,39 +141,76 @@ impl SessionViewController {
 
 struct SessionViewReader<'store> {
 	reader: StoreReader<'store>,
+	clock: &'store dyn Clock,
+	time_format: TimeFormat,
 	tasks_width: usize,
 	session_width: usize,
 }
 
+/// How far ahead the occurrences of repeating sessions are listed.
+const UPCOMING_WEEKS: i64 = 4;
+
 impl<'store> SessionViewReader<'store> {
-	fn get_items(&self) -> Result<Vec<Item>, Error> {
+	fn get_items(&self, meta: &Meta) -> Result<Vec<Item>, Error> {
 		let mut items = Vec::new();
 		for entry in self.reader.all_sessions()? {
 			let (&session, &id) = entry?;
 			let Some(name) = self.reader.name(id)? else { continue };
-			let name_splits = wrap_text(&name, self.tasks_width);
-			let session_text = format!("{}", Displayable(Some(session)));
-			let session_splits = wrap_text(&session_text, self.session_width);
-			items.push(Item { session, id, name: name.into(), name_splits, session_text, session_splits });
+			items.push(self.item(id, name, session, rule(meta, id, session), false));
 		}
+		for (id, repeating) in meta.repeating() {
+			let Some(name) = self.reader.name(id)? else { continue };
+			self.add_upcoming(&mut items, id, name, repeating);
+		}
+		items.sort();
 		Ok(items)
 	}
 
-	fn get_task_items(&self, id: u64) -> Result<Vec<Item>, Error> {
+	fn get_task_items(&self, id: u64, meta: &Meta) -> Result<Vec<Item>, Error> {
 		let mut items = Vec::new();
+		let Some(name) = self.reader.name(id)? else { return Ok(items) };
 		for entry in self.reader.sessions(id)? {
 			let (_, &session) = entry?;
-			let Some(name) = self.reader.name(id)? else { continue };
-			let name_splits = wrap_text(&name, self.tasks_width);
-			let session_text = format!("{}", Displayable(Some(session)));
-			let session_splits = wrap_text(&session_text, self.session_width);
-			items.push(Item { session, id, name: name.into(), name_splits, session_text, session_splits });
+			items.push(self.item(id, name, session, rule(meta, id, session), false));
 		}
+		if let Some(task) = meta.task(id) {
+			self.add_upcoming(&mut items, id, name, &task.repeating);
+		}
+		items.sort();
 		Ok(items)
 	}
+
+	/// Lists the occurrences of the next weeks which follow the repeating sessions of a task,
+	/// unless the latest occurrence was deleted.
+	fn add_upcoming(&self, items: &mut Vec<Item>, id: u64, name: &str, repeating: &[Repeating]) {
+		let until = self.clock.now() + Duration::weeks(UPCOMING_WEEKS);
+		for latest in repeating {
+			if !items.iter().any(|item| item.id == id && item.session == latest.session()) { continue };
+			for session in latest.upcoming(until) {
+				items.push(self.item(id, name, session, Some(&latest.rule), true));
+			}
+		}
+	}
+
+	/// The latest and upcoming occurrences of a repeating session are followed by its rule.
+	fn item(&self, id: u64, name: &str, session: Session, rule: Option<&Rule>, upcoming: bool) -> Item {
+		let name_splits = wrap_text(name, self.tasks_width);
+		let session_text = match rule {
+			Some(rule) => format!("{}, {}", Displayable(Some(session), self.clock, self.time_format), rule),
+			None => format!("{}", Displayable(Some(session), self.clock, self.time_format)),
+		};
+		let session_splits = wrap_text(&session_text, self.session_width);
+		Item { session, id, name: name.into(), name_splits, session_text, session_splits, upcoming }
+	}
+}
+
+/// Returns the rule of the repetition whose latest occurrence is the given session.
+fn rule(meta: &Meta, id: u64, session: Session) -> Option<&Rule> {
+	let task = meta.task(id)?;
+	task.repeating.iter().find(|latest| latest.session() == session).map(|latest| &latest.rule)
 }
 
-impl BufPrint<SessionViewController> for Screen {
+impl<B: Backend> BufPrint<SessionViewController<'_>> for Screen<B> {
 	fn bufprint(&mut self, svc: &SessionViewController) -> io::Result<&mut Self> {
 		self
 			.clear()?
@@ -0,0 +1,482 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::ErrorKind;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use chrono::NaiveDateTime;
+use grus_lib::Store;
+use grus_lib::writer::StoreWriter;
+use serde::{Deserialize, Serialize};
+use crate::app::{Error, StoreArgs};
+use crate::snapshot::Snapshot;
+
+/// Task ids are only unique within a single store, so every synced task is given a uid which
+/// is shared by all stores that it is imported into, and which keys its state. The state maps
+/// the uid to the id of the task in this store, and also remembers the name and due date of the
+/// task as of the last sync, which serves as the base of a three-way merge.
+#[derive(Default, Deserialize, Serialize)]
+struct SyncState {
+	#[serde(default)]
+	uids: BTreeMap<String, TaskState>,
+	/// The states as they were written before they were keyed by uid, which are moved into
+	/// `uids` when loaded.
+	#[serde(default, skip_serializing)]
+	tasks: BTreeMap<u64, IdState>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct TaskState {
+	id: u64,
+	base: Option<Base>,
+}
+
+#[derive(Deserialize)]
+struct IdState {
+	uid: String,
+	base: Option<Base>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Base {
+	name: String,
+	due_date: Option<NaiveDateTime>,
+}
+
+impl SyncState {
+	fn load(path: &Path) -> Result<Self, Error> {
+		let mut state: SyncState = match fs::read(path) {
+			Ok(bytes) => serde_json::from_slice(&bytes)?,
+			Err(e) if e.kind() == ErrorKind::NotFound => SyncState::default(),
+			Err(e) => return Err(e.into()),
+		};
+		for (id, IdState { uid, base }) in mem::take(&mut state.tasks) {
+			state.uids.entry(uid).or_insert(TaskState { id, base });
+		}
+		Ok(state)
+	}
+
+	fn save(&self, path: &Path) -> Result<(), Error> {
+		fs::write(path, serde_json::to_vec(self)?)?;
+		Ok(())
+	}
+
+	/// Maps the ids of the tasks in the store to their uids.
+	fn ids(&self) -> HashMap<u64, &str> {
+		self.uids.iter().map(|(uid, state)| (state.id, uid.as_str())).collect()
+	}
+
+	/// Moves the states of tasks which were created again over to their new ids. Returns whether
+	/// any state was moved.
+	fn remap(&mut self, ids: &HashMap<u64, u64>) -> bool {
+		let mut moved = false;
+		for state in self.uids.values_mut() {
+			if let Some(&id) = ids.get(&state.id) {
+				state.id = id;
+				moved = true;
+			}
+		}
+		moved
+	}
+
+	fn get_mut(&mut self, id: u64) -> Option<&mut TaskState> {
+		self.uids.values_mut().find(|state| state.id == id)
+	}
+
+	/// Forgets deleted tasks and gives a uid to every task which does not have one yet.
+	fn track(&mut self, snapshot: &Snapshot) {
+		self.uids.retain(|_, state| snapshot.tasks.contains_key(&state.id));
+		let tracked: HashSet<u64> = self.uids.values().map(|state| state.id).collect();
+		for &id in snapshot.tasks.keys().filter(|id| !tracked.contains(id)) {
+			let uid = if id == 0 { "root".into() } else { new_uid() };
+			self.uids.insert(uid, TaskState { id, base: None });
+		}
+	}
+
+	fn rebase(&mut self, snapshot: &Snapshot, except: &HashSet<u64>) {
+		for state in self.uids.values_mut() {
+			if except.contains(&state.id) { continue };
+			if let Some(task) = snapshot.tasks.get(&state.id) {
+				state.base = Some(Base { name: task.name.clone(), due_date: task.due_date });
+			}
+		}
+	}
+}
+
+pub struct Conflict {
+	pub id: u64,
+	pub name: String,
+	pub kind: ConflictKind,
+}
+
+pub enum ConflictKind {
+	Name { local: String, foreign: String },
+	DueDate { local: Option<NaiveDateTime>, foreign: Option<NaiveDateTime> },
+}
+
+/// Copies the store to the export path along with the uids of its tasks.
+pub fn export(store: &Store, args: &StoreArgs) -> Result<(), Error> {
+	let local = Snapshot::read(&store.reader()?)?;
+	let path = state_path(&args.path);
+	let mut state = SyncState::load(&path)?;
+	state.track(&local);
+	state.rebase(&local, &HashSet::new());
+
+	fs::copy(&args.path, &args.export_path)?;
+	state.save(&state_path(&args.export_path))?;
+	state.save(&path)
+}
+
+/// Merges the store at the export path into the local store. Tasks, links and sessions are
+/// united. Names and due dates changed on only one side since the last sync take the changed
+/// value, while those changed differently on both sides keep the local value and are returned
+/// as conflicts.
+pub fn import(store: &Store, args: &StoreArgs) -> Result<Vec<Conflict>, Error> {
+	let foreign = read_foreign(args)?;
+	let foreign_state = SyncState::load(&state_path(&args.export_path))?;
+	let path = state_path(&args.path);
+	let mut state = SyncState::load(&path)?;
+	let local = Snapshot::read(&store.reader()?)?;
+	state.track(&local);
+
+	let foreign_uids = foreign_state.ids();
+	let map = map_ids(&local, &state, &foreign, &foreign_uids);
+	let mut merge = Merge { local: &local, map, links: HashSet::new(), created: HashSet::new() };
+
+	let mut writer = store.writer()?;
+	for fpid in foreign.bfs() {
+		let pid = merge.map[&fpid];
+		for &fid in foreign.tasks[&fpid].children.iter().rev() {
+			match merge.map.get(&fid) {
+				Some(&id) if merge.is_linked(pid, id) => {},
+				Some(&id) => if writer.share(id, pid)? { merge.links.insert((pid, id)); },
+				None => {
+					let task = &foreign.tasks[&fid];
+					let id = writer.add_child(pid, &task.name)?;
+					if let Some(due_date) = task.due_date { writer.set_due_date(id, due_date)? };
+					for session in &task.sessions {
+						writer.add_session(id, session)?;
+					}
+
+					let uid = foreign_uids.get(&fid)
+						.filter(|uid| !state.uids.contains_key(**uid))
+						.map_or_else(new_uid, |uid| uid.to_string());
+					state.uids.insert(uid, TaskState { id, base: None });
+					merge.map.insert(fid, id);
+					merge.links.insert((pid, id));
+					merge.created.insert(id);
+				}
+			}
+		}
+	}
+
+	let bases: HashMap<u64, &Base> = state.uids.values()
+		.filter_map(|state| Some((state.id, state.base.as_ref()?)))
+		.collect();
+	let mut conflicts = Vec::new();
+	for (fid, ftask) in &foreign.tasks {
+		let id = merge.map[fid];
+		if id == 0 || merge.created.contains(&id) { continue };
+		let task = &local.tasks[&id];
+		let base = bases.get(&id).copied();
+
+		match merge3(base.map(|base| &base.name), &task.name, &ftask.name) {
+			Merged::Local => {},
+			Merged::Foreign => writer.rename(id, &ftask.name)?,
+			Merged::Conflict => conflicts.push(Conflict {
+				id,
+				name: task.name.clone(),
+				kind: ConflictKind::Name { local: task.name.clone(), foreign: ftask.name.clone() },
+			}),
+		}
+
+		match merge3(base.map(|base| &base.due_date), &task.due_date, &ftask.due_date) {
+			Merged::Local => {},
+			Merged::Foreign => set_due_date(&mut writer, id, ftask.due_date)?,
+			Merged::Conflict => conflicts.push(Conflict {
+				id,
+				name: task.name.clone(),
+				kind: ConflictKind::DueDate { local: task.due_date, foreign: ftask.due_date },
+			}),
+		}
+
+		for session in &ftask.sessions {
+			if !task.sessions.contains(session) {
+				writer.add_session(id, session)?;
+			}
+		}
+	}
+	writer.commit()?;
+
+	let merged = Snapshot::read(&store.reader()?)?;
+	state.track(&merged);
+	state.rebase(&merged, &conflicts.iter().map(|conflict| conflict.id).collect());
+	state.save(&path)?;
+	Ok(conflicts)
+}
+
+/// Settles a conflict by either applying the foreign value or keeping the local one. In both
+/// cases the foreign value becomes the base, so that the next import does not report the
+/// conflict again.
+pub fn resolve(store: &Store, args: &StoreArgs, conflict: &Conflict, take_foreign: bool) -> Result<(), Error> {
+	if take_foreign {
+		let mut writer = store.writer()?;
+		match &conflict.kind {
+			ConflictKind::Name { foreign, .. } => writer.rename(conflict.id, foreign)?,
+			ConflictKind::DueDate { foreign, .. } => set_due_date(&mut writer, conflict.id, *foreign)?,
+		}
+		writer.commit()?;
+	}
+
+	let path = state_path(&args.path);
+	let mut state = SyncState::load(&path)?;
+	let Some(task_state) = state.get_mut(conflict.id) else { return Ok(()) };
+	let base = match task_state.base.take() {
+		Some(base) => base,
+		None => {
+			let reader = store.reader()?;
+			let Some(name) = reader.name(conflict.id)? else { return Ok(()) };
+			Base { name: name.into(), due_date: reader.due_date(conflict.id)? }
+		}
+	};
+	let base = task_state.base.insert(base);
+	match &conflict.kind {
+		ConflictKind::Name { foreign, .. } => base.name = foreign.clone(),
+		ConflictKind::DueDate { foreign, .. } => base.due_date = *foreign,
+	}
+	state.save(&path)
+}
+
+/// Keeps the uids of tasks which were deleted and created again under new ids, as undoing does,
+/// so that the next export and import treat them as the same tasks.
+pub fn remap(args: &StoreArgs, ids: &HashMap<u64, u64>) -> Result<(), Error> {
+	if ids.is_empty() { return Ok(()) };
+	let path = state_path(&args.path);
+	let mut state = SyncState::load(&path)?;
+	if state.remap(ids) { state.save(&path)? };
+	Ok(())
+}
+
+struct Merge<'a> {
+	local: &'a Snapshot,
+	map: HashMap<u64, u64>,
+	links: HashSet<(u64, u64)>,
+	created: HashSet<u64>,
+}
+
+impl<'a> Merge<'a> {
+	fn is_linked(&self, pid: u64, id: u64) -> bool {
+		self.links.contains(&(pid, id))
+			|| self.local.tasks.get(&pid).is_some_and(|task| task.children.contains(&id))
+	}
+}
+
+/// Maps the ids of foreign tasks to the ids of the same tasks in the local store. Foreign stores
+/// exported before uids were introduced fall back to matching tasks by id and name.
+fn map_ids(local: &Snapshot, state: &SyncState, foreign: &Snapshot, foreign_uids: &HashMap<u64, &str>) -> HashMap<u64, u64> {
+	let mut map = HashMap::from([(0, 0)]);
+	let mut claimed = HashSet::from([0]);
+	for (&fid, ftask) in &foreign.tasks {
+		let id = match foreign_uids.get(&fid) {
+			Some(&uid) => state.uids.get(uid).map(|state| state.id),
+			None => local.tasks.get(&fid).filter(|task| task.name == ftask.name).map(|_| fid),
+		};
+		if let Some(id) = id.filter(|&id| claimed.insert(id)) {
+			map.insert(fid, id);
+		}
+	}
+	map
+}
+
+enum Merged {
+	Local,
+	Foreign,
+	Conflict,
+}
+
+fn merge3<T: PartialEq>(base: Option<&T>, local: &T, foreign: &T) -> Merged {
+	if local == foreign || base == Some(foreign) {
+		Merged::Local
+	} else if base == Some(local) {
+		Merged::Foreign
+	} else {
+		Merged::Conflict
+	}
+}
+
+fn set_due_date(writer: &mut StoreWriter, id: u64, due_date: Option<NaiveDateTime>) -> Result<(), Error> {
+	match due_date {
+		Some(due_date) => writer.set_due_date(id, due_date)?,
+		None => writer.unset_due_date(id)?,
+	}
+	Ok(())
+}
+
+/// Opens a copy of the exported store, so that no lock files are left in the export directory.
+fn read_foreign(args: &StoreArgs) -> Result<Snapshot, Error> {
+	let path = with_suffix(&args.path, ".import");
+	fs::copy(&args.export_path, &path)?;
+	let snapshot = read_store(&path, args.n_roots);
+
+	_ = fs::remove_file(&path);
+	for i in 0..args.n_roots {
+		_ = fs::remove_file(with_suffix(&path, &format!(".lock{}", i)));
+	}
+	Ok(snapshot?)
+}
+
+fn read_store(path: &Path, n_roots: usize) -> Result<Snapshot, sanakirja::Error> {
+	let store = Store::open(path, n_roots)?;
+	let reader = store.reader()?;
+	Snapshot::read(&reader)
+}
+
+fn state_path(path: &Path) -> PathBuf {
+	with_suffix(path, ".sync")
+}
+
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+	let mut path = OsString::from(path);
+	path.push(suffix);
+	path.into()
+}
+
+fn new_uid() -> String {
+	let mut hasher = RandomState::new().build_hasher();
+	hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos());
+	format!("{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::NaiveDate;
+	use crate::app::{Error, StoreArgs};
+	use crate::history::{Checkpoint, History};
+	use crate::meta::Meta;
+	use crate::snapshot::Snapshot;
+	use crate::testing::TempDir;
+	use super::{export, import, remap, resolve, ConflictKind, SyncState};
+
+	fn child_names(snapshot: &Snapshot, id: u64) -> Vec<&str> {
+		snapshot.tasks[&id].children.iter().map(|id| snapshot.tasks[id].name.as_str()).collect()
+	}
+
+	#[test]
+	fn merge_stores() -> Result<(), Error> {
+		let dir = TempDir::new();
+		let laptop = StoreArgs {
+			n_roots: 2,
+			path: dir.join("laptop"),
+			export_path: dir.join("export"),
+			calendar_path: dir.join("laptop.ics"),
+		};
+		let desktop = StoreArgs {
+			n_roots: 2,
+			path: dir.join("desktop"),
+			export_path: dir.join("export"),
+			calendar_path: dir.join("desktop.ics"),
+		};
+		let laptop_store = laptop.open()?;
+		let desktop_store = desktop.open()?;
+
+		let mut writer = laptop_store.writer()?;
+		let home = writer.add_child(0, "home")?;
+		let work = writer.add_child(0, "work")?;
+		let report = writer.add_child(work, "report")?;
+		writer.commit()?;
+		export(&laptop_store, &laptop)?;
+		assert!(import(&desktop_store, &desktop)?.is_empty());
+
+		let snapshot = Snapshot::read(&desktop_store.reader()?)?;
+		assert_eq!(child_names(&snapshot, 0), ["work", "home"]);
+
+		let due_date = NaiveDate::from_ymd_opt(2023, 7, 1).unwrap().and_hms_opt(17, 0, 0).unwrap();
+		let mut writer = laptop_store.writer()?;
+		writer.add_child(work, "slides")?;
+		writer.rename(home, "house")?;
+		writer.set_due_date(report, due_date)?;
+		writer.commit()?;
+
+		let ids: Vec<_> = desktop_store.reader()?.child_ids(0)?.collect::<Result<_, _>>()?;
+		let mut writer = desktop_store.writer()?;
+		writer.add_child(ids[1], "groceries")?;
+		writer.rename(ids[0], "job")?;
+		writer.rename(ids[1], "cottage")?;
+		writer.commit()?;
+		export(&desktop_store, &desktop)?;
+
+		let conflicts = import(&laptop_store, &laptop)?;
+		assert_eq!(conflicts.len(), 1);
+		assert_eq!(conflicts[0].id, home);
+		let ConflictKind::Name { local, foreign } = &conflicts[0].kind else { panic!() };
+		assert_eq!((local.as_str(), foreign.as_str()), ("house", "cottage"));
+
+		let snapshot = Snapshot::read(&laptop_store.reader()?)?;
+		assert_eq!(child_names(&snapshot, 0), ["job", "house"]);
+		assert_eq!(child_names(&snapshot, work), ["slides", "report"]);
+		assert_eq!(child_names(&snapshot, home), ["groceries"]);
+		assert_eq!(snapshot.tasks[&report].due_date, Some(due_date));
+
+		assert_eq!(import(&laptop_store, &laptop)?.len(), 1);
+		resolve(&laptop_store, &laptop, &conflicts[0], false)?;
+		assert!(import(&laptop_store, &laptop)?.is_empty());
+		assert_eq!(Snapshot::read(&laptop_store.reader()?)?.tasks[&home].name, "house");
+		Ok(())
+	}
+
+	#[test]
+	fn undo_delete() -> Result<(), Error> {
+		let dir = TempDir::new();
+		let laptop = StoreArgs {
+			n_roots: 2,
+			path: dir.join("laptop"),
+			export_path: dir.join("export"),
+			calendar_path: dir.join("laptop.ics"),
+		};
+		let desktop = StoreArgs {
+			n_roots: 2,
+			path: dir.join("desktop"),
+			export_path: dir.join("export"),
+			calendar_path: dir.join("desktop.ics"),
+		};
+		let laptop_store = laptop.open()?;
+		let desktop_store = desktop.open()?;
+		let mut meta = Meta::default();
+		let mut history = History::default();
+
+		let mut writer = laptop_store.writer()?;
+		let home = writer.add_child(0, "home")?;
+		writer.commit()?;
+		export(&laptop_store, &laptop)?;
+		import(&desktop_store, &desktop)?;
+
+		let checkpoint = Checkpoint::read(&laptop_store, &meta)?;
+		let mut writer = laptop_store.writer()?;
+		writer.delete(0, home)?;
+		writer.commit()?;
+		history.record(checkpoint, &laptop_store, &meta)?;
+		let created = history.undo(&laptop_store, &mut meta)?.unwrap();
+		remap(&laptop, &created)?;
+
+		export(&laptop_store, &laptop)?;
+		assert!(import(&desktop_store, &desktop)?.is_empty());
+		assert_eq!(child_names(&Snapshot::read(&desktop_store.reader()?)?, 0), ["home"]);
+		Ok(())
+	}
+
+	#[test]
+	fn legacy_state() -> Result<(), Error> {
+		let dir = TempDir::new();
+		let path = dir.join("tasks.sync");
+		std::fs::write(&path, r#"{"tasks":{"0":{"uid":"root","base":null},"5":{"uid":"abc","base":{"name":"home","due_date":null}}}}"#)?;
+		let state = SyncState::load(&path)?;
+		assert!(state.tasks.is_empty());
+		assert_eq!(state.uids["abc"].id, 5);
+		assert_eq!(state.uids["abc"].base.as_ref().map(|base| base.name.as_str()), Some("home"));
+		assert_eq!(state.ids()[&0], "root");
+		Ok(())
+	}
+}
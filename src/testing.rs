@@ -0,0 +1,42 @@
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A fresh directory for the files of a test, which is removed when the guard is dropped, also
+/// when the test panics. No other test, in this process or another, is handed the same one.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+	pub fn new() -> Self {
+		static COUNT: AtomicUsize = AtomicUsize::new(0);
+		let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.subsec_nanos());
+		loop {
+			let count = COUNT.fetch_add(1, Ordering::Relaxed);
+			let dir = env::temp_dir().join(format!("grus-{}-{}-{}", process::id(), nanos, count));
+			match fs::create_dir(&dir) {
+				Ok(()) => return TempDir(dir),
+				Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+				Err(e) => panic!("cannot create {}: {}", dir.display(), e),
+			}
+		}
+	}
+}
+
+impl Deref for TempDir {
+	type Target = Path;
+
+	fn deref(&self) -> &Path {
+		&self.0
+	}
+}
+
+impl Drop for TempDir {
+	fn drop(&mut self) {
+		_ = fs::remove_dir_all(&self.0);
+	}
+}
@@ -1,103 +1,189 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::io;
+use std::path::Path;
+use chrono::NaiveDateTime;
 use crossterm::event::{self, KeyCode, Event};
 use grus_lib::Store;
+use grus_lib::types::Session;
 use grus_lib::reader::StoreReader;
 use crate::app::{Action, Error, View};
+use crate::clock::Clock;
+use crate::config::{expand_tilde, Config};
 use crate::flattree::{FlatTreeBuilder, FlatTreeState};
-use crate::node::{Displayable, Node, Priority, wrap_text};
-use crate::parser::{parse_datetime, parse_session};
+use crate::history::{Checkpoint, History};
+use crate::ical::{self, Target};
+use crate::keymap::{Keymap, TreeAction};
+use crate::meta::Meta;
+use crate::node::{Displayable, Node, Priority, TimeFormat, wrap_text};
+use crate::outline;
+use crate::parser::{describe, parse_repeating_datetime, parse_repeating_session};
+use crate::recurrence::{self, Repeating};
+use crate::score::Urgency;
+use crate::search::Index;
+use crate::snapshot::Snapshot;
 use crate::ui::{BufPrint, Screen};
+use crate::ui::backend::Backend;
+use crate::ui::search::SearchView;
 use crate::ui::tree::TreeView;
 use crate::ui::status::{CommandType, Mode, StatusView};
 
-pub struct TreeViewController {
+pub struct TreeViewController<'c> {
+	clock: &'c dyn Clock,
 	tree_view: TreeView,
+	search_view: SearchView,
+	index: Option<Index>,
 	status_view: StatusView<{View::Tree as usize}>,
+	keymap: Keymap<TreeAction>,
+	show_done: bool,
+	sort_by_score: bool,
+	/// Whether timers may run on several tasks at once.
+	multiple_timers: bool,
+	time_format: TimeFormat,
 }
 
-impl TreeViewController {
-	pub fn new(store: &Store) -> Result<Self, Error> {
+impl<'c> TreeViewController<'c> {
+	pub fn new(store: &Store, meta: &Meta, clock: &'c dyn Clock, keymap: Keymap<TreeAction>, config: &Config, (w, h): (u16, u16)) -> Result<Self, Error> {
 		let mut tvc = TreeViewController {
-			tree_view: TreeView::new(Vec::new())?,
-			status_view: StatusView::new()?,
+			clock,
+			tree_view: TreeView::new(Vec::new(), w, h),
+			search_view: SearchView::new(w, h),
+			index: None,
+			status_view: StatusView::new(w, h),
+			keymap,
+			show_done: config.show_done,
+			sort_by_score: config.sort_by_score,
+			multiple_timers: config.multiple_timers,
+			time_format: config.time_format,
 		};
-		tvc.update_tree_view(store)?;
+		tvc.update_tree_view(store, meta)?;
 		Ok(tvc)
 	}
 
-	pub fn run(&mut self, store: &Store) -> Result<Action, Error> {
+	pub fn run(&mut self, store: &Store, meta: &mut Meta, history: &mut History) -> Result<Action, Error> {
+		self.status_view.clear_message();
 		match event::read()? {
 			Event::Key(kev) => match self.status_view.mode {
-				Mode::Normal => match kev.code {
-					KeyCode::Char('q') => return Ok(Action::Quit),
-					KeyCode::Char('j') | KeyCode::Down => self.tree_view.cursor_down(),
-					KeyCode::Char('k') | KeyCode::Up => self.tree_view.cursor_up(),
-					KeyCode::Char('h') | KeyCode::Left => self.move_out(store)?,
-					KeyCode::Char('l') | KeyCode::Right => self.move_into(store)?,
-					KeyCode::Char(' ') => self.tree_view.toggle(),
-					KeyCode::Char('.') => self.share(store)?,
-					KeyCode::Char('x') => self.cut(store)?,
-					KeyCode::Char('a') => self.enter_command_mode(CommandType::AddChild),
-					KeyCode::Char('r') => self.enter_command_mode(CommandType::Rename),
-					KeyCode::Char('z') => self.enter_command_mode(CommandType::SetDueDate),
-					KeyCode::Char('s') => self.enter_command_mode(CommandType::AddSession),
-					KeyCode::Char('Z') => self.unset_due_date(store)?,
-					KeyCode::Char('K') => self.priority_up(store)?,
-					KeyCode::Char('J') => self.priority_down(store)?,
-					KeyCode::Char('D') => self.delete(store)?,
-					KeyCode::Char('v') => if let Some(node) = self.tree_view.cursor_node() {
+				Mode::Normal => match self.keymap.feed(kev) {
+					Some(TreeAction::Quit) => return Ok(Action::Quit),
+					Some(TreeAction::CursorDown) => self.tree_view.cursor_down(),
+					Some(TreeAction::CursorUp) => self.tree_view.cursor_up(),
+					Some(TreeAction::MoveOut) => self.move_out(store, meta)?,
+					Some(TreeAction::MoveInto) => self.move_into(store, meta)?,
+					Some(TreeAction::Select) => self.tree_view.toggle(),
+					Some(TreeAction::Share) => self.share(store, meta, history)?,
+					Some(TreeAction::Cut) => self.cut(store, meta, history)?,
+					Some(TreeAction::AddChild) => self.enter_command_mode(CommandType::AddChild),
+					Some(TreeAction::Rename) => self.enter_rename_mode(store)?,
+					Some(TreeAction::SetDueDate) => self.enter_command_mode(CommandType::SetDueDate),
+					Some(TreeAction::AddSession) => self.enter_command_mode(CommandType::AddSession),
+					Some(TreeAction::UnsetDueDate) => self.unset_due_date(store, meta, history)?,
+					Some(TreeAction::PriorityUp) => self.priority_up(store, meta, history)?,
+					Some(TreeAction::PriorityDown) => self.priority_down(store, meta, history)?,
+					Some(TreeAction::Delete) => self.delete(store, meta, history)?,
+					Some(TreeAction::ToggleDone) => self.toggle_done(store, meta, history)?,
+					Some(TreeAction::Search) => self.enter_search(store)?,
+					Some(TreeAction::StartTimer) => self.start_timer(store, meta)?,
+					Some(TreeAction::StopTimer) => self.stop_timer(store, meta, history)?,
+					Some(TreeAction::Undo) => return Ok(Action::Undo),
+					Some(TreeAction::Redo) => return Ok(Action::Redo),
+					Some(TreeAction::ToggleShowDone) => {
+						self.show_done = !self.show_done;
+						self.update_tree_view(store, meta)?;
+					}
+					Some(TreeAction::ToggleSortByScore) => {
+						self.sort_by_score = !self.sort_by_score;
+						self.update_tree_view(store, meta)?;
+					}
+					Some(TreeAction::ViewSessions) => if let Some(node) = self.tree_view.cursor_node() {
 						return Ok(Action::TaskSessions(node.id));
 					},
-					KeyCode::Char('2') => return Ok(Action::Switch(View::Session)),
-					KeyCode::Char('I') => return Ok(Action::Import),
-					KeyCode::Char('E') => return Ok(Action::Export),
-					_ => {},
+					Some(TreeAction::SwitchSession) => return Ok(Action::Switch(View::Session)),
+					Some(TreeAction::SwitchAgenda) => return Ok(Action::Switch(View::Agenda)),
+					Some(TreeAction::SwitchWeek) => return Ok(Action::Switch(View::Week)),
+					Some(TreeAction::SwitchReport) => return Ok(Action::Switch(View::Report)),
+					Some(TreeAction::Import) => return Ok(Action::Import),
+					Some(TreeAction::Export) => return Ok(Action::Export),
+					Some(TreeAction::ExportCalendar) => return Ok(Action::ExportCalendar),
+					Some(TreeAction::ImportCalendarSessions) => self.enter_command_mode(CommandType::ImportSessions),
+					Some(TreeAction::ImportCalendarTasks) => self.enter_command_mode(CommandType::ImportTasks),
+					Some(TreeAction::ExportOutline) => self.enter_command_mode(CommandType::ExportOutline),
+					Some(TreeAction::ImportOutline) => self.enter_command_mode(CommandType::ImportOutline),
+					None => {},
 				}
-				Mode::Command(cmd) => match kev.code {
-					KeyCode::Enter => {
-						match cmd {
-							CommandType::AddChild => self.add_child(store)?,
-							CommandType::Rename => self.rename(store)?,
-							CommandType::SetDueDate => self.set_due_date(store)?,
-							CommandType::AddSession => self.add_session(store)?,
+				Mode::Command(cmd) => {
+					match kev.code {
+						KeyCode::Enter => {
+							match cmd {
+								CommandType::AddChild => self.add_child(store, meta, history)?,
+								CommandType::Rename => self.rename(store, meta, history)?,
+								CommandType::SetDueDate => self.set_due_date(store, meta, history)?,
+								CommandType::AddSession => self.add_session(store, meta, history)?,
+								CommandType::ImportSessions => return Ok(self.import_calendar(Target::Task)),
+								CommandType::ImportTasks => return Ok(self.import_calendar(Target::Children)),
+								CommandType::ExportOutline => self.export_outline(store)?,
+								CommandType::ImportOutline => self.import_outline(store, meta, history)?,
+								CommandType::Search => self.jump(store, meta)?,
+							}
 						}
+						KeyCode::Char(c) => self.status_view.insert(c),
+						KeyCode::Backspace => self.status_view.delete(),
+						KeyCode::Left => self.status_view.move_left(),
+						KeyCode::Right => self.status_view.move_right(),
+						KeyCode::Up => self.search_view.cursor_up(),
+						KeyCode::Down => self.search_view.cursor_down(),
+						KeyCode::Esc => self.cancel(),
+						_ => {},
+					}
+					if let (CommandType::Search, KeyCode::Char(_) | KeyCode::Backspace) = (cmd, kev.code) {
+						self.rank_search_results();
 					}
-					KeyCode::Char(c) => self.status_view.insert(c),
-					KeyCode::Backspace => _ = self.status_view.delete(),
-					KeyCode::Left => self.status_view.move_left(),
-					KeyCode::Right => self.status_view.move_right(),
-					KeyCode::Esc => self.cancel(),
-					_ => {},
 				}
 			}
-			Event::Resize(w, h) => self.resize(store, w, h)?,
+			Event::Resize(w, h) => self.resize(store, meta, w, h)?,
 			_ => {},
 		}
 		Ok(Action::None)
 	}
 
-	pub fn resize(&mut self, store: &Store, w: u16, h: u16) -> Result<(), Error> {
+	pub fn set_message(&mut self, message: String) {
+		self.status_view.set_message(message);
+	}
+
+	pub fn set_info(&mut self, info: String) {
+		self.status_view.set_info(info);
+	}
+
+	pub fn resize(&mut self, store: &Store, meta: &Meta, w: u16, h: u16) -> Result<(), Error> {
 		self.tree_view.constr.update(w, h);
+		self.search_view.constr.update(w, h);
 		self.status_view.constr.update(w, h);
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
 		Ok(())
 	}
 
 	fn enter_command_mode(&mut self, cmd: CommandType) {
-		let Some(node) = self.tree_view.cursor_node() else { return };
-
+		if self.tree_view.cursor_node().is_none() { return };
 		self.status_view.mode = Mode::Command(cmd);
-		match cmd {
-			CommandType::Rename => self.status_view.set_input(&node.name),
-			_ => {},
-		};
 	}
 
-	fn add_child(&mut self, store: &Store) -> Result<(), Error> {
+	/// Puts the current name of the task in the prompt. The name is read from the store, since
+	/// the name of the node may be followed by the completion ratio.
+	fn enter_rename_mode(&mut self, store: &Store) -> Result<(), Error> {
+		let Some(node) = self.tree_view.cursor_node() else { return Ok(()) };
+		let reader = store.reader()?;
+		let Some(name) = reader.name(node.id)? else { return Ok(()) };
+
+		self.status_view.mode = Mode::Command(CommandType::Rename);
+		self.status_view.set_input(name);
+		Ok(())
+	}
+
+	fn add_child(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<(), Error> {
 		let mut selections = self.tree_view.selection_ids();
 		let Some(&first) = selections.next() else { return Ok(()) };
 
+		let checkpoint = Checkpoint::read(store, meta)?;
 		let mut writer = store.writer()?;
 		let id = writer.add_child(first, self.status_view.input())?;
 
@@ -105,159 +191,393 @@ impl TreeViewController {
 			writer.share(id, pid)?;
 		}
 		writer.commit()?;
+		history.record(checkpoint, store, meta)?;
 
 		self.tree_view.clear_selections();
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
 
 		self.cancel();
 		Ok(())
 	}
 
-	fn delete(&mut self, store: &Store) -> Result<(), Error> {
+	fn delete(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<(), Error> {
 		let Some(node) = self.tree_view.cursor_node() else { return Ok(()) };
 		if self.tree_view.is_cursor_at_root() { return Ok(()) }
 
+		let checkpoint = Checkpoint::read(store, meta)?;
 		let mut writer = store.writer()?;
 		writer.delete(node.pid, node.id)?;
 		writer.commit()?;
+		history.record(checkpoint, store, meta)?;
 
 		self.tree_view.deselect();
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
 		Ok(())
 	}
 
-	fn rename(&mut self, store: &Store) -> Result<(), Error> {
+	/// Marks the selected tasks as done, or as not done if they all are done already.
+	fn toggle_done(&mut self, store: &Store, meta: &mut Meta, history: &mut History) -> Result<(), Error> {
+		let ids: Vec<u64> = self.tree_view.selection_ids().copied().filter(|&id| id != 0).collect();
+		if ids.is_empty() { return Ok(()) };
+
+		let checkpoint = Checkpoint::read(store, meta)?;
+		let undo = ids.iter().all(|&id| meta.done(id).is_some());
+		let now = self.clock.now();
+		let mut writer = store.writer()?;
+		for id in ids {
+			if undo {
+				meta.unmark_done(id);
+			} else if !recurrence::complete(&mut writer, meta, id, now)? {
+				meta.mark_done(id, now);
+			}
+		}
+		writer.commit()?;
+		meta.save()?;
+		history.record(checkpoint, store, meta)?;
+
+		self.tree_view.clear_selections();
+		self.update_tree_view(store, meta)?;
+		Ok(())
+	}
+
+	/// Starts a timer on the task under the cursor. Unless several timers are allowed, refuses
+	/// while the timer of another task is running.
+	fn start_timer(&mut self, store: &Store, meta: &mut Meta) -> Result<(), Error> {
+		let Some(id) = self.tree_view.cursor_node().map(|node| node.id) else { return Ok(()) };
+		if id == 0 { return Ok(()) };
+
+		if !self.multiple_timers {
+			let reader = store.reader()?;
+			for (other, _) in meta.timers().collect::<Vec<_>>() {
+				if other == id { continue };
+				match reader.name(other)? {
+					Some(name) => {
+						self.status_view.set_message(format!("A timer is already running on {}", name));
+						return Ok(());
+					}
+					None => { meta.stop_timer(other); }
+				}
+			}
+		}
+		meta.start_timer(id, self.clock.now());
+		meta.save()?;
+		self.update_tree_view(store, meta)?;
+		Ok(())
+	}
+
+	/// Stops the timer of the task under the cursor, or the only running timer, and records the
+	/// time since it was started as a session of its task.
+	fn stop_timer(&mut self, store: &Store, meta: &mut Meta, history: &mut History) -> Result<(), Error> {
+		let timers: Vec<u64> = meta.timers().map(|(id, _)| id).collect();
+		let id = match self.tree_view.cursor_node().map(|node| node.id) {
+			Some(id) if timers.contains(&id) => id,
+			_ if timers.len() == 1 => timers[0],
+			_ => {
+				self.status_view.set_message(if timers.is_empty() { "No timer is running" } else { "No timer is running on this task" });
+				return Ok(());
+			}
+		};
+
+		let checkpoint = Checkpoint::read(store, meta)?;
+		let Some(start) = meta.stop_timer(id) else { return Ok(()) };
+		let end = self.clock.now();
+		let mut writer = store.writer()?;
+		if end > start && writer.name(id)?.is_some() {
+			writer.add_session(id, &Session { start, end })?;
+		}
+		writer.commit()?;
+		meta.save()?;
+		history.record(checkpoint, store, meta)?;
+
+		self.update_tree_view(store, meta)?;
+		Ok(())
+	}
+
+	fn rename(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<(), Error> {
+		let checkpoint = Checkpoint::read(store, meta)?;
 		let mut writer = store.writer()?;
 		let name = self.status_view.input();
 		for &id in self.tree_view.selection_ids() {
 			writer.rename(id, name)?;
 		}
 		writer.commit()?;
+		history.record(checkpoint, store, meta)?;
 
 		self.tree_view.clear_selections();
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
 
 		self.cancel();
 		Ok(())
 	}
 
-	fn set_due_date(&mut self, store: &Store) -> Result<(), Error> {
-		let mut writer = store.writer()?;
-		let Ok(due_date) = parse_datetime(self.status_view.input()) else {
-			return Ok(());
+	fn set_due_date(&mut self, store: &Store, meta: &mut Meta, history: &mut History) -> Result<(), Error> {
+		let (due_date, rule) = match parse_repeating_datetime(self.status_view.input(), self.clock).map_err(|e| describe(&e)) {
+			Ok(parsed) => parsed,
+			Err(message) => {
+				self.status_view.set_message(message);
+				return Ok(());
+			}
 		};
+		let checkpoint = Checkpoint::read(store, meta)?;
+		let mut writer = store.writer()?;
 		for &id in self.tree_view.selection_ids() {
 			writer.set_due_date(id, due_date)?;
+			meta.set_repeat(id, rule.clone());
 		}
 		writer.commit()?;
+		meta.save()?;
+		history.record(checkpoint, store, meta)?;
 
 		self.tree_view.clear_selections();
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
 
 		self.cancel();
 		Ok(())
 	}
 
-	fn unset_due_date(&mut self, store: &Store) -> Result<(), Error> {
+	fn unset_due_date(&mut self, store: &Store, meta: &mut Meta, history: &mut History) -> Result<(), Error> {
+		let checkpoint = Checkpoint::read(store, meta)?;
 		let mut writer = store.writer()?;
 		for &id in self.tree_view.selection_ids() {
 			writer.unset_due_date(id)?;
+			meta.set_repeat(id, None);
 		}
 		writer.commit()?;
+		meta.save()?;
+		history.record(checkpoint, store, meta)?;
 
 		self.tree_view.clear_selections();
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
 		Ok(())
 	}
 
-	fn add_session(&mut self, store: &Store) -> Result<(), Error> {
+	fn add_session(&mut self, store: &Store, meta: &mut Meta, history: &mut History) -> Result<(), Error> {
 		let Some(node) = self.tree_view.cursor_node() else { return Ok(()) };
+		let id = node.id;
 
+		let (session, rule) = match parse_repeating_session(self.status_view.input(), self.clock).map_err(|e| describe(&e)) {
+			Ok(parsed) => parsed,
+			Err(message) => {
+				self.status_view.set_message(message);
+				return Ok(());
+			}
+		};
+		let checkpoint = Checkpoint::read(store, meta)?;
 		let mut writer = store.writer()?;
+		writer.add_session(id, &session)?;
+		writer.commit()?;
+		if let Some(rule) = rule {
+			meta.add_repeating(id, Repeating { start: session.start, end: session.end, rule });
+			meta.save()?;
+		}
+		history.record(checkpoint, store, meta)?;
 
-		let Ok(session) = parse_session(&self.status_view.input()) else { return Ok(()) };
-		writer.add_session(node.id, &session)?;
+		self.update_tree_view(store, meta)?;
+		self.cancel();
+		Ok(())
+	}
+
+	/// Reads the calendar at the path in the prompt. The events are handed to the application,
+	/// which keeps track of the events imported before.
+	fn import_calendar(&mut self, target: fn(u64) -> Target) -> Action {
+		let Some(node) = self.tree_view.cursor_node() else { return Action::None };
+		let id = node.id;
+		let events = expand_tilde(Path::new(self.status_view.input())).map_err(|e| e.to_string())
+			.and_then(|path| fs::read_to_string(path).map_err(|e| e.to_string()))
+			.and_then(|ics| ical::parse(&ics).map_err(|e| e.to_string()));
+		let events = match events {
+			Ok(events) => events,
+			Err(message) => {
+				self.status_view.set_message(message);
+				return Action::None;
+			}
+		};
+
+		self.cancel();
+		Action::ImportCalendar(events, target(id))
+	}
+
+	fn export_outline(&mut self, store: &Store) -> Result<(), Error> {
+		let Some(node) = self.tree_view.cursor_node() else { return Ok(()) };
+		let text = outline::render(&store.reader()?, node.id)?;
+		let written = expand_tilde(Path::new(self.status_view.input())).map_err(|e| e.to_string())
+			.and_then(|path| fs::write(path, text).map_err(|e| e.to_string()));
+		if let Err(message) = written {
+			self.status_view.set_message(message);
+			return Ok(());
+		}
+
+		self.cancel();
+		Ok(())
+	}
+
+	fn import_outline(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<(), Error> {
+		let Some(node) = self.tree_view.cursor_node() else { return Ok(()) };
+		let id = node.id;
+		let items = expand_tilde(Path::new(self.status_view.input())).map_err(|e| e.to_string())
+			.and_then(|path| fs::read_to_string(path).map_err(|e| e.to_string()))
+			.and_then(|text| outline::parse(&text).map_err(|e| e.to_string()));
+		let items = match items {
+			Ok(items) => items,
+			Err(message) => {
+				self.status_view.set_message(message);
+				return Ok(());
+			}
+		};
+
+		let checkpoint = Checkpoint::read(store, meta)?;
+		let mut writer = store.writer()?;
+		// The writer is dropped without committing, so a failed import changes nothing.
+		if let Err(e) = outline::write(&items, &mut writer, id) {
+			self.status_view.set_message(e.to_string());
+			return Ok(());
+		}
 		writer.commit()?;
+		history.record(checkpoint, store, meta)?;
 
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
 		self.cancel();
 		Ok(())
 	}
 
-	fn priority_up(&mut self, store: &Store) -> Result<(), Error> {
+	fn priority_up(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<(), Error> {
 		let Some(node) = self.tree_view.cursor_node() else { return Ok(()) };
 
 		if self.tree_view.is_cursor_at_root() { return Ok(()) };
 
+		let checkpoint = Checkpoint::read(store, meta)?;
 		let mut writer = store.writer()?;
 		writer.move_up(node.pid, node.id)?;
 		writer.commit()?;
+		history.record(checkpoint, store, meta)?;
 
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
 		Ok(())
 	}
 
-	fn priority_down(&mut self, store: &Store) -> Result<(), Error> {
+	fn priority_down(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<(), Error> {
 		let Some(node) = self.tree_view.cursor_node() else { return Ok(()) };
 
 		if self.tree_view.is_cursor_at_root() { return Ok(()) };
 
+		let checkpoint = Checkpoint::read(store, meta)?;
 		let mut writer = store.writer()?;
 		writer.move_down(node.pid, node.id)?;
 		writer.commit()?;
+		history.record(checkpoint, store, meta)?;
 
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
 		Ok(())
 	}
 
-	fn share(&mut self, store: &Store) -> Result<(), Error> {
+	fn share(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<(), Error> {
 		let Some(node) = self.tree_view.cursor_node() else { return Ok(()) };
 
+		let checkpoint = Checkpoint::read(store, meta)?;
 		let mut writer = store.writer()?;
 		for &id in self.tree_view.selection_ids() {
 			if !writer.share(id, node.id)? { return Ok(()) };
 		}
 		writer.commit()?;
+		history.record(checkpoint, store, meta)?;
 
 		self.tree_view.clear_selections();
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
 		Ok(())
 	}
 
-	fn cut(&mut self, store: &Store) -> Result<(), Error> {
+	fn cut(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<(), Error> {
 		let Some(node) = self.tree_view.cursor_node() else { return Ok(()) };
 
+		let checkpoint = Checkpoint::read(store, meta)?;
 		let mut writer = store.writer()?;
 		for (&pid, &id) in self.tree_view.selections() {
 			if !writer.cut(pid, id, node.id)? { return Ok(()) };
 		}
 		writer.commit()?;
+		history.record(checkpoint, store, meta)?;
 
 		self.tree_view.clear_selections();
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
 		Ok(())
 	}
 
-	fn move_into(&mut self, store: &Store) -> Result<(), Error> {
+	fn move_into(&mut self, store: &Store, meta: &Meta) -> Result<(), Error> {
 		self.tree_view.move_into();
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
 		Ok(())
 	}
 
-	fn move_out(&mut self, store: &Store) -> Result<(), Error> {
+	fn move_out(&mut self, store: &Store, meta: &Meta) -> Result<(), Error> {
 		self.tree_view.move_out();
-		self.update_tree_view(store)?;
+		self.update_tree_view(store, meta)?;
+		Ok(())
+	}
+
+	/// Reads the names of all tasks, which are matched against the prompt as it is typed.
+	fn enter_search(&mut self, store: &Store) -> Result<(), Error> {
+		self.index = Some(Index::new(&Snapshot::read(&store.reader()?)?));
+		self.search_view.reset(Vec::new());
+		self.status_view.mode = Mode::Command(CommandType::Search);
+		Ok(())
+	}
+
+	fn rank_search_results(&mut self) {
+		let Some(index) = &self.index else { return };
+		self.search_view.reset(index.items(self.status_view.input()));
+	}
+
+	fn jump(&mut self, store: &Store, meta: &Meta) -> Result<(), Error> {
+		let Some(path) = self.search_view.cursor_path().map(<[u64]>::to_vec) else { return Ok(()) };
+		self.show_task(store, meta, &path)?;
+		self.cancel();
+		Ok(())
+	}
+
+	/// Shows the task at the end of a path of ids from the root task under its parent, moving
+	/// out to the root task along the path. A task which is not shown under its parent, because
+	/// it is done or there is not enough room, is made the root task instead.
+	pub fn show_task(&mut self, store: &Store, meta: &Meta, path: &[u64]) -> Result<(), Error> {
+		let [.., pid, id] = *path else { return Ok(()) };
+
+		self.tree_view.clear_selections();
+		self.tree_view.show_path(path);
+		self.update_tree_view(store, meta)?;
+		if !self.tree_view.focus(pid, id) {
+			self.tree_view.enter(pid, id);
+			self.update_tree_view(store, meta)?;
+			self.tree_view.focus(pid, id);
+		}
 		Ok(())
 	}
 
 	fn cancel(&mut self) {
+		self.index = None;
 		self.status_view.clear();
 		self.status_view.mode = Mode::Normal;
 	}
 
-	fn update_tree_view(&mut self, store: &Store) -> Result<(), Error> {
+	pub fn clear_selections(&mut self) {
+		self.tree_view.clear_selections();
+	}
+
+	/// Rebuilds the tree. If the root task was deleted, which undoing an operation can do,
+	/// moves out until the root task exists.
+	fn update_tree_view(&mut self, store: &Store, meta: &Meta) -> Result<(), Error> {
+		let reader = store.reader()?;
+		while reader.name(self.tree_view.root_id())?.is_none() {
+			self.tree_view.move_out();
+		}
+		let now = self.clock.now();
+		let parents = if self.sort_by_score { Some(count_parents(&reader)?) } else { None };
 		let flattree = TreeViewReader {
-			reader: store.reader()?,
+			reader,
+			meta,
+			show_done: self.show_done,
+			parents,
+			clock: self.clock,
+			time_format: self.time_format,
+			now,
 			height: self.tree_view.constr.tree_height(),
 			tasks_width: self.tree_view.constr.tree_width(),
 			session_width: self.tree_view.constr.session_width(),
@@ -270,6 +590,14 @@ impl TreeViewController {
 
 struct TreeViewReader<'store> {
 	reader: StoreReader<'store>,
+	meta: &'store Meta,
+	show_done: bool,
+	/// The number of parents of every task, counted only when sorting by score.
+	parents: Option<HashMap<u64, usize>>,
+	clock: &'store dyn Clock,
+	time_format: TimeFormat,
+	/// The time the scores are computed at, read from the clock once for the whole tree.
+	now: NaiveDateTime,
 	height: usize,
 	tasks_width: usize,
 	session_width: usize,
@@ -282,7 +610,7 @@ impl<'store> TreeViewReader<'store> {
 
 		let root = self.get_node(pid, id, 0)?;
 		if root.name_splits.len() - 1 > self.height { return Ok(Vec::new()) };
-		let mut builder = FlatTreeBuilder::new(root, self.height);
+		let mut builder = FlatTreeBuilder::new(root, self.height).by_score(self.parents.is_some());
 		let mut ids = HashSet::new();
 		loop {
 			match builder.step() {
@@ -305,36 +633,65 @@ impl<'store> TreeViewReader<'store> {
 		depth += 1;
 		let mut children = Vec::new();
 		for id in self.reader.child_ids(pid)? {
+			let id = id?;
 			if 2 * depth + 1 >= self.tasks_width { continue };
-			children.push(self.get_node(pid, id?, depth)?);
+			if !self.show_done && self.meta.done(id).is_some() { continue };
+			children.push(self.get_node(pid, id, depth)?);
 		}
 		for i in 0..children.len() {
 			children[i].priority = Priority {
 				det: i as u64,
 				total: children.len() as u64,
 			};
+			if let Some(parents) = &self.parents {
+				children[i].score = self.urgency(&children[i], parents)?.score(self.now);
+			}
 		}
 		Ok(children)
 	}
 
+	fn urgency(&self, node: &Node, parents: &HashMap<u64, usize>) -> Result<Urgency, Error> {
+		let mut next_session = None;
+		for entry in self.reader.sessions(node.id)? {
+			let (_, &session) = entry?;
+			if session.end >= self.now { next_session = Some(session); break };
+		}
+		Ok(Urgency {
+			priority: node.priority,
+			due_date: node.due_date,
+			next_session,
+			parents: parents.get(&node.id).copied().unwrap_or(1),
+		})
+	}
+
+	/// Builds the node of a task. The name of a task with completed children is followed by the
+	/// number of completed children out of all of them.
 	fn get_node(&self, pid: u64, id: u64, depth: usize) -> Result<Node<'static>, Error> {
-		let name = self.reader.name(id)?.unwrap().to_string();
+		let mut name = self.reader.name(id)?.unwrap().to_string();
+		let (mut total, mut done) = (0, 0);
+		for child in self.reader.child_ids(id)? {
+			total += 1;
+			if self.meta.done(child?).is_some() { done += 1 };
+		}
+		if done > 0 { name += &format!(" [{}/{}]", done, total) };
 		let due_date = self.reader.due_date(id)?;
 		let width = self.tasks_width - 2 * depth - 1;
 		let name_splits = wrap_text(&name, width);
 		let session = self.reader.first_session(id)?;
-		let session_text = format!("{}", Displayable(session));
+		let session_text = format!("{}", Displayable(session, self.clock, self.time_format));
 		let session_splits = wrap_text(&session_text, self.session_width);
-		let due_date_text = format!("{}", Displayable(due_date));
+		let due_date_text = format!("{}", Displayable(due_date, self.clock, self.time_format));
 		let due_date_splits = wrap_text(&due_date_text, self.due_date_width);
 		Ok(Node {
 			id,
 			pid,
 			depth,
 			name: name.into(),
+			done: self.meta.done(id).is_some(),
 			due_date,
 			session,
 			priority: Priority::default(),
+			score: 0.0,
 			name_splits,
 			session_text,
 			session_splits,
@@ -344,13 +701,31 @@ impl<'store> TreeViewReader<'store> {
 	}
 }
 
-impl BufPrint<TreeViewController> for Screen {
+/// Counts the parents of every task by walking the whole store, since the store only lists the
+/// children of a task.
+fn count_parents(reader: &StoreReader) -> Result<HashMap<u64, usize>, Error> {
+	let mut parents = HashMap::new();
+	let mut visited = HashSet::from([0]);
+	let mut stack = vec![0];
+	while let Some(id) = stack.pop() {
+		for child in reader.child_ids(id)? {
+			let child = child?;
+			*parents.entry(child).or_default() += 1;
+			if visited.insert(child) { stack.push(child) };
+		}
+	}
+	Ok(parents)
+}
+
+impl<B: Backend> BufPrint<TreeViewController<'_>> for Screen<B> {
 	fn bufprint(&mut self, tvc: &TreeViewController) -> io::Result<&mut Self> {
-		self
-			.clear()?
-			.bufprint(&tvc.status_view)?
-			.bufprint(&tvc.tree_view)?
-			.flush()?;
+		self.clear()?.bufprint(&tvc.status_view)?;
+		if let Mode::Command(CommandType::Search) = tvc.status_view.mode {
+			self.bufprint(&tvc.search_view)?;
+		} else {
+			self.bufprint(&tvc.tree_view)?;
+		}
+		self.flush()?;
 		Ok(self)
 	}
 }
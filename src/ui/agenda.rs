@@ -0,0 +1,146 @@
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use crossterm::style::{Attribute, Color, Colors, ResetColor, SetAttribute, SetColors, SetForegroundColor};
+use super::{AgendaViewConstraints, Backend, BufPrint, Rect, Screen};
+
+/// Tasks with a due date, one per line, under a heading for each group of due dates.
+pub struct AgendaView {
+	rows: Vec<Row>,
+	cursor: usize,
+	start: usize,
+	pub constr: AgendaViewConstraints,
+}
+
+enum Row {
+	Heading(Group),
+	Item(Item),
+}
+
+pub struct Item {
+	pub id: u64,
+	pub group: Group,
+	pub name: String,
+	pub location: String,
+	pub due_date_text: String,
+	pub path: Vec<u64>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Group {
+	Overdue,
+	Today,
+	Tomorrow,
+	ThisWeek,
+	Later,
+}
+
+impl Display for Group {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "{}", match self {
+			Group::Overdue => "Overdue",
+			Group::Today => "Today",
+			Group::Tomorrow => "Tomorrow",
+			Group::ThisWeek => "This week",
+			Group::Later => "Later",
+		})
+	}
+}
+
+impl AgendaView {
+	pub fn new(w: u16, h: u16) -> Self {
+		AgendaView { rows: Vec::new(), cursor: 0, start: 0, constr: AgendaViewConstraints::new(w, h) }
+	}
+
+	/// Replaces the items, which are sorted by group. The cursor stays on the same task if it
+	/// is still listed, or on the same line otherwise.
+	pub fn reset(&mut self, items: Vec<Item>) {
+		let id = self.item().map(|item| item.id);
+		self.rows.clear();
+		for item in items {
+			if !matches!(self.rows.last(), Some(Row::Item(last)) if last.group == item.group) {
+				self.rows.push(Row::Heading(item.group));
+			}
+			self.rows.push(Row::Item(item));
+		}
+
+		let same = self.rows.iter().position(|row| matches!(row, Row::Item(item) if Some(item.id) == id));
+		self.cursor = same.unwrap_or(self.cursor).min(self.rows.len().saturating_sub(1));
+		if let Some(Row::Heading(_)) = self.rows.get(self.cursor) { self.cursor += 1 };
+		self.start = self.start.min(self.cursor.saturating_sub(1));
+		self.scroll();
+	}
+
+	pub fn cursor_up(&mut self) {
+		let Some(i) = (0..self.cursor).rev().find(|&i| matches!(self.rows[i], Row::Item(_))) else { return };
+		self.cursor = i;
+		self.scroll();
+	}
+
+	pub fn cursor_down(&mut self) {
+		let Some(i) = (self.cursor + 1..self.rows.len()).find(|&i| matches!(self.rows[i], Row::Item(_))) else { return };
+		self.cursor = i;
+		self.scroll();
+	}
+
+	pub fn item(&self) -> Option<&Item> {
+		match self.rows.get(self.cursor) {
+			Some(Row::Item(item)) => Some(item),
+			_ => None,
+		}
+	}
+
+	/// Keeps the cursor on screen, along with the heading right above it.
+	fn scroll(&mut self) {
+		let h = usize::from(self.constr.task.h);
+		if h == 0 { return };
+		let top = match self.cursor.checked_sub(1).map(|i| &self.rows[i]) {
+			Some(Row::Heading(_)) => self.cursor - 1,
+			_ => self.cursor,
+		};
+		if top < self.start { self.start = top };
+		if self.cursor >= self.start + h { self.start = self.cursor + 1 - h };
+	}
+}
+
+impl<B: Backend> BufPrint<AgendaView> for Screen<B> {
+	fn bufprint(&mut self, view: &AgendaView) -> io::Result<&mut Self> {
+		let constr = &view.constr;
+		let end = (view.start + usize::from(constr.task.h)).min(view.rows.len());
+		for (i, row) in view.rows[view.start..end].iter().enumerate() {
+			let dy = i as u16;
+			let item = match row {
+				Row::Heading(group) => {
+					self.buffer.queue(SetAttribute(Attribute::Bold))?;
+					if *group == Group::Overdue { self.buffer.queue(SetForegroundColor(Color::Red))?; }
+					self.print_clipped(&constr.due_date, dy, &group.to_string())?;
+					self.buffer.queue(SetAttribute(Attribute::Reset))?.queue(ResetColor)?;
+					continue;
+				}
+				Row::Item(item) => item,
+			};
+
+			let colors = if view.start + i == view.cursor {
+				let area = Rect {
+					x: constr.due_date.x,
+					y: constr.due_date.y + dy,
+					w: constr.location.x + constr.location.w - constr.due_date.x,
+					h: 1,
+				};
+				self.paint(area, Colors::new(Color::Black, Color::White))?;
+				Colors::new(Color::Black, Color::White)
+			} else {
+				Colors { foreground: Some(Color::White), background: None }
+			};
+
+			self.buffer.queue(SetColors(colors))?;
+			self.print_clipped(&constr.due_date, dy, &format!("  {}", item.due_date_text))?;
+			self.print_clipped(&constr.task, dy, &item.name)?;
+			if view.start + i != view.cursor {
+				self.buffer.queue(SetColors(Colors { foreground: Some(Color::DarkGrey), background: None }))?;
+			}
+			self.print_clipped(&constr.location, dy, &item.location)?;
+			self.buffer.queue(ResetColor)?;
+		}
+		Ok(self)
+	}
+}
@@ -0,0 +1,122 @@
+use std::io::{self, stdout, Stdout, Write};
+use crossterm::{terminal, QueueableCommand};
+use crossterm::cursor::MoveTo;
+use crossterm::style::{Attribute, Print, SetAttribute, SetAttributes, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{Clear, ClearType};
+use unicode_width::UnicodeWidthChar;
+use super::buffer::{Buffer, Cell, Style};
+
+/// Where the screen sends its frames: the terminal, or memory when views are rendered without
+/// one, as in tests.
+pub trait Backend {
+	fn size(&self) -> io::Result<(u16, u16)>;
+
+	/// Blanks the whole display, before a frame of a new size is drawn.
+	fn clear(&mut self) -> io::Result<()>;
+
+	/// Writes the cells, which come row by row, each with its position.
+	fn draw<'a>(&mut self, cells: impl Iterator<Item = (u16, u16, &'a Cell)>) -> io::Result<()>;
+}
+
+pub struct CrosstermBackend {
+	stdout: Stdout,
+}
+
+impl CrosstermBackend {
+	pub fn new() -> Self {
+		CrosstermBackend { stdout: stdout() }
+	}
+}
+
+impl Default for CrosstermBackend {
+	fn default() -> Self {
+		CrosstermBackend::new()
+	}
+}
+
+impl Backend for CrosstermBackend {
+	fn size(&self) -> io::Result<(u16, u16)> {
+		terminal::size()
+	}
+
+	fn clear(&mut self) -> io::Result<()> {
+		self.stdout.queue(SetAttribute(Attribute::Reset))?.queue(Clear(ClearType::All))?;
+		Ok(())
+	}
+
+	/// Moves the cursor and changes the style only where needed. The second cells of wide
+	/// characters are skipped, as the terminal draws over them with the first.
+	fn draw<'a>(&mut self, cells: impl Iterator<Item = (u16, u16, &'a Cell)>) -> io::Result<()> {
+		let (w, _) = self.size()?;
+		let mut cursor = None;
+		let mut style = Style::default();
+		self.stdout.queue(SetAttribute(Attribute::Reset))?;
+		for (x, y, cell) in cells {
+			if cell.is_continuation() { continue };
+			if cursor != Some((x, y)) { self.stdout.queue(MoveTo(x, y))?; }
+			if cell.style != style {
+				self.stdout.queue(SetAttribute(Attribute::Reset))?;
+				if !cell.style.attributes.is_empty() { self.stdout.queue(SetAttributes(cell.style.attributes))?; }
+				if let Some(fg) = cell.style.fg { self.stdout.queue(SetForegroundColor(fg))?; }
+				if let Some(bg) = cell.style.bg { self.stdout.queue(SetBackgroundColor(bg))?; }
+				style = cell.style;
+			}
+			self.stdout.queue(Print(cell.ch))?;
+			let x = x + cell.ch.width().unwrap_or(1) as u16;
+			cursor = (x < w).then_some((x, y));
+		}
+		self.stdout.queue(SetAttribute(Attribute::Reset))?;
+		self.stdout.flush()
+	}
+}
+
+/// Keeps what is drawn in a grid of cells of a fixed size, which can be read back.
+pub struct MemoryBackend {
+	cells: Buffer,
+}
+
+impl MemoryBackend {
+	pub fn new(w: u16, h: u16) -> Self {
+		MemoryBackend { cells: Buffer::new(w, h) }
+	}
+
+	/// Changes the size, as a resized terminal would, and blanks the grid.
+	pub fn resize(&mut self, w: u16, h: u16) {
+		self.cells = Buffer::new(w, h);
+	}
+
+	pub fn cell(&self, x: u16, y: u16) -> Option<&Cell> {
+		self.cells.get(x, y)
+	}
+
+	/// Returns the characters of each row, without the blanks at their ends.
+	pub fn lines(&self) -> Vec<String> {
+		let (w, h) = self.cells.size();
+		(0..h).map(|y| {
+			let line: String = (0..w).filter_map(|x| self.cells.get(x, y))
+				.filter(|cell| !cell.is_continuation())
+				.map(|cell| cell.ch)
+				.collect();
+			line.trim_end().to_string()
+		}).collect()
+	}
+}
+
+impl Backend for MemoryBackend {
+	fn size(&self) -> io::Result<(u16, u16)> {
+		Ok(self.cells.size())
+	}
+
+	fn clear(&mut self) -> io::Result<()> {
+		let (w, h) = self.cells.size();
+		self.cells.clear(w, h);
+		Ok(())
+	}
+
+	fn draw<'a>(&mut self, cells: impl Iterator<Item = (u16, u16, &'a Cell)>) -> io::Result<()> {
+		for (x, y, &cell) in cells {
+			self.cells.set(x, y, cell);
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,189 @@
+use std::fmt::Display;
+use std::io;
+use crossterm::cursor::MoveTo;
+use crossterm::style::{
+	Attribute, Attributes, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetColors,
+	SetForegroundColor,
+};
+use unicode_width::UnicodeWidthChar;
+use super::Rect;
+
+/// The contents of the screen as a grid of cells, which views draw into with the same commands
+/// they would send to the terminal. The screen compares it with the previous frame and sends
+/// only the cells which changed.
+#[derive(Clone, Default)]
+pub struct Buffer {
+	w: u16,
+	h: u16,
+	cells: Vec<Cell>,
+	x: u16,
+	y: u16,
+	style: Style,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct Cell {
+	/// The character, or `'\0'` in the second column of a wide character, which the terminal
+	/// draws over both columns.
+	pub ch: char,
+	pub style: Style,
+}
+
+/// Colors of `None` are the default colors of the terminal.
+#[derive(Copy, Clone, Default, PartialEq)]
+pub struct Style {
+	pub fg: Option<Color>,
+	pub bg: Option<Color>,
+	pub attributes: Attributes,
+}
+
+impl Default for Cell {
+	fn default() -> Self {
+		Cell { ch: ' ', style: Style::default() }
+	}
+}
+
+impl Cell {
+	/// Whether the cell is covered by the wide character to its left.
+	pub fn is_continuation(&self) -> bool {
+		self.ch == '\0'
+	}
+}
+
+impl Buffer {
+	pub fn new(w: u16, h: u16) -> Self {
+		Buffer { w, h, cells: vec![Cell::default(); usize::from(w) * usize::from(h)], ..Buffer::default() }
+	}
+
+	pub fn size(&self) -> (u16, u16) {
+		(self.w, self.h)
+	}
+
+	/// Blanks every cell, resizing the buffer if the terminal was resized.
+	pub fn clear(&mut self, w: u16, h: u16) {
+		if (w, h) == (self.w, self.h) {
+			self.cells.fill(Cell::default());
+			self.x = 0;
+			self.y = 0;
+			self.style = Style::default();
+		} else {
+			*self = Buffer::new(w, h);
+		}
+	}
+
+	/// Returns the cells along with their positions, row by row.
+	pub fn cells(&self) -> impl Iterator<Item = (u16, u16, &Cell)> {
+		let w = usize::from(self.w.max(1));
+		self.cells.iter().enumerate().map(move |(i, cell)| ((i % w) as u16, (i / w) as u16, cell))
+	}
+
+	pub fn get(&self, x: u16, y: u16) -> Option<&Cell> {
+		if x >= self.w || y >= self.h { return None };
+		self.cells.get(usize::from(y) * usize::from(self.w) + usize::from(x))
+	}
+
+	/// Sets a cell, ignoring positions outside the buffer.
+	pub fn set(&mut self, x: u16, y: u16, cell: Cell) {
+		if x >= self.w || y >= self.h { return };
+		self.cells[usize::from(y) * usize::from(self.w) + usize::from(x)] = cell;
+	}
+
+	pub fn queue(&mut self, command: impl Draw) -> io::Result<&mut Self> {
+		command.draw(self);
+		Ok(self)
+	}
+
+	/// Fills the area with blank cells of the given style.
+	pub fn fill(&mut self, area: Rect, style: Style) {
+		for y in area.y..(area.y + area.h).min(self.h) {
+			for x in area.x..(area.x + area.w).min(self.w) {
+				self.put(x, y, Cell { ch: ' ', style });
+			}
+		}
+	}
+
+	/// Writes the text from the cursor on in the current style. As on a terminal, text which
+	/// reaches the right edge continues on the next row, and text below the last row is lost.
+	/// Wide characters take two cells and move to the next row if only one is left, and
+	/// characters without a width are left out.
+	fn print(&mut self, text: &str) {
+		for ch in text.chars().filter(|ch| !ch.is_control()) {
+			let width = ch.width().unwrap_or(0) as u16;
+			if width == 0 || width > self.w { continue };
+			if self.x + width > self.w {
+				self.x = 0;
+				self.y = self.y.saturating_add(1);
+			}
+			if self.y >= self.h { return };
+			self.put(self.x, self.y, Cell { ch, style: self.style });
+			if width == 2 { self.put(self.x + 1, self.y, Cell { ch: '\0', style: self.style }) };
+			self.x += width;
+		}
+	}
+
+	/// Sets a cell, blanking the other half of a wide character it overwrites half of, as the
+	/// terminal would.
+	fn put(&mut self, x: u16, y: u16, cell: Cell) {
+		let i = usize::from(y) * usize::from(self.w) + usize::from(x);
+		if self.cells[i].is_continuation() && x > 0 {
+			self.cells[i - 1].ch = ' ';
+		} else if x + 1 < self.w && self.cells[i + 1].is_continuation() {
+			self.cells[i + 1].ch = ' ';
+		}
+		self.cells[i] = cell;
+	}
+}
+
+/// A terminal command which can be drawn into a buffer instead.
+pub trait Draw {
+	fn draw(self, buffer: &mut Buffer);
+}
+
+impl Draw for MoveTo {
+	fn draw(self, buffer: &mut Buffer) {
+		buffer.x = self.0;
+		buffer.y = self.1;
+	}
+}
+
+impl<T: Display> Draw for Print<T> {
+	fn draw(self, buffer: &mut Buffer) {
+		buffer.print(&self.0.to_string());
+	}
+}
+
+impl Draw for SetColors {
+	fn draw(self, buffer: &mut Buffer) {
+		if let Some(fg) = self.0.foreground { SetForegroundColor(fg).draw(buffer) };
+		if let Some(bg) = self.0.background { SetBackgroundColor(bg).draw(buffer) };
+	}
+}
+
+impl Draw for SetForegroundColor {
+	fn draw(self, buffer: &mut Buffer) {
+		buffer.style.fg = Some(self.0).filter(|&color| color != Color::Reset);
+	}
+}
+
+impl Draw for SetBackgroundColor {
+	fn draw(self, buffer: &mut Buffer) {
+		buffer.style.bg = Some(self.0).filter(|&color| color != Color::Reset);
+	}
+}
+
+/// Resetting the attributes resets the colors as well, as it does on the terminal.
+impl Draw for SetAttribute {
+	fn draw(self, buffer: &mut Buffer) {
+		match self.0 {
+			Attribute::Reset => buffer.style = Style::default(),
+			attribute => buffer.style.attributes.set(attribute),
+		}
+	}
+}
+
+/// Resetting the colors resets the attributes as well, as it does on the terminal.
+impl Draw for ResetColor {
+	fn draw(self, buffer: &mut Buffer) {
+		buffer.style = Style::default();
+	}
+}
@@ -0,0 +1,115 @@
+use std::io;
+use chrono::NaiveDateTime;
+use crossterm::cursor::MoveTo;
+use crossterm::style::{Attribute, Color, Colors, Print, ResetColor, SetAttribute, SetColors};
+use crate::clock::Clock;
+use crate::node::{Displayable, TimeFormat};
+use crate::sync::{Conflict, ConflictKind};
+use super::{clip, Backend, BufPrint, ConflictViewConstraints, Rect, Screen};
+
+pub struct ConflictView<'c> {
+	conflicts: Vec<Conflict>,
+	clock: &'c dyn Clock,
+	time_format: TimeFormat,
+	cursor: usize,
+	start: usize,
+	pub constr: ConflictViewConstraints,
+}
+
+impl<'c> ConflictView<'c> {
+	pub fn new(conflicts: Vec<Conflict>, clock: &'c dyn Clock, time_format: TimeFormat, w: u16, h: u16) -> Self {
+		ConflictView { conflicts, clock, time_format, cursor: 0, start: 0, constr: ConflictViewConstraints::new(w, h) }
+	}
+
+	pub fn reset(&mut self, conflicts: Vec<Conflict>) {
+		self.conflicts = conflicts;
+		self.cursor = 0;
+		self.start = 0;
+	}
+
+	pub fn cursor_up(&mut self) {
+		if self.cursor == 0 { return };
+		self.cursor -= 1;
+		if self.cursor < self.start { self.start = self.cursor };
+	}
+
+	pub fn cursor_down(&mut self) {
+		if self.cursor + 1 >= self.conflicts.len() { return };
+		self.cursor += 1;
+		let h = usize::from(self.constr.task.h);
+		if self.cursor >= self.start + h { self.start = self.cursor + 1 - h };
+	}
+
+	/// Removes the conflict under the cursor from the list.
+	pub fn take(&mut self) -> Option<Conflict> {
+		if self.conflicts.is_empty() { return None };
+		let conflict = self.conflicts.remove(self.cursor);
+		if self.cursor == self.conflicts.len() { self.cursor_up() };
+		Some(conflict)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.conflicts.is_empty()
+	}
+}
+
+impl<B: Backend> BufPrint<ConflictView<'_>> for Screen<B> {
+	fn bufprint(&mut self, view: &ConflictView) -> io::Result<&mut Self> {
+		let constr = &view.constr;
+		self.buffer.queue(SetAttribute(Attribute::Bold))?;
+		self.print_cell(&constr.task, 0, "Task")?;
+		self.print_cell(&constr.field, 0, "Field")?;
+		self.print_cell(&constr.local, 0, "Local")?;
+		self.print_cell(&constr.foreign, 0, "Imported")?;
+		self.buffer.queue(SetAttribute(Attribute::Reset))?;
+
+		let end = (view.start + usize::from(constr.task.h)).min(view.conflicts.len());
+		for (i, conflict) in view.conflicts[view.start..end].iter().enumerate() {
+			let dy = i as u16 + 1;
+			let colors = if view.start + i == view.cursor {
+				let area = Rect {
+					x: constr.task.x,
+					y: constr.task.y + i as u16,
+					w: constr.foreign.x + constr.foreign.w - constr.task.x,
+					h: 1,
+				};
+				self.paint(area, Colors::new(Color::Black, Color::White))?;
+				Colors::new(Color::Black, Color::White)
+			} else {
+				Colors { foreground: Some(Color::White), background: None }
+			};
+
+			let (field, local, foreign) = match &conflict.kind {
+				ConflictKind::Name { local, foreign } => ("name", local.clone(), foreign.clone()),
+				ConflictKind::DueDate { local, foreign } => ("due date", due_date_text(*local, view), due_date_text(*foreign, view)),
+			};
+			self.buffer.queue(SetColors(colors))?;
+			self.print_cell(&constr.task, dy, &conflict.name)?;
+			self.print_cell(&constr.field, dy, field)?;
+			self.print_cell(&constr.local, dy, &local)?;
+			self.print_cell(&constr.foreign, dy, &foreign)?;
+			self.buffer.queue(ResetColor)?;
+		}
+		Ok(self)
+	}
+}
+
+fn due_date_text(due_date: Option<NaiveDateTime>, view: &ConflictView) -> String {
+	match due_date {
+		Some(_) => format!("{}", Displayable(due_date, view.clock, view.time_format)),
+		None => "none".into(),
+	}
+}
+
+trait PrintCell {
+	fn print_cell(&mut self, column: &Rect, dy: u16, text: &str) -> io::Result<()>;
+}
+
+impl<B: Backend> PrintCell for Screen<B> {
+	fn print_cell(&mut self, column: &Rect, dy: u16, text: &str) -> io::Result<()> {
+		self.buffer
+			.queue(MoveTo(column.x, column.y + dy - 1))?
+			.queue(Print(clip(text, column.w.into())))?;
+		Ok(())
+	}
+}
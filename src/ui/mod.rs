@@ -1,47 +1,94 @@
+pub mod agenda;
+pub mod backend;
+pub mod buffer;
+pub mod conflict;
+pub mod report;
+pub mod search;
 pub mod session;
 pub mod status;
 pub mod tree;
+pub mod week;
 
-use std::io::{self, stdout, Stdout, Write};
-use crossterm::{terminal, QueueableCommand, ExecutableCommand};
+use std::io;
 use crossterm::cursor::MoveTo;
-use crossterm::style::{Colors, Print, ResetColor, SetColors};
-use crossterm::terminal::{Clear, ClearType};
+use crossterm::style::{Colors, Print};
+use unicode_width::UnicodeWidthChar;
+use backend::{Backend, CrosstermBackend};
+use buffer::{Buffer, Style};
 
-pub struct Screen {
-	stdout: Stdout,
+/// Views draw each frame into a buffer, which is compared with the previous frame when it is
+/// flushed, so that only the cells which changed are sent to the backend.
+pub struct Screen<B: Backend = CrosstermBackend> {
+	buffer: Buffer,
+	front: Buffer,
+	backend: B,
 }
 
 impl Screen {
 	pub fn new() -> io::Result<Self> {
-		Ok(Screen { stdout: stdout() })
+		Ok(Screen::with_backend(CrosstermBackend::new()))
+	}
+}
+
+impl<B: Backend> Screen<B> {
+	pub fn with_backend(backend: B) -> Self {
+		Screen { buffer: Buffer::default(), front: Buffer::default(), backend }
+	}
+
+	pub fn backend(&self) -> &B {
+		&self.backend
+	}
+
+	pub fn backend_mut(&mut self) -> &mut B {
+		&mut self.backend
+	}
+
+	pub fn size(&self) -> io::Result<(u16, u16)> {
+		self.backend.size()
 	}
 
 	pub fn draw_vline(&mut self, x: u16, y: u16, h: u16) -> io::Result<()> {
 		for y in y..y + h {
-			self.stdout.queue(MoveTo(x, y))?.queue(Print('│'))?;
+			self.buffer.queue(MoveTo(x, y))?.queue(Print('│'))?;
 		}
 		Ok(())
 	}
 
 	pub fn paint(&mut self, area: Rect, colors: Colors) -> io::Result<()> {
-		self.stdout.queue(SetColors(colors))?;
-		for y in area.y..area.y + area.h {
-			for x in area.x..area.x + area.w {
-				self.stdout.queue(MoveTo(x, y))?.queue(Print(' '))?;
-			}
-		}
-		self.stdout.queue(ResetColor)?;
+		let style = Style { fg: colors.foreground, bg: colors.background, ..Style::default() };
+		self.buffer.fill(area, style);
+		Ok(())
+	}
+
+	/// Prints a line of text in a column, cut to the width of the column.
+	fn print_clipped(&mut self, column: &Rect, dy: u16, text: &str) -> io::Result<()> {
+		self.buffer
+			.queue(MoveTo(column.x, column.y + dy))?
+			.queue(Print(clip(text, column.w.into())))?;
 		Ok(())
 	}
 
+	/// Starts a new frame, blank and as large as the backend.
 	pub fn clear(&mut self) -> io::Result<&mut Self> {
-		self.stdout.execute(Clear(ClearType::All))?;
+		let (w, h) = self.backend.size()?;
+		self.buffer.clear(w, h);
 		Ok(self)
 	}
 
+	/// Sends the cells which differ from the previous frame to the backend. The whole display is
+	/// cleared and redrawn after a resize.
 	pub fn flush(&mut self) -> io::Result<()> {
-		self.stdout.flush()
+		let (w, h) = self.buffer.size();
+		if self.front.size() != (w, h) {
+			self.backend.clear()?;
+			self.front = Buffer::new(w, h);
+		}
+		let changed = self.buffer.cells().zip(self.front.cells())
+			.filter(|((_, _, cell), (_, _, old))| cell != old)
+			.map(|(new, _)| new);
+		self.backend.draw(changed)?;
+		self.front.clone_from(&self.buffer);
+		Ok(())
 	}
 }
 
@@ -49,6 +96,19 @@ pub trait BufPrint<V> {
 	fn bufprint(&mut self, view: &V) -> io::Result<&mut Self>;
 }
 
+/// Returns the longest start of the text which fits in `w` columns of the terminal, on which
+/// wide characters take two.
+fn clip(text: &str, w: usize) -> &str {
+	let mut width = 0;
+	let end = text.char_indices()
+		.find(|&(_, ch)| {
+			width += ch.width().unwrap_or(0);
+			width > w
+		})
+		.map_or(text.len(), |(i, _)| i);
+	&text[..end]
+}
+
 #[derive(Default)]
 pub struct TreeViewConstraints {
 	w: u16,
@@ -59,11 +119,10 @@ pub struct TreeViewConstraints {
 }
 
 impl TreeViewConstraints {
-	pub fn new() -> io::Result<Self> {
+	pub fn new(w: u16, h: u16) -> Self {
 		let mut constr = TreeViewConstraints::default();
-		let (w, h) = terminal::size()?;
 		constr.update(w, h);
-		Ok(constr)
+		constr
 	}
 
 	pub fn update(&mut self, w: u16, h: u16) {
@@ -108,20 +167,23 @@ impl TreeViewConstraints {
 
 #[derive(Default)]
 pub struct SessionViewConstraints {
+	w: u16,
+	h: u16,
 	session: Rect,
 	tasks: Rect,
 	pub mode: SessionViewMode,
 }
 
 impl SessionViewConstraints {
-	pub fn new() -> io::Result<Self> {
+	pub fn new(w: u16, h: u16) -> Self {
 		let mut constr = SessionViewConstraints::default();
-		let (w, h) = terminal::size()?;
 		constr.update(w, h);
-		Ok(constr)
+		constr
 	}
 
 	pub fn update(&mut self, w: u16, h: u16) {
+		self.w = w;
+		self.h = h;
 		match self.mode {
 			SessionViewMode::Normal if h >= 2 && w >= 2 => {
 				self.session = Rect { x: 1, y: 1, w: (w - 2) / 2, h: h - 2 };
@@ -140,6 +202,10 @@ impl SessionViewConstraints {
 		}
 	}
 
+	pub fn size(&self) -> (u16, u16) {
+		(self.w, self.h)
+	}
+
 	pub fn session_height(&self) -> u16 {
 		self.session.h
 	}
@@ -160,17 +226,144 @@ pub enum SessionViewMode {
 	Task(u64),
 }
 
+#[derive(Default)]
+pub struct ConflictViewConstraints {
+	task: Rect,
+	field: Rect,
+	local: Rect,
+	foreign: Rect,
+}
+
+impl ConflictViewConstraints {
+	pub fn new(w: u16, h: u16) -> Self {
+		let mut constr = ConflictViewConstraints::default();
+		constr.update(w, h);
+		constr
+	}
+
+	pub fn update(&mut self, w: u16, h: u16) {
+		if h < 3 || w < 5 { return }
+		self.task = Rect { x: 1, y: 1, w: (w - 4) / 3, h: h - 2 };
+		self.field = Rect { x: self.task.x + self.task.w + 1, y: 1, w: (w - 4) / 6, h: h - 2 };
+		self.local = Rect {
+			x: self.field.x + self.field.w + 1,
+			y: 1,
+			w: (w - 4 - self.task.w - self.field.w) / 2,
+			h: h - 2
+		};
+		self.foreign = Rect {
+			x: self.local.x + self.local.w + 1,
+			y: 1,
+			w: w - 4 - self.task.w - self.field.w - self.local.w,
+			h: h - 2
+		};
+	}
+}
+
+#[derive(Default)]
+pub struct AgendaViewConstraints {
+	due_date: Rect,
+	task: Rect,
+	location: Rect,
+}
+
+impl AgendaViewConstraints {
+	pub fn new(w: u16, h: u16) -> Self {
+		let mut constr = AgendaViewConstraints::default();
+		constr.update(w, h);
+		constr
+	}
+
+	pub fn update(&mut self, w: u16, h: u16) {
+		if h < 2 || w < 4 { return }
+		self.due_date = Rect { x: 1, y: 1, w: (w - 4) / 4, h: h - 2 };
+		self.task = Rect { x: self.due_date.x + self.due_date.w + 1, y: 1, w: (w - 4) * 3 / 8, h: h - 2 };
+		self.location = Rect {
+			x: self.task.x + self.task.w + 1,
+			y: 1,
+			w: w - 4 - self.due_date.w - self.task.w,
+			h: h - 2,
+		};
+	}
+}
+
+#[derive(Default)]
+pub struct WeekViewConstraints {
+	hours: Rect,
+	day_w: u16,
+}
+
+impl WeekViewConstraints {
+	pub fn new(w: u16, h: u16) -> Self {
+		let mut constr = WeekViewConstraints::default();
+		constr.update(w, h);
+		constr
+	}
+
+	pub fn update(&mut self, w: u16, h: u16) {
+		if h < 3 || w < 6 + 7 * 2 { return }
+		self.hours = Rect { x: 0, y: 1, w: 5, h: h - 2 };
+		self.day_w = (w - 6) / 7 - 1;
+	}
+
+	/// Returns the column of a day, counted from Monday, which has a line on its left.
+	fn day(&self, day: u16) -> Rect {
+		Rect { x: self.hours.w + 2 + day * (self.day_w + 1), y: 1, w: self.day_w, h: self.hours.h }
+	}
+}
+
+#[derive(Default)]
+pub struct ReportViewConstraints {
+	total: Rect,
+	own: Rect,
+	task: Rect,
+}
+
+impl ReportViewConstraints {
+	pub fn new(w: u16, h: u16) -> Self {
+		let mut constr = ReportViewConstraints::default();
+		constr.update(w, h);
+		constr
+	}
+
+	pub fn update(&mut self, w: u16, h: u16) {
+		if h < 2 || w < 21 { return }
+		self.total = Rect { x: 1, y: 1, w: 8, h: h - 2 };
+		self.own = Rect { x: self.total.x + self.total.w + 1, y: 1, w: 8, h: h - 2 };
+		self.task = Rect { x: self.own.x + self.own.w + 2, y: 1, w: w - 21, h: h - 2 };
+	}
+}
+
+#[derive(Default)]
+pub struct SearchViewConstraints {
+	name: Rect,
+	location: Rect,
+}
+
+impl SearchViewConstraints {
+	pub fn new(w: u16, h: u16) -> Self {
+		let mut constr = SearchViewConstraints::default();
+		constr.update(w, h);
+		constr
+	}
+
+	pub fn update(&mut self, w: u16, h: u16) {
+		if h < 2 || w < 3 { return }
+		self.name = Rect { x: 1, y: 1, w: (w - 3) / 2, h: h - 2 };
+		self.location = Rect { x: self.name.x + self.name.w + 1, y: 1, w: w - 3 - self.name.w, h: h - 2 };
+	}
+}
+
 #[derive(Default)]
 pub struct StatusViewConstraints {
 	status: Rect,
 }
 
 impl StatusViewConstraints {
-	pub fn new() -> io::Result<Self> {
+	pub fn new(w: u16, h: u16) -> Self {
 		let mut constr = StatusViewConstraints::default();
-		let (w, h) = terminal::size()?;
 		constr.update(w, h);
-		Ok(constr)
+		constr
 	}
 
 	pub fn update(&mut self, w: u16, h: u16) {
@@ -185,3 +378,27 @@ pub struct Rect {
 	w: u16,
 	pub h: u16,
 }
+
+#[cfg(test)]
+mod tests {
+	use crate::ui::backend::MemoryBackend;
+	use super::{Rect, Screen};
+
+	#[test]
+	fn wide_characters() {
+		let mut screen = Screen::with_backend(MemoryBackend::new(8, 2));
+		let column = Rect { x: 1, y: 0, w: 5, h: 2 };
+		screen.clear().unwrap().print_clipped(&column, 0, "abcdef").unwrap();
+		screen.print_clipped(&column, 1, "日本語です").unwrap();
+		screen.flush().unwrap();
+		assert_eq!(screen.backend().lines(), [" abcde", " 日本"]);
+		assert!(screen.backend().cell(2, 1).unwrap().is_continuation());
+
+		// Only the cells which changed are drawn, so those covered by a wide character must
+		// differ from the narrow ones they replace.
+		screen.clear().unwrap().print_clipped(&column, 0, "日x本").unwrap();
+		screen.print_clipped(&column, 1, "a日b").unwrap();
+		screen.flush().unwrap();
+		assert_eq!(screen.backend().lines(), [" 日x本", " a日b"]);
+	}
+}
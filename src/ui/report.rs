@@ -0,0 +1,98 @@
+use std::io;
+use crossterm::style::{Attribute, Color, Colors, ResetColor, SetAttribute, SetColors};
+use crate::report::{format_duration, Row};
+use super::{Backend, BufPrint, Rect, ReportViewConstraints, Screen};
+
+/// The time spent on each task within a period, as a tree indented by depth under a title
+/// naming the period.
+pub struct ReportView {
+	rows: Vec<Row>,
+	title: String,
+	cursor: usize,
+	start: usize,
+	pub constr: ReportViewConstraints,
+}
+
+impl ReportView {
+	pub fn new(w: u16, h: u16) -> Self {
+		ReportView { rows: Vec::new(), title: String::new(), cursor: 0, start: 0, constr: ReportViewConstraints::new(w, h) }
+	}
+
+	/// Replaces the rows, keeping the cursor on the same task if it is still listed.
+	pub fn reset(&mut self, title: String, rows: Vec<Row>) {
+		let id = self.rows.get(self.cursor).map(|row| row.id);
+		self.cursor = rows.iter().position(|row| Some(row.id) == id).unwrap_or(0);
+		self.start = self.start.min(self.cursor);
+		self.rows = rows;
+		self.title = title;
+		self.scroll();
+	}
+
+	pub fn cursor_up(&mut self) {
+		if self.cursor == 0 { return };
+		self.cursor -= 1;
+		self.scroll();
+	}
+
+	pub fn cursor_down(&mut self) {
+		if self.cursor + 1 >= self.rows.len() { return };
+		self.cursor += 1;
+		self.scroll();
+	}
+
+	/// Returns the ids from the root task to the task under the cursor, which are the closest
+	/// rows above it at each smaller depth.
+	pub fn cursor_path(&self) -> Option<Vec<u64>> {
+		let row = self.rows.get(self.cursor)?;
+		let mut path = vec![row.id];
+		let mut depth = row.depth;
+		for row in self.rows[..self.cursor].iter().rev() {
+			if row.depth < depth {
+				path.push(row.id);
+				depth = row.depth;
+			}
+		}
+		path.reverse();
+		Some(path)
+	}
+
+	fn scroll(&mut self) {
+		let h = usize::from(self.constr.task.h);
+		if h == 0 { return };
+		if self.cursor < self.start { self.start = self.cursor };
+		if self.cursor >= self.start + h { self.start = self.cursor + 1 - h };
+	}
+}
+
+impl<B: Backend> BufPrint<ReportView> for Screen<B> {
+	fn bufprint(&mut self, view: &ReportView) -> io::Result<&mut Self> {
+		let constr = &view.constr;
+		self.buffer.queue(SetAttribute(Attribute::Bold))?;
+		self.print_clipped(&Rect { y: 0, h: 1, w: constr.task.x + constr.task.w - constr.total.x, ..constr.total }, 0, &view.title)?;
+		self.buffer.queue(SetAttribute(Attribute::Reset))?;
+
+		let end = (view.start + usize::from(constr.task.h)).min(view.rows.len());
+		for (i, row) in view.rows[view.start..end].iter().enumerate() {
+			let dy = i as u16;
+			let colors = if view.start + i == view.cursor {
+				let area = Rect {
+					x: constr.total.x,
+					y: constr.total.y + dy,
+					w: constr.task.x + constr.task.w - constr.total.x,
+					h: 1,
+				};
+				self.paint(area, Colors::new(Color::Black, Color::White))?;
+				Colors::new(Color::Black, Color::White)
+			} else {
+				Colors { foreground: Some(Color::White), background: None }
+			};
+
+			self.buffer.queue(SetColors(colors))?;
+			self.print_clipped(&constr.total, dy, &format!("{:>w$}", format_duration(row.total), w = constr.total.w.into()))?;
+			self.print_clipped(&constr.own, dy, &format!("{:>w$}", format_duration(row.own), w = constr.own.w.into()))?;
+			self.print_clipped(&constr.task, dy, &format!("{}{}", "  ".repeat(row.depth), row.name))?;
+			self.buffer.queue(ResetColor)?;
+		}
+		Ok(self)
+	}
+}
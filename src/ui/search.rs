@@ -0,0 +1,78 @@
+use std::io;
+use crossterm::style::{Color, Colors, ResetColor, SetColors};
+use super::{Backend, BufPrint, Rect, Screen, SearchViewConstraints};
+
+/// The ranked results of a search, one task per line, with the path of its parent.
+pub struct SearchView {
+	items: Vec<Item>,
+	cursor: usize,
+	start: usize,
+	pub constr: SearchViewConstraints,
+}
+
+pub struct Item {
+	pub name: String,
+	pub location: String,
+	pub path: Vec<u64>,
+}
+
+impl SearchView {
+	pub fn new(w: u16, h: u16) -> Self {
+		SearchView { items: Vec::new(), cursor: 0, start: 0, constr: SearchViewConstraints::new(w, h) }
+	}
+
+	pub fn reset(&mut self, items: Vec<Item>) {
+		self.items = items;
+		self.cursor = 0;
+		self.start = 0;
+	}
+
+	pub fn cursor_up(&mut self) {
+		if self.cursor == 0 { return };
+		self.cursor -= 1;
+		if self.cursor < self.start { self.start = self.cursor };
+	}
+
+	pub fn cursor_down(&mut self) {
+		if self.cursor + 1 >= self.items.len() { return };
+		self.cursor += 1;
+		let h = usize::from(self.constr.name.h);
+		if self.cursor >= self.start + h { self.start = self.cursor + 1 - h };
+	}
+
+	/// Returns the ids from the root task to the task under the cursor.
+	pub fn cursor_path(&self) -> Option<&[u64]> {
+		self.items.get(self.cursor).map(|item| item.path.as_slice())
+	}
+}
+
+impl<B: Backend> BufPrint<SearchView> for Screen<B> {
+	fn bufprint(&mut self, view: &SearchView) -> io::Result<&mut Self> {
+		let constr = &view.constr;
+		let end = (view.start + usize::from(constr.name.h)).min(view.items.len());
+		for (i, item) in view.items[view.start..end].iter().enumerate() {
+			let dy = i as u16;
+			let colors = if view.start + i == view.cursor {
+				let area = Rect {
+					x: constr.name.x,
+					y: constr.name.y + dy,
+					w: constr.location.x + constr.location.w - constr.name.x,
+					h: 1,
+				};
+				self.paint(area, Colors::new(Color::Black, Color::White))?;
+				Colors::new(Color::Black, Color::White)
+			} else {
+				Colors { foreground: Some(Color::White), background: None }
+			};
+
+			self.buffer.queue(SetColors(colors))?;
+			self.print_clipped(&constr.name, dy, &item.name)?;
+			if view.start + i != view.cursor {
+				self.buffer.queue(SetColors(Colors { foreground: Some(Color::DarkGrey), background: None }))?;
+			}
+			self.print_clipped(&constr.location, dy, &item.location)?;
+			self.buffer.queue(ResetColor)?;
+		}
+		Ok(self)
+	}
+}
@@ -1,12 +1,11 @@
 use std::cmp::max;
 use std::io;
 use std::ops::Range;
-use crossterm::QueueableCommand;
 use crossterm::cursor::MoveTo;
 use crossterm::style::{Color, Colors, Print, ResetColor, SetColors};
 use grus_lib::types::Session;
 use crate::node::wrap_text;
-use super::{BufPrint, Rect, Screen, SessionViewConstraints};
+use super::{Backend, BufPrint, Rect, Screen, SessionViewConstraints};
 
 pub struct SessionView {
 	items: Vec<Item>,
@@ -17,20 +16,20 @@ pub struct SessionView {
 }
 
 impl SessionView {
-	pub fn new(items: Vec<Item>) -> io::Result<Self> {
+	pub fn new(items: Vec<Item>, w: u16, h: u16) -> Self {
 		let mut sv = SessionView {
 			items,
 			cursor: 0,
 			start: 0,
 			heights: Vec::new(),
-			constr: SessionViewConstraints::new()?,
+			constr: SessionViewConstraints::new(w, h),
 		};
 		sv.anchor_top(sv.cursor);
-		Ok(sv)
+		sv
 	}
 
 	pub fn reset(&mut self, items: Vec<Item>) {
-		if self.items.len() == 0 {
+		if self.items.is_empty() {
 			self.items = items;
 			self.anchor_top(self.start);
 			return;
@@ -95,6 +94,10 @@ impl SessionView {
 		self.items.get(self.cursor).map(|Item { id, session, .. }| (*id, session))
 	}
 
+	pub fn is_upcoming(&self) -> bool {
+		self.items.get(self.cursor).is_some_and(|item| item.upcoming)
+	}
+
 	fn anchor_top(&mut self, index: usize) {
 		self.heights.clear();
 		let mut h = 0;
@@ -131,6 +134,8 @@ pub struct Item {
 	pub name_splits: Vec<usize>,
 	pub session_text: String,
 	pub session_splits: Vec<usize>,
+	/// Whether the item is a future occurrence of a repeating session, which is not stored yet.
+	pub upcoming: bool,
 }
 
 impl Item {
@@ -147,7 +152,7 @@ impl Item {
 	}
 }
 
-impl BufPrint<SessionView> for Screen {
+impl<B: Backend> BufPrint<SessionView> for Screen<B> {
 	fn bufprint(&mut self, view: &SessionView) -> io::Result<&mut Self> {
 		let mut h = 0;
 		for (i, item) in view.items[view.window()].iter().enumerate() {
@@ -169,7 +174,7 @@ impl BufPrint<SessionView> for Screen {
 				self.print_item(item, h, Colors::new(Color::Black, Color::White), &view.constr)?;
 			} else {
 				self.print_item(item, h, Colors {
-					foreground: Some(Color::White),
+					foreground: Some(if item.upcoming { Color::DarkGrey } else { Color::White }),
 					background: None,
 				}, &view.constr)?;
 			}
@@ -183,22 +188,66 @@ trait PrintItem {
 	fn print_item(&mut self, item: &Item, dy: u16, colors: Colors, constr: &SessionViewConstraints) -> io::Result<()>;
 }
 
-impl PrintItem for Screen {
+impl<B: Backend> PrintItem for Screen<B> {
 	fn print_item(&mut self, item: &Item, dy: u16, colors: Colors, constr: &SessionViewConstraints) -> io::Result<()> {
-		self.stdout.queue(SetColors(colors))?;
+		self.buffer.queue(SetColors(colors))?;
 
 		for (i, split) in item.session_splits().enumerate() {
-			self.stdout
+			self.buffer
 				.queue(MoveTo(constr.session.x, constr.session.y + dy + i as u16))?
 				.queue(Print(split))?;
 		}
 
 		for (i, split) in item.name_splits().enumerate() {
-			self.stdout
+			self.buffer
 				.queue(MoveTo(constr.tasks.x, constr.tasks.y + dy + i as u16))?
 				.queue(Print(split))?;
 		}
-		self.stdout.queue(ResetColor)?;
+		self.buffer.queue(ResetColor)?;
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use chrono::{Duration, NaiveDate};
+	use crossterm::style::Color;
+	use grus_lib::types::Session;
+	use crate::node::wrap_text;
+	use crate::ui::{BufPrint, Screen};
+	use crate::ui::backend::MemoryBackend;
+	use super::{Item, SessionView};
+
+	fn item(id: u64, name: &str, day: u32, upcoming: bool) -> Item {
+		let start = NaiveDate::from_ymd_opt(2023, 7, day).unwrap().and_hms_opt(9, 0, 0).unwrap();
+		let session_text = format!("{} 9 AM", day);
+		Item {
+			session: Session { start, end: start + Duration::hours(1) },
+			id,
+			name: name.into(),
+			name_splits: wrap_text(name, 14),
+			session_splits: wrap_text(&session_text, 14),
+			session_text,
+			upcoming,
+		}
+	}
+
+	#[test]
+	fn render() {
+		let items = vec![item(1, "standup", 3, false), item(2, "long weekly review", 4, false), item(1, "standup", 10, true)];
+		let mut view = SessionView::new(items, 30, 6);
+		view.cursor_down();
+		let mut screen = Screen::with_backend(MemoryBackend::new(30, 6));
+		screen.clear().unwrap().bufprint(&view).unwrap().flush().unwrap();
+		assert_eq!(screen.backend().lines(), [
+			"",
+			" 3 9 AM         standup",
+			" 4 9 AM         long weekly",
+			"                review",
+			" 10 9 AM        standup",
+			"",
+		]);
+		assert_eq!(screen.backend().cell(1, 2).unwrap().style.bg, Some(Color::White));
+		assert_eq!(screen.backend().cell(1, 4).unwrap().style.fg, Some(Color::DarkGrey));
+	}
+}
@@ -1,28 +1,34 @@
 use std::cmp::min;
 use std::io;
 use std::fmt::{self, Display, Formatter};
-use crossterm::QueueableCommand;
 use crossterm::cursor::MoveTo;
-use crossterm::style::{Color, Colors, Print, ResetColor, SetColors};
-use super::{BufPrint, Screen, StatusViewConstraints};
+use crossterm::style::{Color, Colors, Print, ResetColor, SetColors, SetForegroundColor};
+use super::{clip, Backend, BufPrint, Screen, StatusViewConstraints};
 
 pub struct StatusView<const V: usize> {
 	input: Input,
 	start: usize,
 	buffer: String,
+	/// Text shown on the left while no command is typed, such as the running timers.
+	info: String,
+	/// Shown instead of the info, or of the prompt while a command is typed, until it is cleared,
+	/// usually by the next key.
+	message: Option<String>,
 	pub mode: Mode,
 	pub constr: StatusViewConstraints,
 }
 
 impl<const V: usize> StatusView<V> {
-	pub fn new() -> io::Result<Self> {
-		Ok(StatusView {
+	pub fn new(w: u16, h: u16) -> Self {
+		StatusView {
 			input: Input { front: "".into(), back: "".into() },
 			start: 0,
 			buffer: "".into(),
+			info: "".into(),
+			message: None,
 			mode: Mode::Normal,
-			constr: StatusViewConstraints::new()?,
-		})
+			constr: StatusViewConstraints::new(w, h),
+		}
 	}
 
 	pub fn set_input(&mut self, input: &str) {
@@ -34,7 +40,7 @@ impl<const V: usize> StatusView<V> {
 	pub fn insert(&mut self, c: char) {
 		self.input.front.push(c);
 		if self.input.front.len() - self.start >= self.cmd_width() {
-			self.start = self.input.front.len() - usize::from(self.cmd_width()) + 1;
+			self.start = self.input.front.len() - self.cmd_width() + 1;
 		}
 	}
 
@@ -58,7 +64,7 @@ impl<const V: usize> StatusView<V> {
 		if let Some(c) = self.input.back.pop() {
 			self.input.front.push(c);
 			if self.input.front.len() - self.start >= self.cmd_width() / 2 {
-				self.start = self.input.front.len() - usize::from(self.cmd_width() / 2) + 1;
+				self.start = self.input.front.len() - self.cmd_width() / 2 + 1;
 			}
 		}
 	}
@@ -69,6 +75,19 @@ impl<const V: usize> StatusView<V> {
 		self.input.back.clear();
 	}
 
+	pub fn set_info(&mut self, info: String) {
+		self.info = info;
+	}
+
+	/// Shows a message in the status line until the next key is pressed.
+	pub fn set_message(&mut self, message: impl Into<String>) {
+		self.message = Some(message.into());
+	}
+
+	pub fn clear_message(&mut self) {
+		self.message = None;
+	}
+
 	pub fn input(&mut self) -> &str {
 		self.buffer.clear();
 		self.buffer += &self.input.front;
@@ -95,6 +114,11 @@ pub enum CommandType {
 	Rename,
 	SetDueDate,
 	AddSession,
+	ImportSessions,
+	ImportTasks,
+	ExportOutline,
+	ImportOutline,
+	Search,
 }
 
 impl Display for CommandType {
@@ -106,6 +130,10 @@ impl Display for CommandType {
 const VIEW_TEXT: &[&str] = &[
 	" TREE VIEW ",
 	" SESSION VIEW ",
+	" CONFLICT VIEW ",
+	" AGENDA VIEW ",
+	" WEEK VIEW ",
+	" REPORT VIEW ",
 ];
 
 const COMMAND_TEXT: &[&str] = &[
@@ -113,6 +141,11 @@ const COMMAND_TEXT: &[&str] = &[
 	" rename ",
 	" due date ",
 	" add session ",
+	" import sessions from ",
+	" import tasks from ",
+	" export outline to ",
+	" import outline from ",
+	" search ",
 ];
 
 struct Input {
@@ -120,20 +153,35 @@ struct Input {
 	back: String,
 }
 
-impl<const V: usize> BufPrint<StatusView<V>> for Screen {
+impl<const V: usize, B: Backend> BufPrint<StatusView<V>> for Screen<B> {
 	fn bufprint(&mut self, view: &StatusView<V>) -> io::Result<&mut Self> {
 		if VIEW_TEXT[V].len() <= view.constr.status.w.into() {
-			self.stdout
+			self.buffer
 				.queue(MoveTo(view.constr.status.x + view.constr.status.w - VIEW_TEXT[V].len() as u16, view.constr.status.y))?
 				.queue(SetColors(Colors::new(Color::Black, Color::DarkCyan)))?
 				.queue(Print(VIEW_TEXT[V]))?
-				.queue(Print(ResetColor))?;
+				.queue(ResetColor)?;
+		}
+
+		let shown = match (&view.message, view.mode) {
+			(Some(message), _) => Some((message, Color::Red)),
+			(None, Mode::Normal) => Some((&view.info, Color::Reset)),
+			(None, Mode::Command(_)) => None,
+		};
+		if let Some((text, color)) = shown {
+			let w = usize::from(view.constr.status.w).saturating_sub(VIEW_TEXT[V].len() + 1);
+			self.buffer
+				.queue(MoveTo(view.constr.status.x, view.constr.status.y))?
+				.queue(SetForegroundColor(color))?
+				.queue(Print(clip(text, w)))?
+				.queue(ResetColor)?;
+			return Ok(self);
 		}
 
 		if view.cmd_width() == 0 { return Ok(self) };
 
 		let Mode::Command(cmd_type) = view.mode else { return Ok(self) };
-		self.stdout
+		self.buffer
 			.queue(MoveTo(view.constr.status.x, view.constr.status.y))?
 			.queue(SetColors(Colors::new(Color::Black, Color::Yellow)))?
 			.queue(Print(Print(cmd_type)))?
@@ -141,12 +189,12 @@ impl<const V: usize> BufPrint<StatusView<V>> for Screen {
 			.queue(Print(' '))?
 			.queue(Print(&view.input.front[view.start..]))?
 			.queue(SetColors(Colors::new(Color::Black, Color::White)))?
-			.queue(Print(view.input.back.chars().rev().next().unwrap_or(' ')))?
-			.queue(Print(ResetColor))?;
+			.queue(Print(view.input.back.chars().next_back().unwrap_or(' ')))?
+			.queue(ResetColor)?;
 
 		if !view.input.back.is_empty() {
 			let till = min(view.input.back.len(), view.cmd_width() + view.start - view.input.front.len() - 1);
-			self.stdout.queue(Print(Reverse(&view.input.back[view.input.back.len() - till..view.input.back.len() - 1])))?;
+			self.buffer.queue(Print(Reverse(&view.input.back[view.input.back.len() - till..view.input.back.len() - 1])))?;
 		}
 
 		Ok(self)
@@ -163,3 +211,36 @@ impl<'a> Display for Reverse<'a> {
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use crossterm::style::Color;
+	use crate::ui::{BufPrint, Screen};
+	use crate::ui::backend::MemoryBackend;
+	use super::{CommandType, Mode, StatusView};
+
+	#[test]
+	fn render() {
+		let mut view = StatusView::<0>::new(40, 3);
+		view.set_info("report 0:25".into());
+		let mut screen = Screen::with_backend(MemoryBackend::new(40, 3));
+		screen.clear().unwrap().bufprint(&view).unwrap().flush().unwrap();
+		assert_eq!(screen.backend().lines()[2], "report 0:25                   TREE VIEW");
+
+		view.set_message("not a date");
+		screen.clear().unwrap().bufprint(&view).unwrap().flush().unwrap();
+		assert_eq!(screen.backend().lines()[2], "not a date                    TREE VIEW");
+		assert_eq!(screen.backend().cell(0, 2).unwrap().style.fg, Some(Color::Red));
+
+		view.mode = Mode::Command(CommandType::Rename);
+		"groceries".chars().for_each(|c| view.insert(c));
+		view.move_left();
+		screen.clear().unwrap().bufprint(&view).unwrap().flush().unwrap();
+		assert_eq!(screen.backend().lines()[2], "not a date                    TREE VIEW");
+
+		view.clear_message();
+		screen.clear().unwrap().bufprint(&view).unwrap().flush().unwrap();
+		assert_eq!(screen.backend().lines()[2], " rename  groceries            TREE VIEW");
+		assert_eq!(screen.backend().cell(17, 2).unwrap().style.bg, Some(Color::White));
+	}
+}
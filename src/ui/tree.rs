@@ -3,11 +3,10 @@ use std::collections::hash_map::DefaultHasher;
 use std::hash::Hasher;
 use std::io;
 use std::iter;
-use crossterm::QueueableCommand;
 use crossterm::cursor::MoveTo;
 use crossterm::style::{Color, Colors, Print, ResetColor, SetColors, SetForegroundColor};
 use crate::node::{Node, Priority};
-use super::{BufPrint, Rect, Screen, TreeViewConstraints};
+use super::{Backend, BufPrint, Rect, Screen, TreeViewConstraints};
 
 pub struct TreeView {
 	flattree: Vec<Node<'static>>,
@@ -20,16 +19,16 @@ pub struct TreeView {
 }
 
 impl TreeView {
-	pub fn new(flattree: Vec<Node<'static>>) -> io::Result<Self> {
-		Ok(TreeView {
+	pub fn new(flattree: Vec<Node<'static>>, w: u16, h: u16) -> Self {
+		TreeView {
 			flattree,
 			cursor: 0,
 			selections: HashMap::new(),
 			root_pid: 0,
 			root_id: 0,
 			stack: Vec::new(),
-			constr: TreeViewConstraints::new()?,
-		})
+			constr: TreeViewConstraints::new(w, h),
+		}
 	}
 
 	pub fn reset(&mut self, flattree: Vec<Node<'static>>) {
@@ -98,12 +97,32 @@ impl TreeView {
 	pub fn move_into(&mut self) {
 		let Some(&Node { id, pid, .. }) = self.cursor_node() else { return };
 		if self.is_cursor_at_root() { return };
+		self.enter(pid, id);
+	}
 
+	/// Makes the task the root task, keeping the current root task on the stack.
+	pub fn enter(&mut self, pid: u64, id: u64) {
 		self.stack.push((self.root_pid, self.root_id));
 		self.root_pid = pid;
 		self.root_id = id;
 	}
 
+	/// Makes the parent of the last task of a path of ids from the root task the root task,
+	/// with the ancestors of the parent on the stack, so that moving out retraces the path.
+	pub fn show_path(&mut self, path: &[u64]) {
+		let mut roots: Vec<(u64, u64)> = iter::once((0, 0)).chain(path.windows(2).map(|w| (w[0], w[1]))).collect();
+		roots.pop();
+		(self.root_pid, self.root_id) = roots.pop().unwrap_or((0, 0));
+		self.stack = roots;
+	}
+
+	/// Moves the cursor to the task under the given parent. Returns false if it is not shown.
+	pub fn focus(&mut self, pid: u64, id: u64) -> bool {
+		let Some(i) = self.flattree.iter().position(|node| node.id == id && node.pid == pid) else { return false };
+		self.cursor = i;
+		true
+	}
+
 	pub fn move_out(&mut self) {
 		let Some((root_pid, root_id)) = self.stack.pop() else { return };
 		self.root_pid = root_pid;
@@ -118,8 +137,8 @@ impl TreeView {
 		self.root_id
 	}
 
-	pub fn cursor_node(&self) -> Option<&Node> {
-		if self.flattree.len() > 0 {
+	pub fn cursor_node(&self) -> Option<&Node<'static>> {
+		if !self.flattree.is_empty() {
 			Some(&self.flattree[self.cursor])
 		} else {
 			None
@@ -195,7 +214,7 @@ impl<'s, T: Iterator<Item = &'s u64>> Iterator for SelectionIds<'s, T> {
 	}
 }
 
-impl BufPrint<TreeView> for Screen {
+impl<B: Backend> BufPrint<TreeView> for Screen<B> {
 	fn bufprint(&mut self, view: &TreeView) -> io::Result<&mut Self> {
 		let mut painter = TreeViewPainter::new(self, view, &view.constr)?;
 		painter.paint_sel_task()?;
@@ -206,16 +225,16 @@ impl BufPrint<TreeView> for Screen {
 	}
 }
 
-struct TreeViewPainter<'screen, 'view, 'constr> {
-	screen: &'screen mut Screen,
+struct TreeViewPainter<'screen, 'view, 'constr, B: Backend> {
+	screen: &'screen mut Screen<B>,
 	view: &'view TreeView,
 	constr: &'constr TreeViewConstraints,
 	height: u16,
 	color_map: HashMap<u64, Color>,
 }
 
-impl<'screen, 'view, 'constr> TreeViewPainter<'screen, 'view, 'constr> {
-	fn new(screen: &'screen mut Screen, view: &'view TreeView, constr: &'constr TreeViewConstraints) -> io::Result<Self> {
+impl<'screen, 'view, 'constr, B: Backend> TreeViewPainter<'screen, 'view, 'constr, B> {
+	fn new(screen: &'screen mut Screen<B>, view: &'view TreeView, constr: &'constr TreeViewConstraints) -> io::Result<Self> {
 		let mut h = 0;
 		let mut color_map = HashMap::new();
 		for task in view.flattree.iter() {
@@ -246,7 +265,7 @@ impl<'screen, 'view, 'constr> TreeViewPainter<'screen, 'view, 'constr> {
 				(false, true) =>
 					self.print_task(task, h, Colors::new(Color::White, Color::DarkBlue))?,
 				(false, false) => self.print_task(task, h, Colors {
-					foreground: Some(Color::White),
+					foreground: Some(if task.done { Color::DarkGrey } else { Color::White }),
 					background: None,
 				})?,
 			}
@@ -256,10 +275,10 @@ impl<'screen, 'view, 'constr> TreeViewPainter<'screen, 'view, 'constr> {
 	}
 
 	fn print_task(&mut self, task: &Node, dy: u16, colors: Colors) -> io::Result<()> {
-		self.screen.stdout.queue(SetColors(colors))?;
+		self.screen.buffer.queue(SetColors(colors))?;
 
 		for (i, split) in task.name_splits().enumerate() {
-			self.screen.stdout
+			self.screen.buffer
 				.queue(MoveTo(
 					self.constr.tasks.x + 2 * task.depth as u16 + 1,
 					self.constr.tasks.y + dy + i as u16
@@ -268,7 +287,7 @@ impl<'screen, 'view, 'constr> TreeViewPainter<'screen, 'view, 'constr> {
 		}
 
 		for (i, split) in task.session_splits().enumerate() {
-			self.screen.stdout
+			self.screen.buffer
 				.queue(MoveTo(
 					self.constr.session.x,
 					self.constr.session.y + dy + i as u16
@@ -277,7 +296,7 @@ impl<'screen, 'view, 'constr> TreeViewPainter<'screen, 'view, 'constr> {
 		}
 
 		for (i, split) in task.due_date_splits().enumerate() {
-			self.screen.stdout
+			self.screen.buffer
 				.queue(MoveTo(
 					self.constr.due_date.x,
 					self.constr.due_date.y + dy + i as u16
@@ -285,7 +304,7 @@ impl<'screen, 'view, 'constr> TreeViewPainter<'screen, 'view, 'constr> {
 				.queue(Print(split))?;
 		}
 
-		self.screen.stdout.queue(ResetColor)?;
+		self.screen.buffer.queue(ResetColor)?;
 		Ok(())
 	}
 
@@ -294,7 +313,7 @@ impl<'screen, 'view, 'constr> TreeViewPainter<'screen, 'view, 'constr> {
 		for (i, task) in self.view.flattree.iter().enumerate() {
 			let area = Rect {
 				x: self.constr.tasks.x,
-				y: self.constr.tasks.y + h as u16,
+				y: self.constr.tasks.y + h,
 				w: self.constr.tasks.w + self.constr.session.w + self.constr.due_date.w + 2,
 				h: task.height() as u16,
 			};
@@ -354,11 +373,11 @@ impl<'screen, 'view, 'constr> TreeViewPainter<'screen, 'view, 'constr> {
 		if task.depth == 0 {
 			if next_is_child {
 				for dy in dy..dy + task.height() as u16 {
-					self.screen.stdout.queue(MoveTo(self.constr.tasks.x, self.constr.tasks.y + dy))?;
-					self.screen.stdout.queue(Print("│"))?;
+					self.screen.buffer.queue(MoveTo(self.constr.tasks.x, self.constr.tasks.y + dy))?;
+					self.screen.buffer.queue(Print("│"))?;
 				}
 			}
-			self.screen.stdout
+			self.screen.buffer
 				.queue(MoveTo(self.constr.tasks.x, self.constr.tasks.y))?
 				.queue(SetForegroundColor(color_from_prio(&task.priority)))?
 				.queue(Print("•"))?
@@ -368,34 +387,34 @@ impl<'screen, 'view, 'constr> TreeViewPainter<'screen, 'view, 'constr> {
 
 		let color = *self.color_map.get(&task.id).unwrap();
 		for dy in dy..dy + task.height() as u16 {
-			self.screen.stdout.queue(MoveTo(self.constr.tasks.x, self.constr.tasks.y + dy))?;
+			self.screen.buffer.queue(MoveTo(self.constr.tasks.x, self.constr.tasks.y + dy))?;
 			let mut pos_iter = line_pos.iter();
 			let mut pos = pos_iter.next();
 			for d in 1..task.depth {
 				if Some(&d) == pos {
-					self.screen.stdout.queue(Print("│ "))?;
+					self.screen.buffer.queue(Print("│ "))?;
 					pos = pos_iter.next();
 				} else {
-					self.screen.stdout.queue(Print("  "))?;
+					self.screen.buffer.queue(Print("  "))?;
 				}
 			}
-			self.screen.stdout
+			self.screen.buffer
 				.queue(Print(if task.priority.is_least() { "  " } else { "│ " }))?
 				.queue(Print(if next_is_child { "│" } else { " " }))?;
 		}
 
 		let dx = 2 * task.depth as u16 - 2;
-		self.screen.stdout
+		self.screen.buffer
 			.queue(MoveTo(self.constr.tasks.x + dx, self.constr.tasks.y + dy))?;
 		if color != Color::White {
-			self.screen.stdout
+			self.screen.buffer
 				.queue(SetForegroundColor(color))?
 				.queue(Print(if task.priority.is_least() { "┕━" } else { "┝━" }))?
 				.queue(ResetColor)?;
 		} else {
-			self.screen.stdout.queue(Print(if task.priority.is_least() { "└─" } else { "├─" }))?;
+			self.screen.buffer.queue(Print(if task.priority.is_least() { "└─" } else { "├─" }))?;
 		}
-		self.screen.stdout
+		self.screen.buffer
 			.queue(SetForegroundColor(color_from_prio(&task.priority)))?
 			.queue(Print("•"))?
 			.queue(ResetColor)?;
@@ -406,15 +425,15 @@ impl<'screen, 'view, 'constr> TreeViewPainter<'screen, 'view, 'constr> {
 		let mut h = 0;
 		for (i, task) in self.view.flattree.iter().enumerate() {
 			match (i == self.view.cursor, self.view.is_selected(task.pid, task.id)) {
-				(true, true) => self.screen.stdout.queue(SetColors(Colors::new(Color::White, Color::Blue)))?,
-				(true, false) => self.screen.stdout.queue(SetColors(Colors::new(Color::Black, Color::White)))?,
-				(false, true) => self.screen.stdout.queue(SetColors(Colors::new(Color::White, Color::DarkBlue)))?,
-				(false, false) => &mut self.screen.stdout,
+				(true, true) => self.screen.buffer.queue(SetColors(Colors::new(Color::White, Color::Blue)))?,
+				(true, false) => self.screen.buffer.queue(SetColors(Colors::new(Color::Black, Color::White)))?,
+				(false, true) => self.screen.buffer.queue(SetColors(Colors::new(Color::White, Color::DarkBlue)))?,
+				(false, false) => &mut self.screen.buffer,
 			};
 
 			self.screen.draw_vline(self.constr.session.x - 1, self.constr.session.y + h, task.height() as u16)?;
 			self.screen.draw_vline(self.constr.due_date.x - 1, self.constr.due_date.y + h, task.height() as u16)?;
-			self.screen.stdout.queue(ResetColor)?;
+			self.screen.buffer.queue(ResetColor)?;
 
 			h += task.height() as u16;
 		}
@@ -432,15 +451,15 @@ fn color_from_hsv(hue: f64, saturation: f64, value: f64) -> Color {
 	let x = c * (1.0 - (h % 2.0 - 1.0).abs());
 	let m = value - c;
 
-	let (red, green, blue) = if h >= 0.0 && h < 1.0 {
+	let (red, green, blue) = if (0.0..1.0).contains(&h) {
 		(c, x, 0.0)
-	} else if h >= 1.0 && h < 2.0 {
+	} else if (1.0..2.0).contains(&h) {
 		(x, c, 0.0)
-	} else if h >= 2.0 && h < 3.0 {
+	} else if (2.0..3.0).contains(&h) {
 		(0.0, c, x)
-	} else if h >= 3.0 && h < 4.0 {
+	} else if (3.0..4.0).contains(&h) {
 		(0.0, x, c)
-	} else if h >= 4.0 && h < 5.0 {
+	} else if (4.0..5.0).contains(&h) {
 		(x, 0.0, c)
 	} else {
 		(c, 0.0, x)
@@ -452,3 +471,53 @@ fn color_from_hsv(hue: f64, saturation: f64, value: f64) -> Color {
 		b: ((blue + m) * 255.0) as u8,
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use crossterm::style::Color;
+	use crate::node::{wrap_text, Node, Priority};
+	use crate::ui::{BufPrint, Screen};
+	use crate::ui::backend::MemoryBackend;
+	use super::TreeView;
+
+	fn node(id: u64, pid: u64, depth: usize, name: &'static str, priority: Priority, due_date_text: &str) -> Node<'static> {
+		Node {
+			id,
+			pid,
+			depth,
+			name: name.into(),
+			done: false,
+			due_date: None,
+			session: None,
+			priority,
+			score: 0.0,
+			name_splits: wrap_text(name, 10),
+			session_text: String::new(),
+			session_splits: wrap_text("", 10),
+			due_date_text: due_date_text.into(),
+			due_date_splits: wrap_text(due_date_text, 10),
+		}
+	}
+
+	#[test]
+	fn render() {
+		let flattree = vec![
+			node(0, 0, 0, "root", Priority::default(), ""),
+			node(1, 0, 1, "report", Priority { det: 0, total: 2 }, "Fri"),
+			node(2, 0, 1, "call mom", Priority { det: 1, total: 2 }, ""),
+		];
+		let mut view = TreeView::new(flattree, 40, 6);
+		view.cursor_down();
+		let mut screen = Screen::with_backend(MemoryBackend::new(40, 6));
+		screen.clear().unwrap().bufprint(&view).unwrap().flush().unwrap();
+		assert_eq!(screen.backend().lines(), [
+			"",
+			" •root              │             │",
+			" ├─•report          │             │Fri",
+			" └─•call mom        │             │",
+			"",
+			"",
+		]);
+		assert_eq!(screen.backend().cell(4, 2).unwrap().style.bg, Some(Color::White));
+	}
+}
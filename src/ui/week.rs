@@ -0,0 +1,184 @@
+use std::io;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use crossterm::style::{Attribute, Color, Colors, ResetColor, SetAttribute, SetColors};
+use grus_lib::types::Session;
+use crate::clock::Clock;
+use crate::node::TimeFormat;
+use super::{Backend, BufPrint, Rect, Screen, WeekViewConstraints};
+
+/// A grid of the sessions of one week, with a column for each day from Monday on and a row for
+/// each hour. The cursor is an hour of a day, and a range of hours can be selected from it.
+pub struct WeekView<'c> {
+	/// Tells which day is today, which is highlighted.
+	clock: &'c dyn Clock,
+	time_format: TimeFormat,
+	blocks: Vec<Block>,
+	cursor: NaiveDateTime,
+	anchor: Option<NaiveDateTime>,
+	start_hour: u32,
+	pub constr: WeekViewConstraints,
+}
+
+pub struct Block {
+	pub id: u64,
+	pub name: String,
+	pub session: Session,
+}
+
+impl<'c> WeekView<'c> {
+	/// Starts with the cursor on the current hour.
+	pub fn new(clock: &'c dyn Clock, time_format: TimeFormat, w: u16, h: u16) -> Self {
+		let now = clock.now();
+		let mut wv = WeekView {
+			clock,
+			time_format,
+			blocks: Vec::new(),
+			cursor: now.date().and_hms_opt(now.hour(), 0, 0).unwrap_or(now),
+			anchor: None,
+			start_hour: 0,
+			constr: WeekViewConstraints::new(w, h),
+		};
+		wv.scroll();
+		wv
+	}
+
+	/// Returns the first day of the week under the cursor.
+	pub fn week(&self) -> NaiveDate {
+		let date = self.cursor.date();
+		date - Duration::days(date.weekday().num_days_from_monday().into())
+	}
+
+	/// Replaces the sessions of the week, which are sorted by start time.
+	pub fn reset(&mut self, blocks: Vec<Block>) {
+		self.blocks = blocks;
+		self.scroll();
+	}
+
+	pub fn cursor(&self) -> NaiveDateTime {
+		self.cursor
+	}
+
+	/// Moves the cursor to the hour which contains the given time.
+	pub fn set_cursor(&mut self, time: NaiveDateTime) {
+		self.cursor = time.date().and_hms_opt(time.hour(), 0, 0).unwrap_or(time);
+		self.scroll();
+	}
+
+	pub fn cursor_up(&mut self) {
+		if self.cursor.hour() > 0 { self.set_cursor(self.cursor - Duration::hours(1)) };
+	}
+
+	pub fn cursor_down(&mut self) {
+		if self.cursor.hour() < 23 { self.set_cursor(self.cursor + Duration::hours(1)) };
+	}
+
+	pub fn cursor_left(&mut self) {
+		self.set_cursor(self.cursor - Duration::days(1));
+	}
+
+	pub fn cursor_right(&mut self) {
+		self.set_cursor(self.cursor + Duration::days(1));
+	}
+
+	/// Starts a selection at the cursor, or clears the selection if there is one.
+	pub fn toggle_selection(&mut self) {
+		self.anchor = match self.anchor {
+			Some(_) => None,
+			None => Some(self.cursor),
+		};
+	}
+
+	/// Returns the hours from the start of the selection to the cursor, or the hour under the
+	/// cursor if nothing is selected.
+	pub fn selection(&self) -> Session {
+		let anchor = self.anchor.unwrap_or(self.cursor);
+		Session {
+			start: anchor.min(self.cursor),
+			end: anchor.max(self.cursor) + Duration::hours(1),
+		}
+	}
+
+	pub fn clear_selection(&mut self) {
+		self.anchor = None;
+	}
+
+	/// Returns the earliest session which overlaps the hour under the cursor.
+	pub fn block(&self) -> Option<&Block> {
+		self.block_at(self.cursor)
+	}
+
+	fn block_at(&self, hour: NaiveDateTime) -> Option<&Block> {
+		let end = hour + Duration::hours(1);
+		self.blocks.iter().find(|block| block.session.start < end && block.session.end > hour)
+	}
+
+	fn is_selected(&self, hour: NaiveDateTime) -> bool {
+		let selection = self.selection();
+		self.anchor.is_some() && selection.start <= hour && hour < selection.end
+	}
+
+	fn scroll(&mut self) {
+		let rows = u32::from(self.constr.hours.h).min(24);
+		let hour = self.cursor.hour();
+		if hour < self.start_hour { self.start_hour = hour };
+		if hour >= self.start_hour + rows { self.start_hour = hour + 1 - rows };
+		self.start_hour = self.start_hour.min(24 - rows);
+	}
+}
+
+impl<B: Backend> BufPrint<WeekView<'_>> for Screen<B> {
+	fn bufprint(&mut self, view: &WeekView) -> io::Result<&mut Self> {
+		let constr = &view.constr;
+		let week = view.week();
+		let today = view.clock.today();
+		let rows = u32::from(constr.hours.h).min(24);
+		let header = Rect { y: 0, h: 1, ..constr.hours };
+		self.print_clipped(&header, 0, &week.format("%b").to_string())?;
+		for hour in view.start_hour..view.start_hour + rows {
+			let dy = (hour - view.start_hour) as u16;
+			self.print_clipped(&constr.hours, dy, &format!("{:>5}", view.time_format.format(NaiveTime::from_hms_opt(hour, 0, 0).unwrap_or_default())))?;
+		}
+
+		for day in 0..7 {
+			let date = week + Duration::days(day);
+			let column = constr.day(day as u16);
+			self.draw_vline(column.x - 1, 0, column.h + 1)?;
+
+			if date == today { self.buffer.queue(SetAttribute(Attribute::Bold))?; }
+			self.print_clipped(&Rect { y: 0, h: 1, ..column }, 0, &date.format("%a %-d").to_string())?;
+			self.buffer.queue(SetAttribute(Attribute::Reset))?;
+
+			for hour in view.start_hour..view.start_hour + rows {
+				let dy = (hour - view.start_hour) as u16;
+				let time = date.and_hms_opt(hour, 0, 0).unwrap_or_default();
+				let block = view.block_at(time);
+				let colors = if time == view.cursor {
+					Colors::new(Color::Black, Color::White)
+				} else if view.is_selected(time) {
+					Colors::new(Color::Black, Color::DarkYellow)
+				} else if block.is_some() && block.map(|b| b.session) == view.block().map(|b| b.session) {
+					Colors::new(Color::Black, Color::Cyan)
+				} else if block.is_some() {
+					Colors::new(Color::Black, Color::DarkCyan)
+				} else {
+					continue;
+				};
+				self.paint(Rect { y: column.y + dy, h: 1, ..column }, colors)?;
+
+				// A block is labelled with the name of its task on its first visible hour of the
+				// day and with its times on the next.
+				let Some(block) = block else { continue };
+				let first = block.session.start.max(date.and_hms_opt(view.start_hour, 0, 0).unwrap_or_default());
+				let label = match hour.checked_sub(first.hour()) {
+					Some(0) => block.name.clone(),
+					Some(1) => format!("{}-{}", view.time_format.format(block.session.start.time()), view.time_format.format(block.session.end.time())),
+					_ => continue,
+				};
+				self.buffer.queue(SetColors(colors))?;
+				self.print_clipped(&column, dy, &label)?;
+				self.buffer.queue(ResetColor)?;
+			}
+		}
+		Ok(self)
+	}
+}
@@ -0,0 +1,226 @@
+use std::io;
+use chrono::Duration;
+use crossterm::event::{self, KeyCode, Event};
+use grus_lib::Store;
+use crate::app::{Action, Error, View};
+use crate::clock::Clock;
+use crate::history::{Checkpoint, History};
+use crate::keymap::{Keymap, WeekAction};
+use crate::node::TimeFormat;
+use crate::meta::Meta;
+use crate::search::Index;
+use crate::snapshot::Snapshot;
+use crate::ui::{BufPrint, Screen};
+use crate::ui::backend::Backend;
+use crate::ui::search::SearchView;
+use crate::ui::status::{CommandType, Mode, StatusView};
+use crate::ui::week::{Block, WeekView};
+
+pub struct WeekViewController<'c> {
+	clock: &'c dyn Clock,
+	week_view: WeekView<'c>,
+	search_view: SearchView,
+	index: Option<Index>,
+	status_view: StatusView<{View::Week as usize}>,
+	keymap: Keymap<WeekAction>,
+}
+
+impl<'c> WeekViewController<'c> {
+	pub fn new(clock: &'c dyn Clock, time_format: TimeFormat, keymap: Keymap<WeekAction>, (w, h): (u16, u16)) -> Result<Self, Error> {
+		Ok(WeekViewController {
+			clock,
+			week_view: WeekView::new(clock, time_format, w, h),
+			search_view: SearchView::new(w, h),
+			index: None,
+			status_view: StatusView::new(w, h),
+			keymap,
+		})
+	}
+
+	pub fn run(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<Action, Error> {
+		self.status_view.clear_message();
+		match event::read()? {
+			Event::Key(kev) => match self.status_view.mode {
+				Mode::Normal => match self.keymap.feed(kev) {
+					Some(WeekAction::Quit) => return Ok(Action::Quit),
+					Some(WeekAction::CursorDown) => self.week_view.cursor_down(),
+					Some(WeekAction::CursorUp) => self.week_view.cursor_up(),
+					Some(WeekAction::CursorLeft) => self.move_cursor(store, WeekView::cursor_left)?,
+					Some(WeekAction::CursorRight) => self.move_cursor(store, WeekView::cursor_right)?,
+					Some(WeekAction::NextWeek) => self.move_cursor(store, |wv| wv.set_cursor(wv.cursor() + Duration::weeks(1)))?,
+					Some(WeekAction::PreviousWeek) => self.move_cursor(store, |wv| wv.set_cursor(wv.cursor() - Duration::weeks(1)))?,
+					Some(WeekAction::Today) => {
+						let now = self.clock.now();
+						self.move_cursor(store, |wv| wv.set_cursor(now))?;
+					}
+					Some(WeekAction::NextSession) => self.next_session(store)?,
+					Some(WeekAction::PreviousSession) => self.previous_session(store)?,
+					Some(WeekAction::Select) => self.week_view.toggle_selection(),
+					Some(WeekAction::AddSession) => self.enter_search(store)?,
+					Some(WeekAction::Delete) => self.delete(store, meta, history)?,
+					Some(WeekAction::ViewSessions) => if let Some(block) = self.week_view.block() {
+						return Ok(Action::TaskSessions(block.id));
+					},
+					Some(WeekAction::Undo) => return Ok(Action::Undo),
+					Some(WeekAction::Redo) => return Ok(Action::Redo),
+					Some(WeekAction::SwitchTree) => return Ok(Action::Switch(View::Tree)),
+					Some(WeekAction::SwitchSession) => return Ok(Action::Switch(View::Session)),
+					Some(WeekAction::SwitchAgenda) => return Ok(Action::Switch(View::Agenda)),
+					Some(WeekAction::SwitchReport) => return Ok(Action::Switch(View::Report)),
+					None => {},
+				}
+				Mode::Command(_) => {
+					match kev.code {
+						KeyCode::Enter => self.add_session(store, meta, history)?,
+						KeyCode::Char(c) => self.status_view.insert(c),
+						KeyCode::Backspace => self.status_view.delete(),
+						KeyCode::Left => self.status_view.move_left(),
+						KeyCode::Right => self.status_view.move_right(),
+						KeyCode::Up => self.search_view.cursor_up(),
+						KeyCode::Down => self.search_view.cursor_down(),
+						KeyCode::Esc => self.cancel(),
+						_ => {},
+					}
+					if let KeyCode::Char(_) | KeyCode::Backspace = kev.code {
+						self.rank_search_results();
+					}
+				}
+			}
+			Event::Resize(w, h) => {
+				self.resize(w, h);
+				self.update_week_view(store)?;
+			}
+			_ => {}
+		}
+		Ok(Action::None)
+	}
+
+	pub fn set_message(&mut self, message: String) {
+		self.status_view.set_message(message);
+	}
+
+	pub fn set_info(&mut self, info: String) {
+		self.status_view.set_info(info);
+	}
+
+	pub fn resize(&mut self, w: u16, h: u16) {
+		self.week_view.constr.update(w, h);
+		self.search_view.constr.update(w, h);
+		self.status_view.constr.update(w, h);
+	}
+
+	/// Reads the sessions which overlap the week under the cursor.
+	pub fn update_week_view(&mut self, store: &Store) -> Result<(), Error> {
+		let reader = store.reader()?;
+		let start = self.week_view.week().and_hms_opt(0, 0, 0).unwrap_or_default();
+		let end = start + Duration::weeks(1);
+		let mut blocks = Vec::new();
+		for entry in reader.all_sessions()? {
+			let (&session, &id) = entry?;
+			if session.start >= end { break };
+			if session.end <= start { continue };
+			let Some(name) = reader.name(id)? else { continue };
+			blocks.push(Block { id, name: name.into(), session });
+		}
+		self.week_view.reset(blocks);
+		Ok(())
+	}
+
+	fn move_cursor(&mut self, store: &Store, f: impl FnOnce(&mut WeekView<'c>)) -> Result<(), Error> {
+		let week = self.week_view.week();
+		f(&mut self.week_view);
+		if self.week_view.week() != week { self.update_week_view(store)? };
+		Ok(())
+	}
+
+	/// Moves the cursor to the start of the first session which starts after the hour under the
+	/// cursor, in any week.
+	fn next_session(&mut self, store: &Store) -> Result<(), Error> {
+		let after = self.week_view.cursor() + Duration::hours(1);
+		let reader = store.reader()?;
+		for entry in reader.all_sessions()? {
+			let (session, _) = entry?;
+			if session.start >= after {
+				let start = session.start;
+				return self.move_cursor(store, |wv| wv.set_cursor(start));
+			}
+		}
+		Ok(())
+	}
+
+	fn previous_session(&mut self, store: &Store) -> Result<(), Error> {
+		let before = self.week_view.cursor();
+		let reader = store.reader()?;
+		let mut previous = None;
+		for entry in reader.all_sessions()? {
+			let (session, _) = entry?;
+			if session.start >= before { break };
+			previous = Some(session.start);
+		}
+		match previous {
+			Some(start) => self.move_cursor(store, |wv| wv.set_cursor(start)),
+			None => Ok(()),
+		}
+	}
+
+	fn delete(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<(), Error> {
+		let Some(&Block { id, session, .. }) = self.week_view.block() else { return Ok(()) };
+
+		let checkpoint = Checkpoint::read(store, meta)?;
+		let mut writer = store.writer()?;
+		writer.delete_session(id, &session)?;
+		writer.commit()?;
+		history.record(checkpoint, store, meta)?;
+
+		self.update_week_view(store)?;
+		Ok(())
+	}
+
+	/// Reads the names of all tasks, one of which is given the selected hours as a session.
+	fn enter_search(&mut self, store: &Store) -> Result<(), Error> {
+		self.index = Some(Index::new(&Snapshot::read(&store.reader()?)?));
+		self.search_view.reset(Vec::new());
+		self.status_view.mode = Mode::Command(CommandType::AddSession);
+		Ok(())
+	}
+
+	fn rank_search_results(&mut self) {
+		let Some(index) = &self.index else { return };
+		self.search_view.reset(index.items(self.status_view.input()));
+	}
+
+	fn add_session(&mut self, store: &Store, meta: &Meta, history: &mut History) -> Result<(), Error> {
+		let Some(&id) = self.search_view.cursor_path().and_then(<[u64]>::last) else { return Ok(()) };
+		let session = self.week_view.selection();
+
+		let checkpoint = Checkpoint::read(store, meta)?;
+		let mut writer = store.writer()?;
+		writer.add_session(id, &session)?;
+		writer.commit()?;
+		history.record(checkpoint, store, meta)?;
+
+		self.week_view.clear_selection();
+		self.update_week_view(store)?;
+		self.cancel();
+		Ok(())
+	}
+
+	fn cancel(&mut self) {
+		self.index = None;
+		self.status_view.clear();
+		self.status_view.mode = Mode::Normal;
+	}
+}
+
+impl<B: Backend> BufPrint<WeekViewController<'_>> for Screen<B> {
+	fn bufprint(&mut self, wvc: &WeekViewController) -> io::Result<&mut Self> {
+		self.clear()?.bufprint(&wvc.status_view)?;
+		if let Mode::Command(_) = wvc.status_view.mode {
+			self.bufprint(&wvc.search_view)?;
+		} else {
+			self.bufprint(&wvc.week_view)?;
+		}
+		self.flush()?;
+		Ok(self)
+	}
+}